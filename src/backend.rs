@@ -0,0 +1,209 @@
+use crate::diagnostics::{report, report_diagnostics, report_warnings, Diagnostic};
+use crate::parser::{Parsed, Parser};
+use crate::repl;
+use crate::resolver::{Locals, Resolver};
+use crate::result::{Error, Warning};
+use crate::scanner::Scanner;
+use crate::statement::Statement;
+use crate::token::Span;
+use serde::{Deserialize, Serialize};
+
+/// Something that can run Lox source code, either by walking the AST or by compiling to bytecode.
+pub trait Backend {
+    /// Runs code that was analyzed without errors and returns what a top-level `return` returned,
+    /// shown with [`Object::repr`](crate::Object::repr). Code that runs to the end returns `nil`.
+    fn run_for_value(&mut self, analysis: Analysis) -> Result<String, Vec<Error>>;
+
+    /// Runs code that was analyzed without errors.
+    fn run(&mut self, analysis: Analysis) -> Result<(), Vec<Error>> {
+        self.run_for_value(analysis).map(drop)
+    }
+
+    /// The global variables and their values, shown with [`Object::repr`](crate::Object::repr)
+    /// and sorted by name.
+    fn globals(&self) -> Vec<(String, String)>;
+
+    /// The variables in each scope of the current environment, innermost scope first and the
+    /// global scope last. Between runs, only the global scope is left.
+    fn environment(&self) -> Vec<Vec<(String, String)>> {
+        vec![self.globals()]
+    }
+
+    /// Forgets everything that was defined, leaving only the builtins.
+    fn reset(&mut self);
+
+    /// Runs source code whose first line is `line_number`.
+    fn eval_from_line(&mut self, source: &str, line_number: usize) -> Result<(), Vec<Error>> {
+        self.run(analyze(source, line_number)?)
+    }
+
+    fn repl(&mut self) -> Result<(), Error> {
+        repl::run(self)
+    }
+
+    /// Returns the exit code of the first error, or success if there were none.
+    fn run_file(&mut self, path: String) -> Result<i32, Error> {
+        let source = std::fs::read_to_string(&path)?;
+        let exit_code = match self.run_and_report(&path, &source, 1) {
+            Ok(()) => exitcode::OK,
+            Err(errors) => errors[0].exit_code(),
+        };
+        Ok(exit_code)
+    }
+
+    /// Runs a file holding a syntax tree in the JSON format of [`dump_syntax_tree`]. There is no
+    /// source to show, so errors and warnings only point to a line and column.
+    /// Returns the exit code of the first error, or success if there were none.
+    fn run_syntax_tree_file(&mut self, path: String) -> Result<i32, Error> {
+        let json = std::fs::read_to_string(&path)?;
+        let result = analyze_syntax_tree(&json).and_then(|analysis| {
+            report_diagnostics(
+                &path,
+                None,
+                analysis.warnings().iter().map(Diagnostic::from),
+            );
+            self.run(analysis)
+        });
+        match result {
+            Ok(()) => Ok(exitcode::OK),
+            Err(errors) => {
+                report_diagnostics(&path, None, errors.iter().map(Diagnostic::from));
+                Ok(errors[0].exit_code())
+            }
+        }
+    }
+
+    /// Runs the source, printing its warnings before it runs and its errors after. The errors
+    /// are also returned.
+    fn run_and_report(
+        &mut self,
+        file_name: &str,
+        source: &str,
+        line_number: usize,
+    ) -> Result<(), Vec<Error>> {
+        let result = analyze(source, line_number).and_then(|analysis| {
+            report_warnings(file_name, source, analysis.warnings());
+            self.run(analysis)
+        });
+        if let Err(errors) = &result {
+            report(file_name, source, errors);
+        }
+        result
+    }
+}
+
+/// Source code that was scanned, parsed and resolved without errors, ready to run.
+pub struct Analysis {
+    pub(crate) statements: Vec<Statement>,
+    pub(crate) locals: Locals,
+    warnings: Vec<Warning>,
+}
+
+impl Analysis {
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Turns a final expression statement into a top-level `return`, so running the code returns
+    /// its value. Returns whether there was such a statement.
+    pub fn return_last_expression(&mut self) -> bool {
+        let Some(Statement::Expression(_)) = self.statements.last() else {
+            return false;
+        };
+        let Some(Statement::Expression(expression)) = self.statements.pop() else {
+            unreachable!("the last statement was just checked");
+        };
+        self.statements.push(Statement::Return {
+            expression: Some(expression),
+            span: Span::default(),
+        });
+        true
+    }
+}
+
+/// Scans, parses and resolves the source.
+pub fn analyze(source: &str, line_number: usize) -> Result<Analysis, Vec<Error>> {
+    analyze_statements(parse(source, line_number)?)
+}
+
+/// The version of the JSON format of syntax trees. It changes whenever trees written for an older
+/// version would no longer load, so the names of nodes, operators and fields are part of it.
+pub const SYNTAX_TREE_VERSION: u32 = 1;
+
+/// The top level of the JSON format: the version it was written for and the statements.
+#[derive(Serialize, Deserialize)]
+struct SyntaxTree<S> {
+    version: u32,
+    statements: S,
+}
+
+/// Parses the source and serializes its syntax tree as JSON, for tools that inspect programs.
+pub fn dump_syntax_tree(source: &str) -> Result<String, Vec<Error>> {
+    let tree = SyntaxTree {
+        version: SYNTAX_TREE_VERSION,
+        statements: parse(source, 1)?,
+    };
+    Ok(serde_json::to_string_pretty(&tree).expect("syntax trees can always be serialized"))
+}
+
+/// Resolves a syntax tree in the JSON format of [`dump_syntax_tree`], so it can be run without
+/// its source. Spans can be left out, for trees that tools build instead of parsing.
+pub fn analyze_syntax_tree(json: &str) -> Result<Analysis, Vec<Error>> {
+    let invalid = |message: String| vec![Error::InvalidSyntaxTree { message }];
+    // The version is checked first, so a tree from another version isn't reported as malformed.
+    let tree: SyntaxTree<serde_json::Value> =
+        serde_json::from_str(json).map_err(|error| invalid(error.to_string()))?;
+    if tree.version != SYNTAX_TREE_VERSION {
+        return Err(invalid(format!(
+            "version {} is not supported, only version {SYNTAX_TREE_VERSION} is",
+            tree.version
+        )));
+    }
+    let statements =
+        serde_json::from_value(tree.statements).map_err(|error| invalid(error.to_string()))?;
+    analyze_statements(statements)
+}
+
+fn analyze_statements(statements: Vec<Statement>) -> Result<Analysis, Vec<Error>> {
+    let (errors, locals, warnings) = resolve(&statements);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(Analysis {
+        statements,
+        locals,
+        warnings,
+    })
+}
+
+/// Scans and parses the source, recovering from syntax errors to report all of them. The
+/// statements around the errors are kept, for tools that look at code which doesn't run yet.
+pub fn parse_syntax_tree(source: &str) -> Parsed {
+    parse_from_line(source, 1)
+}
+
+/// Scans and parses the source.
+pub(crate) fn parse(source: &str, line_number: usize) -> Result<Vec<Statement>, Vec<Error>> {
+    parse_from_line(source, line_number).into_result()
+}
+
+fn parse_from_line(source: &str, line_number: usize) -> Parsed {
+    match Scanner::new(source, line_number).scan_tokens() {
+        Ok(tokens) => Parser::new(tokens).parse(),
+        Err(error) => Parsed {
+            statements: Vec::new(),
+            errors: vec![error],
+        },
+    }
+}
+
+/// Resolves every statement, returning the errors and the warnings along with the slots.
+pub(crate) fn resolve(statements: &[Statement]) -> (Vec<Error>, Locals, Vec<Warning>) {
+    let mut resolver = Resolver::new();
+    let errors = statements
+        .iter()
+        .filter_map(|statement| resolver.resolve_statement(statement).err())
+        .collect::<Vec<_>>();
+    let (locals, warnings) = resolver.finish();
+    (errors, locals, warnings)
+}
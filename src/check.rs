@@ -0,0 +1,248 @@
+//! Finds the errors and warnings in files without running them, for linting many files at once.
+
+use crate::backend::{parse_syntax_tree, resolve};
+use crate::diagnostics::{report_diagnostics, Diagnostic};
+use crate::parser::Parsed;
+use crate::result::{Error, Warning};
+use serde::Serialize;
+
+/// The errors and warnings in source code.
+#[derive(Debug, Default)]
+pub struct Checked {
+    pub errors: Vec<Error>,
+    pub warnings: Vec<Warning>,
+}
+
+/// Scans, parses and resolves the source. With syntax errors, the statements around them are
+/// still resolved for more errors, but warnings are left out: a variable can look unused when the
+/// statement reading it didn't parse.
+pub fn check(source: &str) -> Checked {
+    let Parsed { statements, errors } = parse_syntax_tree(source);
+    let (resolver_errors, _, warnings) = resolve(&statements);
+    if errors.is_empty() {
+        return Checked {
+            errors: resolver_errors,
+            warnings,
+        };
+    }
+    Checked {
+        errors: errors.into_iter().chain(resolver_errors).collect(),
+        warnings: Vec::new(),
+    }
+}
+
+/// What checking one file found.
+pub struct FileCheck {
+    pub path: String,
+    /// The contents of the file, unless it couldn't be read.
+    pub source: Option<String>,
+    pub checked: Checked,
+}
+
+impl FileCheck {
+    /// Errors and warnings in the order they appear in the file.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = self
+            .checked
+            .errors
+            .iter()
+            .map(Diagnostic::from)
+            .chain(self.checked.warnings.iter().map(Diagnostic::from))
+            .collect::<Vec<_>>();
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| span.start));
+        diagnostics
+    }
+}
+
+pub fn check_files(paths: &[String]) -> Vec<FileCheck> {
+    paths
+        .iter()
+        .map(|path| match std::fs::read_to_string(path) {
+            Ok(source) => FileCheck {
+                path: path.clone(),
+                checked: check(&source),
+                source: Some(source),
+            },
+            Err(error) => FileCheck {
+                path: path.clone(),
+                source: None,
+                checked: Checked {
+                    errors: vec![error.into()],
+                    warnings: Vec::new(),
+                },
+            },
+        })
+        .collect()
+}
+
+/// The exit code of the first error, or success if there are only warnings.
+pub fn exit_code(checks: &[FileCheck]) -> i32 {
+    checks
+        .iter()
+        .flat_map(|check| &check.checked.errors)
+        .map(Error::exit_code)
+        .next()
+        .unwrap_or(exitcode::OK)
+}
+
+/// Prints the diagnostics like running the files would, followed by a summary.
+pub fn print_human(checks: &[FileCheck]) {
+    for check in checks {
+        report_diagnostics(&check.path, check.source.as_deref(), check.diagnostics());
+    }
+    println!("{}", summary(checks));
+}
+
+fn summary(checks: &[FileCheck]) -> String {
+    let errors = checks.iter().map(|check| check.checked.errors.len()).sum();
+    let warnings = checks
+        .iter()
+        .map(|check| check.checked.warnings.len())
+        .sum();
+    format!(
+        "Checked {}: {}, {}.",
+        plural(checks.len(), "file"),
+        plural(errors, "error"),
+        plural(warnings, "warning")
+    )
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+/// A diagnostic with the file it is about, as it appears in the JSON output.
+#[derive(Serialize)]
+struct FileDiagnostic<'a> {
+    file: &'a str,
+    #[serde(flatten)]
+    diagnostic: Diagnostic,
+}
+
+/// All the diagnostics as a JSON array, with a `file` field added to each.
+pub fn to_json(checks: &[FileCheck]) -> String {
+    let diagnostics = checks
+        .iter()
+        .flat_map(|check| {
+            check
+                .diagnostics()
+                .into_iter()
+                .map(|diagnostic| FileDiagnostic {
+                    file: &check.path,
+                    diagnostic,
+                })
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_string_pretty(&diagnostics).expect("diagnostics can always be serialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches;
+
+    fn file(path: &str, source: &str) -> FileCheck {
+        FileCheck {
+            path: path.to_string(),
+            checked: check(source),
+            source: Some(source.to_string()),
+        }
+    }
+
+    #[test]
+    fn valid_code_has_nothing_to_report() {
+        let checked = check("var a = 1; print a;");
+        assert!(checked.errors.is_empty());
+        assert!(checked.warnings.is_empty());
+    }
+
+    #[test]
+    fn code_is_not_run() {
+        let checked = check("print 1 + nil; undefined();");
+        assert!(checked.errors.is_empty());
+    }
+
+    #[test]
+    fn resolver_errors_come_with_warnings() {
+        let checked = check("{ var unused; }\nbreak;");
+        assert_matches!(checked.errors.as_slice(), [Error::BreakOutsideLoop { .. }]);
+        assert_matches!(
+            checked.warnings.as_slice(),
+            [Warning::UnusedVariable { .. }]
+        );
+    }
+
+    #[test]
+    fn every_syntax_error_is_reported() {
+        let checked = check("print (1;\nprint 2");
+        assert_eq!(checked.errors.len(), 2);
+        assert!(checked.warnings.is_empty());
+    }
+
+    #[test]
+    fn statements_around_syntax_errors_are_resolved() {
+        let checked = check("{ var unused; }\nprint (1;\nbreak;");
+        assert_matches!(
+            checked.errors.as_slice(),
+            [
+                Error::UnmatchedParenthesis { .. },
+                Error::BreakOutsideLoop { .. }
+            ]
+        );
+        assert!(checked.warnings.is_empty());
+    }
+
+    #[test]
+    fn diagnostics_are_in_source_order() {
+        let check = file("a.lox", "{ var unused; }\nbreak;\n{ var other; }");
+        let codes = check
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, ["W0200", "E0205", "W0200"]);
+    }
+
+    #[test]
+    fn exit_code_fails_only_on_errors() {
+        let warning = file("a.lox", "{ var unused; }");
+        let error = file("b.lox", "print;");
+        assert_eq!(exit_code(&[]), exitcode::OK);
+        assert_eq!(exit_code(&[warning]), exitcode::OK);
+        let warning = file("a.lox", "{ var unused; }");
+        assert_eq!(exit_code(&[warning, error]), exitcode::USAGE);
+    }
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let checks = check_files(&["does/not/exist.lox".to_string()]);
+        assert_eq!(exit_code(&checks), exitcode::IOERR);
+    }
+
+    #[test]
+    fn summary_counts_files_errors_and_warnings() {
+        let checks = [file("a.lox", "{ var unused; }"), file("b.lox", "print;")];
+        assert_eq!(summary(&checks), "Checked 2 files: 1 error, 1 warning.");
+    }
+
+    #[test]
+    fn json_has_one_object_per_diagnostic() {
+        let json = to_json(&[file("a.lox", "{ var unused; }")]);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([{
+                "file": "a.lox",
+                "severity": "warning",
+                "code": "W0200",
+                "message": "Local variable 'unused' is never read.",
+                "span": { "line": 1, "column": 7, "start": 6, "end": 12 },
+                "notes": ["start the name with '_' if this is intentional"],
+            }])
+        );
+    }
+}
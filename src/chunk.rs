@@ -0,0 +1,213 @@
+use crate::number::Number;
+use crate::token::Span;
+use std::rc::Rc;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetUpvalue,
+    SetUpvalue,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Closure,
+    CloseUpvalue,
+    Return,
+    Class,
+    Inherit,
+    Method,
+    List,
+    ListAppend,
+    GetIndex,
+    SetIndex,
+    Map,
+    MapInsert,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 44] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::GetSuper,
+        OpCode::Equal,
+        OpCode::NotEqual,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::Closure,
+        OpCode::CloseUpvalue,
+        OpCode::Return,
+        OpCode::Class,
+        OpCode::Inherit,
+        OpCode::Method,
+        OpCode::List,
+        OpCode::ListAppend,
+        OpCode::GetIndex,
+        OpCode::SetIndex,
+        OpCode::Map,
+        OpCode::MapInsert,
+    ];
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        OpCode::ALL.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+    Number(Number),
+    String(String),
+    Function(Rc<FunctionPrototype>),
+}
+
+/// A compiled function: its bytecode plus what the virtual machine needs to build a closure.
+#[derive(Debug, Default, PartialEq)]
+pub struct FunctionPrototype {
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+/// Bytecode with one span per byte, so runtime errors point to the same place as in the tree-walker.
+#[derive(Debug, Default, PartialEq)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub spans: Vec<Span>,
+    pub constants: Vec<Constant>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, byte: u8, span: Span) {
+        self.code.push(byte);
+        self.spans.push(span);
+    }
+
+    pub fn write_op(&mut self, op_code: OpCode, span: Span) {
+        self.write(op_code as u8, span);
+    }
+
+    pub fn write_u16(&mut self, value: u16, span: Span) {
+        let [high, low] = value.to_be_bytes();
+        self.write(high, span);
+        self.write(low, span);
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    pub fn patch_u16(&mut self, offset: usize, value: u16) {
+        let [high, low] = value.to_be_bytes();
+        self.code[offset] = high;
+        self.code[offset + 1] = low;
+    }
+
+    /// Returns the index of the constant, reusing an equal one if it is already in the pool.
+    pub fn add_constant(&mut self, constant: Constant) -> Option<u16> {
+        let existing = self.constants.iter().position(|existing| {
+            !matches!(existing, Constant::Function(_)) && *existing == constant
+        });
+        let index = match existing {
+            Some(index) => index,
+            None => {
+                self.constants.push(constant);
+                self.constants.len() - 1
+            }
+        };
+        u16::try_from(index).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn op_code_round_trip() {
+        for op_code in OpCode::ALL {
+            assert_eq!(OpCode::try_from(op_code as u8), Ok(op_code));
+        }
+        assert_eq!(OpCode::try_from(OpCode::ALL.len() as u8), Err(44));
+    }
+
+    #[test]
+    fn write_and_patch_u16() {
+        let mut chunk = Chunk::default();
+        chunk.write_u16(0x1234, Span::default());
+        assert_eq!(chunk.code, vec![0x12, 0x34]);
+        assert_eq!(chunk.spans.len(), 2);
+        chunk.patch_u16(0, 0xabcd);
+        assert_eq!(chunk.read_u16(0), 0xabcd);
+    }
+
+    #[test]
+    fn add_constant_reuses_equal_constants() {
+        let mut chunk = Chunk::default();
+        assert_eq!(
+            chunk.add_constant(Constant::String("a".to_string())),
+            Some(0)
+        );
+        assert_eq!(chunk.add_constant(Constant::Number(1.0.into())), Some(1));
+        assert_eq!(
+            chunk.add_constant(Constant::String("a".to_string())),
+            Some(0)
+        );
+        assert_eq!(chunk.constants.len(), 2);
+    }
+}
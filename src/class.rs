@@ -0,0 +1,233 @@
+use crate::heap::{address, track, Address, Trace};
+use crate::interpreter::Interpreter;
+use crate::object::{Arity, Callable, Error, ErrorKind, Function, Object};
+use crate::token::Span;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+
+pub const INITIALIZER_NAME: &str = "init";
+
+#[derive(Clone)]
+pub struct Class(Rc<ClassInner>);
+
+struct ClassInner {
+    name: String,
+    superclass: Option<Class>,
+    methods: HashMap<String, Function>,
+}
+
+impl Class {
+    pub fn new(
+        name: String,
+        superclass: Option<Class>,
+        methods: HashMap<String, Function>,
+    ) -> Class {
+        let class = Rc::new(ClassInner {
+            name,
+            superclass,
+            methods,
+        });
+        track(&class);
+        Class(class)
+    }
+    pub fn address(&self) -> Address {
+        address(&self.0)
+    }
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+    pub fn find_method(&self, name: &str) -> Option<Function> {
+        if let Some(method) = self.0.methods.get(name) {
+            return Some(method.clone());
+        }
+        self.0.superclass.as_ref()?.find_method(name)
+    }
+}
+
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{}", self.name())
+    }
+}
+
+impl Debug for Class {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "Class({})", self.name())
+    }
+}
+
+impl Trace for ClassInner {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        if let Some(superclass) = &self.superclass {
+            visit(superclass.address());
+        }
+        for method in self.methods.values() {
+            method.trace(visit);
+        }
+    }
+}
+
+/// A class used as a function refers to the class itself.
+impl Trace for Class {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        visit(self.address());
+    }
+}
+
+impl Callable for Class {
+    fn signature(&self) -> String {
+        self.name().to_string()
+    }
+
+    fn arity(&self) -> Arity {
+        match self.find_method(INITIALIZER_NAME) {
+            Some(initializer) => initializer.arity(),
+            None => Arity::Fixed(0),
+        }
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+        span: Span,
+    ) -> Result<Object, Error> {
+        let instance = Instance::new(self.clone());
+        if let Some(initializer) = self.find_method(INITIALIZER_NAME) {
+            initializer
+                .bind(instance.clone())
+                .call(interpreter, arguments, span)?;
+        }
+        Ok(Object::Instance(instance))
+    }
+}
+
+#[derive(Clone)]
+pub struct Instance(Rc<RefCell<InstanceInner>>);
+
+struct InstanceInner {
+    class: Class,
+    fields: HashMap<String, Object>,
+}
+
+impl Instance {
+    pub fn new(class: Class) -> Instance {
+        let instance = Rc::new(RefCell::new(InstanceInner {
+            class,
+            fields: HashMap::new(),
+        }));
+        track(&instance);
+        Instance(instance)
+    }
+    pub fn address(&self) -> Address {
+        address(&self.0)
+    }
+    pub fn class(&self) -> Class {
+        self.0.borrow().class.clone()
+    }
+    pub fn get(&self, name: &str) -> Result<Object, ErrorKind> {
+        if let Some(value) = self.0.borrow().fields.get(name) {
+            return Ok(value.clone());
+        }
+        let Some(method) = self.class().find_method(name) else {
+            return Err(ErrorKind::UndefinedProperty {
+                name: name.to_string(),
+            });
+        };
+        Ok(Object::function(method.bind(self.clone())))
+    }
+    pub fn set(&self, name: String, value: Object) {
+        self.0.borrow_mut().fields.insert(name, value);
+    }
+}
+
+impl Trace for RefCell<InstanceInner> {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        let Ok(inner) = self.try_borrow() else {
+            return;
+        };
+        visit(inner.class.address());
+        for value in inner.fields.values() {
+            value.trace(visit);
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut inner) = self.try_borrow_mut() {
+            inner.fields.clear();
+        }
+    }
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Display for Instance {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{} instance", self.class().name())
+    }
+}
+
+impl Debug for Instance {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "Instance({})", self.class().name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches;
+
+    fn empty_class(name: &str, superclass: Option<Class>) -> Class {
+        Class::new(name.to_string(), superclass, HashMap::new())
+    }
+
+    #[test]
+    fn instance_set_and_get_field() {
+        let instance = Instance::new(empty_class("Point", None));
+        instance.set("x".to_string(), Object::Number(1.0.into()));
+        assert_eq!(instance.get("x").unwrap(), Object::Number(1.0.into()));
+    }
+
+    #[test]
+    fn instance_get_undefined_property() {
+        let instance = Instance::new(empty_class("Point", None));
+        assert_matches!(
+            instance.get("x").unwrap_err(),
+            ErrorKind::UndefinedProperty { .. }
+        );
+    }
+
+    #[test]
+    fn class_without_initializer_has_no_arity() {
+        assert_eq!(empty_class("Point", None).arity(), Arity::Fixed(0));
+    }
+
+    #[test]
+    fn class_identity() {
+        let class = empty_class("Point", None);
+        assert_eq!(class, class.clone());
+        assert_ne!(class, empty_class("Point", None));
+    }
+
+    #[test]
+    fn instance_identity() {
+        let class = empty_class("Point", None);
+        let instance = Instance::new(class.clone());
+        assert_eq!(instance, instance.clone());
+        assert_ne!(instance, Instance::new(class));
+    }
+}
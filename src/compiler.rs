@@ -0,0 +1,758 @@
+use crate::chunk::{Chunk, Constant, FunctionPrototype, OpCode};
+use crate::class::INITIALIZER_NAME;
+use crate::expression::{BinaryOperator, Expression, Literal, UnaryOperator};
+use crate::result::Error;
+use crate::statement::{ClassDeclaration, FunctionDeclaration, Statement};
+use crate::token::Span;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    is_captured: bool,
+}
+
+struct Upvalue {
+    index: u8,
+    is_local: bool,
+}
+
+enum Variable {
+    Local(u8),
+    Upvalue(u8),
+    Global(u16),
+}
+
+/// A loop being compiled, with the jumps of its `break` and `continue` statements, which are
+/// patched once the code they jump to is emitted.
+struct Loop {
+    scope_depth: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+struct FunctionState {
+    prototype: FunctionPrototype,
+    function_type: FunctionType,
+    locals: Vec<Local>,
+    upvalues: Vec<Upvalue>,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+}
+
+impl FunctionState {
+    fn new(name: String, function_type: FunctionType) -> FunctionState {
+        // Slot zero holds the callee, or the receiver for methods.
+        let slot_zero = match function_type {
+            FunctionType::Method | FunctionType::Initializer => "this",
+            FunctionType::Script | FunctionType::Function => "",
+        };
+        FunctionState {
+            prototype: FunctionPrototype {
+                name,
+                ..Default::default()
+            },
+            function_type,
+            locals: vec![Local {
+                name: slot_zero.to_string(),
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+        }
+    }
+}
+
+/// Compiles resolved statements into bytecode for the virtual machine.
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+    span: Span,
+}
+
+impl Compiler {
+    pub fn compile(statements: &[Statement]) -> Result<Rc<FunctionPrototype>, Error> {
+        let mut compiler = Compiler {
+            functions: vec![FunctionState::new(String::new(), FunctionType::Script)],
+            span: Span::default(),
+        };
+        for statement in statements {
+            compiler.statement(statement)?;
+        }
+        compiler.emit_op(OpCode::Nil);
+        compiler.emit_op(OpCode::Return);
+        let script = compiler.functions.pop().unwrap();
+        Ok(Rc::new(script.prototype))
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), Error> {
+        match statement {
+            Statement::Expression(expression) => {
+                self.expression(expression)?;
+                self.emit_op(OpCode::Pop);
+            }
+            Statement::If {
+                condition,
+                then_statement,
+                else_statement,
+            } => {
+                self.expression(condition)?;
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+                self.statement(then_statement)?;
+                let else_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(then_jump)?;
+                self.emit_op(OpCode::Pop);
+                if let Some(else_statement) = else_statement {
+                    self.statement(else_statement)?;
+                }
+                self.patch_jump(else_jump)?;
+            }
+            Statement::Print(expression) => {
+                self.expression(expression)?;
+                self.emit_op(OpCode::Print);
+            }
+            Statement::Return { expression, span } => {
+                self.span = *span;
+                if self.function().function_type == FunctionType::Initializer {
+                    self.emit_op(OpCode::GetLocal);
+                    self.emit_byte(0);
+                } else if let Some(expression) = expression {
+                    self.expression(expression)?;
+                } else {
+                    self.emit_op(OpCode::Nil);
+                }
+                self.emit_op(OpCode::Return);
+            }
+            Statement::VariableDeclaration {
+                identifier,
+                expression,
+                ..
+            } => {
+                match expression {
+                    Some(expression) => self.expression(expression)?,
+                    None => self.emit_op(OpCode::Nil),
+                }
+                self.define_variable(identifier)?;
+            }
+            Statement::FunctionDeclaration(declaration) => {
+                // Declared before the body is compiled, so the function can call itself.
+                if self.function().scope_depth > 0 {
+                    self.add_local(&declaration.identifier)?;
+                }
+                self.function_declaration(declaration, FunctionType::Function)?;
+                if self.function().scope_depth == 0 {
+                    let name = self.identifier_constant(&declaration.identifier)?;
+                    self.emit_op(OpCode::DefineGlobal);
+                    self.emit_u16(name);
+                }
+            }
+            Statement::ClassDeclaration(declaration) => self.class_declaration(declaration)?,
+            Statement::While {
+                expression,
+                statement,
+                increment,
+            } => {
+                let loop_start = self.chunk().code.len();
+                self.expression(expression)?;
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+                let scope_depth = self.function().scope_depth;
+                self.function_mut().loops.push(Loop {
+                    scope_depth,
+                    breaks: Vec::new(),
+                    continues: Vec::new(),
+                });
+                let body = self.statement(statement);
+                let Loop {
+                    breaks, continues, ..
+                } = self.function_mut().loops.pop().unwrap();
+                body?;
+                for jump in continues {
+                    self.patch_jump(jump)?;
+                }
+                if let Some(increment) = increment {
+                    self.expression(increment)?;
+                    self.emit_op(OpCode::Pop);
+                }
+                self.emit_loop(loop_start)?;
+                self.patch_jump(exit_jump)?;
+                self.emit_op(OpCode::Pop);
+                for jump in breaks {
+                    self.patch_jump(jump)?;
+                }
+            }
+            Statement::Break { span } | Statement::Continue { span } => {
+                self.span = *span;
+                let depth = self.function().loops.last().unwrap().scope_depth;
+                self.discard_locals(depth);
+                let jump = self.emit_jump(OpCode::Jump);
+                let innermost = self.function_mut().loops.last_mut().unwrap();
+                match statement {
+                    Statement::Break { .. } => innermost.breaks.push(jump),
+                    _ => innermost.continues.push(jump),
+                }
+            }
+            Statement::Block(statements) => {
+                self.begin_scope();
+                for statement in statements {
+                    self.statement(statement)?;
+                }
+                self.end_scope();
+            }
+        }
+        Ok(())
+    }
+
+    fn function_declaration(
+        &mut self,
+        declaration: &FunctionDeclaration,
+        function_type: FunctionType,
+    ) -> Result<(), Error> {
+        self.functions.push(FunctionState::new(
+            declaration.identifier.clone(),
+            function_type,
+        ));
+        self.begin_scope();
+        for parameter in &declaration.parameters {
+            self.add_local(&parameter.name)?;
+        }
+        self.function_mut().prototype.arity = declaration.parameters.len();
+        self.statement(&declaration.body)?;
+        if function_type == FunctionType::Initializer {
+            self.emit_op(OpCode::GetLocal);
+            self.emit_byte(0);
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.emit_op(OpCode::Return);
+
+        let FunctionState {
+            mut prototype,
+            upvalues,
+            ..
+        } = self.functions.pop().unwrap();
+        prototype.upvalue_count = upvalues.len();
+        let constant = self.make_constant(Constant::Function(Rc::new(prototype)))?;
+        self.emit_op(OpCode::Closure);
+        self.emit_u16(constant);
+        for upvalue in upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+        Ok(())
+    }
+
+    fn class_declaration(&mut self, declaration: &ClassDeclaration) -> Result<(), Error> {
+        let ClassDeclaration {
+            identifier,
+            superclass,
+            methods,
+            span,
+        } = declaration;
+        self.span = *span;
+        let name = self.identifier_constant(identifier)?;
+        self.emit_op(OpCode::Class);
+        self.emit_u16(name);
+        self.define_variable(identifier)?;
+
+        if let Some(superclass) = superclass {
+            self.expression(superclass)?;
+            self.begin_scope();
+            self.add_local("super")?;
+            self.named_variable(identifier)?;
+            self.span = *span;
+            self.emit_op(OpCode::Inherit);
+        }
+
+        self.named_variable(identifier)?;
+        for method in methods {
+            let function_type = if method.identifier == INITIALIZER_NAME {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.function_declaration(method, function_type)?;
+            let name = self.identifier_constant(&method.identifier)?;
+            self.emit_op(OpCode::Method);
+            self.emit_u16(name);
+        }
+        self.emit_op(OpCode::Pop);
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Result<(), Error> {
+        match expression {
+            Expression::Literal(literal) => match literal {
+                Literal::Number(number) => self.emit_constant(Constant::Number(*number))?,
+                Literal::String(string) => self.emit_constant(Constant::String(string.clone()))?,
+                Literal::True => self.emit_op(OpCode::True),
+                Literal::False => self.emit_op(OpCode::False),
+                Literal::Nil => self.emit_op(OpCode::Nil),
+            },
+            Expression::Unary {
+                operator,
+                expression,
+                span,
+            } => {
+                self.expression(expression)?;
+                self.span = *span;
+                match operator {
+                    UnaryOperator::Negation => self.emit_op(OpCode::Not),
+                    UnaryOperator::Minus => self.emit_op(OpCode::Negate),
+                }
+            }
+            Expression::Binary {
+                left,
+                operator: BinaryOperator::And,
+                right,
+                ..
+            } => {
+                self.expression(left)?;
+                let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+                self.expression(right)?;
+                self.patch_jump(end_jump)?;
+            }
+            Expression::Binary {
+                left,
+                operator: BinaryOperator::Or,
+                right,
+                ..
+            } => {
+                self.expression(left)?;
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                let end_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(else_jump)?;
+                self.emit_op(OpCode::Pop);
+                self.expression(right)?;
+                self.patch_jump(end_jump)?;
+            }
+            Expression::Binary {
+                left,
+                operator,
+                right,
+                span,
+            } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.span = *span;
+                self.emit_op(match operator {
+                    BinaryOperator::Equality => OpCode::Equal,
+                    BinaryOperator::Different => OpCode::NotEqual,
+                    BinaryOperator::Less => OpCode::Less,
+                    BinaryOperator::EqualOrLess => OpCode::LessEqual,
+                    BinaryOperator::Greater => OpCode::Greater,
+                    BinaryOperator::EqualOrGreater => OpCode::GreaterEqual,
+                    BinaryOperator::Addition => OpCode::Add,
+                    BinaryOperator::Subtraction => OpCode::Subtract,
+                    BinaryOperator::Multiplication => OpCode::Multiply,
+                    BinaryOperator::Division => OpCode::Divide,
+                    BinaryOperator::And | BinaryOperator::Or => unreachable!(),
+                });
+            }
+            Expression::Variable {
+                identifier, span, ..
+            } => {
+                self.span = *span;
+                self.named_variable(identifier)?;
+            }
+            Expression::Grouping(expression) => self.expression(expression)?,
+            Expression::Assignment {
+                identifier,
+                value,
+                span,
+                ..
+            } => {
+                self.expression(value)?;
+                self.span = *span;
+                match self.resolve_variable(identifier)? {
+                    Variable::Local(slot) => {
+                        self.emit_op(OpCode::SetLocal);
+                        self.emit_byte(slot);
+                    }
+                    Variable::Upvalue(index) => {
+                        self.emit_op(OpCode::SetUpvalue);
+                        self.emit_byte(index);
+                    }
+                    Variable::Global(name) => {
+                        self.emit_op(OpCode::SetGlobal);
+                        self.emit_u16(name);
+                    }
+                }
+            }
+            Expression::FunctionCall {
+                function,
+                arguments,
+                span,
+            } => {
+                self.expression(function)?;
+                for argument in arguments {
+                    self.expression(argument)?;
+                }
+                self.span = *span;
+                self.emit_op(OpCode::Call);
+                // The parser rejects calls with more than 255 arguments.
+                self.emit_byte(arguments.len() as u8);
+            }
+            Expression::Get { object, name, span } => {
+                self.expression(object)?;
+                self.span = *span;
+                let name = self.identifier_constant(name)?;
+                self.emit_op(OpCode::GetProperty);
+                self.emit_u16(name);
+            }
+            Expression::Set {
+                object,
+                name,
+                value,
+                span,
+            } => {
+                self.expression(object)?;
+                self.expression(value)?;
+                self.span = *span;
+                let name = self.identifier_constant(name)?;
+                self.emit_op(OpCode::SetProperty);
+                self.emit_u16(name);
+            }
+            Expression::List { elements, span } => {
+                self.span = *span;
+                self.emit_op(OpCode::List);
+                for element in elements {
+                    self.expression(element)?;
+                    self.emit_op(OpCode::ListAppend);
+                }
+            }
+            Expression::Map { entries, span } => {
+                self.span = *span;
+                self.emit_op(OpCode::Map);
+                for (key, value) in entries {
+                    self.expression(key)?;
+                    self.expression(value)?;
+                    self.span = *span;
+                    self.emit_op(OpCode::MapInsert);
+                }
+            }
+            Expression::Index {
+                object,
+                index,
+                span,
+            } => {
+                self.expression(object)?;
+                self.expression(index)?;
+                self.span = *span;
+                self.emit_op(OpCode::GetIndex);
+            }
+            Expression::SetIndex {
+                object,
+                index,
+                value,
+                span,
+            } => {
+                self.expression(object)?;
+                self.expression(index)?;
+                self.expression(value)?;
+                self.span = *span;
+                self.emit_op(OpCode::SetIndex);
+            }
+            Expression::This { span, .. } => {
+                self.span = *span;
+                self.named_variable("this")?;
+            }
+            Expression::Super { method, span, .. } => {
+                self.span = *span;
+                self.named_variable("this")?;
+                self.named_variable("super")?;
+                let name = self.identifier_constant(method)?;
+                self.emit_op(OpCode::GetSuper);
+                self.emit_u16(name);
+            }
+        }
+        Ok(())
+    }
+
+    fn named_variable(&mut self, name: &str) -> Result<(), Error> {
+        match self.resolve_variable(name)? {
+            Variable::Local(slot) => {
+                self.emit_op(OpCode::GetLocal);
+                self.emit_byte(slot);
+            }
+            Variable::Upvalue(index) => {
+                self.emit_op(OpCode::GetUpvalue);
+                self.emit_byte(index);
+            }
+            Variable::Global(name) => {
+                self.emit_op(OpCode::GetGlobal);
+                self.emit_u16(name);
+            }
+        }
+        Ok(())
+    }
+
+    fn define_variable(&mut self, name: &str) -> Result<(), Error> {
+        if self.function().scope_depth > 0 {
+            return self.add_local(name);
+        }
+        let name = self.identifier_constant(name)?;
+        self.emit_op(OpCode::DefineGlobal);
+        self.emit_u16(name);
+        Ok(())
+    }
+
+    fn resolve_variable(&mut self, name: &str) -> Result<Variable, Error> {
+        let current = self.functions.len() - 1;
+        if let Some(slot) = self.resolve_local(current, name) {
+            return Ok(Variable::Local(slot));
+        }
+        if let Some(index) = self.resolve_upvalue(current, name)? {
+            return Ok(Variable::Upvalue(index));
+        }
+        Ok(Variable::Global(self.identifier_constant(name)?))
+    }
+
+    fn resolve_local(&self, function: usize, name: &str) -> Option<u8> {
+        let locals = &self.functions[function].locals;
+        let slot = locals.iter().rposition(|local| local.name == name)?;
+        Some(slot as u8)
+    }
+
+    fn resolve_upvalue(&mut self, function: usize, name: &str) -> Result<Option<u8>, Error> {
+        if function == 0 {
+            return Ok(None);
+        }
+        if let Some(slot) = self.resolve_local(function - 1, name) {
+            self.functions[function - 1].locals[slot as usize].is_captured = true;
+            return self.add_upvalue(function, slot, true).map(Some);
+        }
+        match self.resolve_upvalue(function - 1, name)? {
+            Some(index) => self.add_upvalue(function, index, false).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn add_upvalue(&mut self, function: usize, index: u8, is_local: bool) -> Result<u8, Error> {
+        let upvalues = &mut self.functions[function].upvalues;
+        if let Some(existing) = upvalues
+            .iter()
+            .position(|upvalue| upvalue.index == index && upvalue.is_local == is_local)
+        {
+            return Ok(existing as u8);
+        }
+        if upvalues.len() > u8::MAX as usize {
+            return Err(Error::TooManyUpvalues { span: self.span });
+        }
+        upvalues.push(Upvalue { index, is_local });
+        Ok((upvalues.len() - 1) as u8)
+    }
+
+    fn add_local(&mut self, name: &str) -> Result<(), Error> {
+        let span = self.span;
+        let function = self.function_mut();
+        if function.locals.len() > u8::MAX as usize {
+            return Err(Error::TooManyLocalVariables { span });
+        }
+        let depth = function.scope_depth;
+        function.locals.push(Local {
+            name: name.to_string(),
+            depth,
+            is_captured: false,
+        });
+        Ok(())
+    }
+
+    fn begin_scope(&mut self) {
+        self.function_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let function = self.function_mut();
+        function.scope_depth -= 1;
+        let depth = function.scope_depth;
+        self.discard_locals(depth);
+        self.function_mut()
+            .locals
+            .retain(|local| local.depth <= depth);
+    }
+
+    /// Emits the code removing the locals deeper than `depth` from the stack. They stay in scope
+    /// for the compiler, because `break` and `continue` leave them at runtime only.
+    fn discard_locals(&mut self, depth: usize) {
+        let op_codes = self
+            .function()
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > depth)
+            .map(|local| {
+                if local.is_captured {
+                    OpCode::CloseUpvalue
+                } else {
+                    OpCode::Pop
+                }
+            })
+            .collect::<Vec<_>>();
+        for op_code in op_codes {
+            self.emit_op(op_code);
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &str) -> Result<u16, Error> {
+        self.make_constant(Constant::String(name.to_string()))
+    }
+
+    fn make_constant(&mut self, constant: Constant) -> Result<u16, Error> {
+        let span = self.span;
+        self.chunk()
+            .add_constant(constant)
+            .ok_or(Error::TooManyConstants { span })
+    }
+
+    fn emit_constant(&mut self, constant: Constant) -> Result<(), Error> {
+        let index = self.make_constant(constant)?;
+        self.emit_op(OpCode::Constant);
+        self.emit_u16(index);
+        Ok(())
+    }
+
+    fn emit_jump(&mut self, op_code: OpCode) -> usize {
+        self.emit_op(op_code);
+        self.emit_u16(u16::MAX);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) -> Result<(), Error> {
+        let span = self.span;
+        let jump = self.chunk().code.len() - offset - 2;
+        let jump = u16::try_from(jump).map_err(|_| Error::JumpTooLarge { span })?;
+        self.chunk().patch_u16(offset, jump);
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) -> Result<(), Error> {
+        self.emit_op(OpCode::Loop);
+        let span = self.span;
+        let jump = self.chunk().code.len() - loop_start + 2;
+        let jump = u16::try_from(jump).map_err(|_| Error::JumpTooLarge { span })?;
+        self.emit_u16(jump);
+        Ok(())
+    }
+
+    fn emit_op(&mut self, op_code: OpCode) {
+        let span = self.span;
+        self.chunk().write_op(op_code, span);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let span = self.span;
+        self.chunk().write(byte, span);
+    }
+
+    fn emit_u16(&mut self, value: u16) {
+        let span = self.span;
+        self.chunk().write_u16(value, span);
+    }
+
+    fn function(&self) -> &FunctionState {
+        self.functions.last().unwrap()
+    }
+
+    fn function_mut(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.function_mut().prototype.chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use std::assert_matches;
+
+    fn compile(source: &str) -> Result<Rc<FunctionPrototype>, Error> {
+        let tokens = Scanner::new(source, 1).scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().into_result().unwrap();
+        Compiler::compile(&statements)
+    }
+
+    fn function_constant(chunk: &Chunk) -> Rc<FunctionPrototype> {
+        chunk
+            .constants
+            .iter()
+            .find_map(|constant| match constant {
+                Constant::Function(function) => Some(function.clone()),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn compile_print() {
+        let script = compile("print 1 + 2;").unwrap();
+        assert_eq!(
+            script.chunk.code,
+            vec![
+                OpCode::Constant as u8,
+                0,
+                0,
+                OpCode::Constant as u8,
+                0,
+                1,
+                OpCode::Add as u8,
+                OpCode::Print as u8,
+                OpCode::Nil as u8,
+                OpCode::Return as u8,
+            ]
+        );
+        assert_eq!(script.chunk.code.len(), script.chunk.spans.len());
+    }
+
+    #[test]
+    fn compile_local_variables_use_slots() {
+        let script = compile("{ var a = 1; print a; }").unwrap();
+        assert_eq!(
+            script.chunk.code[3..8],
+            [
+                OpCode::GetLocal as u8,
+                1,
+                OpCode::Print as u8,
+                OpCode::Pop as u8,
+                OpCode::Nil as u8,
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_closure_captures_upvalue() {
+        let script = compile("fun outer() { var x = 1; fun inner() { return x; } }").unwrap();
+        let outer = function_constant(&script.chunk);
+        let inner = function_constant(&outer.chunk);
+        assert_eq!(inner.upvalue_count, 1);
+        assert!(outer.chunk.code.contains(&(OpCode::CloseUpvalue as u8)));
+    }
+
+    #[test]
+    fn compile_too_many_locals() {
+        let declarations = (0..300).map(|i| format!("var v{i};")).collect::<String>();
+        assert_matches!(
+            compile(&format!("{{ {declarations} }}")),
+            Err(Error::TooManyLocalVariables { .. })
+        );
+    }
+}
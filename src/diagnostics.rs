@@ -0,0 +1,354 @@
+use crate::object::ErrorKind;
+use crate::result::{Error, Warning};
+use crate::token::Span;
+use serde::Serialize;
+use std::io::{IsTerminal, Write};
+
+/// A human-readable report of an error, rendered like:
+///
+/// ```text
+/// error[E0002]: Unterminated string.
+///  --> main.lox:1:7
+///   |
+/// 1 | print "hello;
+///   |       ^^^^^^^
+///   = help: strings must be closed with '"' before the end of the file
+/// ```
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(error: &Error) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code: error.code(),
+            message: error.to_string(),
+            span: error.span().and_then(known),
+            notes: help(error).into_iter().map(str::to_string).collect(),
+        }
+    }
+}
+
+impl From<&Warning> for Diagnostic {
+    fn from(warning: &Warning) -> Self {
+        let help = match warning {
+            Warning::UnusedVariable { .. } | Warning::UnusedParameter { .. } => {
+                Some("start the name with '_' if this is intentional")
+            }
+            _ => None,
+        };
+        Diagnostic {
+            severity: Severity::Warning,
+            code: warning.code(),
+            message: warning.to_string(),
+            span: known(warning.span()),
+            notes: help.into_iter().map(str::to_string).collect(),
+        }
+    }
+}
+
+/// Syntax trees loaded without spans have every span at line 0, which points nowhere.
+fn known(span: Span) -> Option<Span> {
+    (span.line > 0).then_some(span)
+}
+
+fn help(error: &Error) -> Option<&'static str> {
+    let help = match error {
+        Error::UnterminatedString { .. } => {
+            "strings must be closed with '\"' before the end of the file"
+        }
+        Error::UnterminatedNumber { .. } => "numbers can't be followed by letters or '_'",
+        Error::ExpectedEndOfExpression { .. } => "add a ';' after the previous statement",
+        Error::InvalidAssignmentTarget { .. } => "only variables and properties can be assigned to",
+        Error::TooManyArguments { .. } => "functions take at most 255 arguments",
+        Error::ReturnFromInitializer { .. } => "initializers always return 'this'; use 'return;'",
+        Error::ReadInOwnInitializer { .. } => {
+            "to use an outer variable with the same name, give this one a different name"
+        }
+        Error::DuplicateDeclaration { .. } => "assign to the existing variable instead",
+        Error::SuperWithoutSuperclass { .. } => {
+            "declare a superclass with 'class Name < Superclass'"
+        }
+        Error::TooManyLocalVariables { .. } => {
+            "a function can have at most 256 local variables in scope"
+        }
+        Error::EvaluationError(error) => match error.kind {
+            ErrorKind::UndefinedVariable { .. } => "declare it with 'var' before using it",
+            ErrorKind::AttemptedToCallUncallableExpression { .. } => {
+                "only functions and classes can be called"
+            }
+            ErrorKind::StackOverflow => "check for recursion without a base case",
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(help)
+}
+
+impl Diagnostic {
+    /// Renders the report. The offending line is shown when the source that produced the error is
+    /// given, and ANSI colours are used when `colored` is set.
+    pub fn render(&self, file_name: Option<&str>, source: Option<&str>, colored: bool) -> String {
+        let style = Style { colored };
+        let (label, color) = match self.severity {
+            Severity::Error => ("error", RED_BOLD),
+            Severity::Warning => ("warning", YELLOW_BOLD),
+        };
+        let gutter = match self.span {
+            Some(span) => " ".repeat(span.line.to_string().len()),
+            None => String::new(),
+        };
+        let mut output = format!(
+            "{}{}\n",
+            style.paint(color, &format!("{label}[{}]", self.code)),
+            style.paint(BOLD, &format!(": {}", self.message)),
+        );
+        match (file_name, self.span) {
+            (Some(file_name), Some(span)) => {
+                output += &format!(
+                    "{gutter}{} {file_name}:{span}\n",
+                    style.paint(BLUE_BOLD, "-->")
+                )
+            }
+            (Some(file_name), None) => {
+                output += &format!("{} {file_name}\n", style.paint(BLUE_BOLD, "-->"))
+            }
+            (None, _) => {}
+        }
+        if let Some((line, underline)) = self.span.zip(source).and_then(snippet) {
+            let bar = style.paint(BLUE_BOLD, "|");
+            let line_number = self.span.unwrap().line;
+            output += &format!("{gutter} {bar}\n");
+            output += &format!(
+                "{} {bar} {line}\n",
+                style.paint(BLUE_BOLD, &line_number.to_string())
+            );
+            output += &format!("{gutter} {bar} {}\n", style.paint(color, &underline));
+        }
+        for note in &self.notes {
+            output += &format!("{gutter} {} {note}\n", style.paint(BOLD, "= help:"));
+        }
+        output
+    }
+}
+
+/// The source line containing the start of the span, and a line of carets under the span.
+fn snippet((span, source): (Span, &str)) -> Option<(&str, String)> {
+    let prefix = source.get(..span.start)?;
+    let line_start = prefix.rfind('\n').map_or(0, |index| index + 1);
+    let line_end = source[span.start..]
+        .find('\n')
+        .map_or(source.len(), |index| span.start + index);
+    let line = source[line_start..line_end].trim_end_matches('\r');
+
+    // Tabs are kept so the carets line up with the text above them.
+    let indentation = source[line_start..span.start]
+        .chars()
+        .map(|character| if character == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    let underlined = source
+        .get(span.start..span.end.clamp(span.start, line_end))
+        .map_or(0, |text| text.chars().count());
+    Some((line, indentation + &"^".repeat(underlined.max(1))))
+}
+
+const BOLD: &str = "\x1b[1m";
+const RED_BOLD: &str = "\x1b[1;31m";
+const YELLOW_BOLD: &str = "\x1b[1;33m";
+const BLUE_BOLD: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+struct Style {
+    colored: bool,
+}
+
+impl Style {
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.colored {
+            format!("{color}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Prints every error with the line of `source` it refers to.
+pub fn report(file_name: &str, source: &str, errors: &[Error]) {
+    report_diagnostics(file_name, Some(source), errors.iter().map(Diagnostic::from));
+}
+
+/// Prints every warning with the line of `source` it refers to.
+pub fn report_warnings(file_name: &str, source: &str, warnings: &[Warning]) {
+    report_diagnostics(
+        file_name,
+        Some(source),
+        warnings.iter().map(Diagnostic::from),
+    );
+}
+
+/// Prints the diagnostics of a file, with the lines they refer to when the source is given.
+pub fn report_diagnostics(
+    file_name: &str,
+    source: Option<&str>,
+    diagnostics: impl IntoIterator<Item = Diagnostic>,
+) {
+    let colored = std::io::stdout().is_terminal();
+    for diagnostic in diagnostics {
+        write_to_stdout(&diagnostic.render(Some(file_name), source, colored));
+    }
+}
+
+/// Prints an error that isn't tied to any source, like failing to read a file.
+pub fn report_without_source(error: &Error) {
+    let colored = std::io::stdout().is_terminal();
+    write_to_stdout(&Diagnostic::from(error).render(None, None, colored));
+}
+
+/// Unlike `print!`, doesn't panic when stdout is closed, like a pipe whose reader stopped
+/// reading. The failure to write a program's output is reported as its own error, if it can be.
+fn write_to_stdout(text: &str) {
+    let _ = std::io::stdout().write_all(text.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object;
+
+    fn render(error: Error, source: &str) -> String {
+        Diagnostic::from(&error).render(Some("main.lox"), Some(source), false)
+    }
+
+    #[test]
+    fn diagnostic_from_error() {
+        let error = Error::UnterminatedString {
+            string: "hello;".to_string(),
+            span: Span::new(1, 7, 6, 13),
+        };
+        assert_eq!(
+            Diagnostic::from(&error),
+            Diagnostic {
+                severity: Severity::Error,
+                code: "E0002",
+                message: "Unterminated string.".to_string(),
+                span: Some(Span::new(1, 7, 6, 13)),
+                notes: vec![
+                    "strings must be closed with '\"' before the end of the file".to_string()
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn render_with_snippet_and_help() {
+        let error = Error::UnterminatedString {
+            string: "hello;".to_string(),
+            span: Span::new(1, 7, 6, 13),
+        };
+        assert_eq!(
+            render(error, "print \"hello;"),
+            "error[E0002]: Unterminated string.
+ --> main.lox:1:7
+  |
+1 | print \"hello;
+  |       ^^^^^^^
+  = help: strings must be closed with '\"' before the end of the file
+"
+        );
+    }
+
+    #[test]
+    fn render_picks_the_line_of_the_span() {
+        let source = "var a = 1;\nvar b = a + nil;\nprint b;\n";
+        let error = Error::EvaluationError(object::Error::new(
+            ErrorKind::ExpectedNumber {
+                actual: object::Object::Nil,
+            },
+            Span::new(2, 11, 21, 22),
+        ));
+        assert_eq!(
+            render(error, source),
+            "error[E0401]: Expected number, found nil.
+ --> main.lox:2:11
+  |
+2 | var b = a + nil;
+  |           ^
+"
+        );
+    }
+
+    #[test]
+    fn render_underline_stops_at_end_of_line() {
+        let error = Error::UnterminatedString {
+            string: "a\nb".to_string(),
+            span: Span::new(1, 1, 0, 5),
+        };
+        let rendered = render(error, "\"a\nb");
+        assert!(rendered.contains("1 | \"a\n  | ^^\n"), "{rendered}");
+    }
+
+    #[test]
+    fn render_keeps_tabs_in_indentation() {
+        let error = Error::UnexpectedCharacter {
+            character: '@',
+            span: Span::new(1, 2, 1, 2),
+        };
+        let rendered = render(error, "\t@");
+        assert!(rendered.contains("  | \t^\n"), "{rendered}");
+    }
+
+    #[test]
+    fn render_without_span_or_source() {
+        let error = Error::InvalidSyntaxTree {
+            message: "expected value".to_string(),
+        };
+        let rendered = Diagnostic::from(&error).render(None, None, false);
+        assert_eq!(
+            rendered,
+            "error[E0903]: Invalid syntax tree: expected value.\n"
+        );
+    }
+
+    #[test]
+    fn render_warning() {
+        let warning = Warning::UnusedVariable {
+            name: "a".to_string(),
+            span: Span::new(1, 7, 6, 7),
+        };
+        let rendered =
+            Diagnostic::from(&warning).render(Some("main.lox"), Some("{ var a = 1; }"), false);
+        assert_eq!(
+            rendered,
+            "warning[W0200]: Local variable 'a' is never read.
+ --> main.lox:1:7
+  |
+1 | { var a = 1; }
+  |       ^
+  = help: start the name with '_' if this is intentional
+"
+        );
+    }
+
+    #[test]
+    fn render_colored() {
+        let error = Error::ExpectedExpression {
+            span: Span::new(1, 1, 0, 1),
+        };
+        let rendered = Diagnostic::from(&error).render(Some("main.lox"), Some(";"), true);
+        assert!(rendered.starts_with("\x1b[1;31merror[E0100]\x1b[0m"));
+        assert!(rendered.contains("\x1b[1;31m^\x1b[0m"));
+    }
+}
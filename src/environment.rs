@@ -1,32 +1,69 @@
+use crate::heap::{address, track, Address, Trace};
 use crate::object::*;
+use crate::resolver::Slot;
 
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+/// A scope. Locals live in a vector, in the order they are defined, so the interpreter can reach
+/// them through the slots the resolver computed. The global scope is looked up by name instead,
+/// because a function can refer to a global that is only defined after it.
 #[derive(Clone, Debug)]
 pub struct Environment(Rc<RefCell<EnvironmentInner>>);
 
 impl Environment {
     pub fn new() -> Environment {
-        Environment(Rc::new(RefCell::new(EnvironmentInner::new())))
+        Environment::tracked(EnvironmentInner::new())
     }
     pub fn new_child(&self) -> Environment {
-        Environment(Rc::new(RefCell::new(EnvironmentInner::new_child(self))))
+        Environment::tracked(EnvironmentInner::new_child(self))
+    }
+    fn tracked(inner: EnvironmentInner) -> Environment {
+        let environment = Rc::new(RefCell::new(inner));
+        track(&environment);
+        Environment(environment)
+    }
+    pub fn address(&self) -> Address {
+        address(&self.0)
     }
     pub fn end(&mut self) -> Option<Environment> {
         self.0.borrow_mut().enclosing.clone()
     }
+    pub fn enclosing(&self) -> Option<Environment> {
+        self.0.borrow().enclosing.clone()
+    }
+    /// The variables defined in this scope, without the ones it encloses. Locals are in the order
+    /// they were defined and globals are sorted by name.
+    pub fn variables(&self) -> Vec<(String, Object)> {
+        let inner = self.0.borrow();
+        if inner.is_global() {
+            let mut globals = inner
+                .globals
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<Vec<_>>();
+            globals.sort_by(|(name, _), (other_name, _)| name.cmp(other_name));
+            globals
+        } else {
+            inner
+                .names
+                .iter()
+                .cloned()
+                .zip(inner.locals.iter().cloned())
+                .collect()
+        }
+    }
     pub fn define(&mut self, name: String, value: Object) {
         (*self.0).borrow_mut().define(name, value)
     }
-    pub fn get(&self, name: &String) -> Result<Object, Error> {
+    pub fn get(&self, name: &String) -> Result<Object, ErrorKind> {
         self.0.borrow().get(name)
     }
-    pub fn get_at(&self, depth: usize, name: &String) -> Result<Object, Error> {
-        self.ancestor(depth)
-            .ok_or(Error::UndefinedVariable)?
-            .get(name)
+    pub fn get_at(&self, slot: Slot) -> Option<Object> {
+        let environment = self.ancestor(slot.depth)?;
+        let value = environment.0.borrow().locals.get(slot.index)?.clone();
+        Some(value)
     }
     fn ancestor(&self, depth: usize) -> Option<Environment> {
         let mut environment = Some(self.clone());
@@ -35,69 +72,111 @@ impl Environment {
         }
         environment
     }
-    pub fn assign(&mut self, name: String, value: Object) -> Result<Object, Error> {
+    pub fn assign(&mut self, name: String, value: Object) -> Result<Object, ErrorKind> {
         (*self.0).borrow_mut().assign(name, value)
     }
-    pub fn assign_at(
-        &mut self,
-        depth: usize,
-        name: String,
-        value: Object,
-    ) -> Result<Object, Error> {
-        self.ancestor(depth)
-            .ok_or(Error::UndefinedVariable)?
-            .assign(name, value)
+    /// Replaces the local in `slot`, returning the previous value.
+    pub fn assign_at(&mut self, slot: Slot, value: Object) -> Option<Object> {
+        let environment = self.ancestor(slot.depth)?;
+        let mut inner = environment.0.borrow_mut();
+        let local = inner.locals.get_mut(slot.index)?;
+        Some(std::mem::replace(local, value))
+    }
+}
+
+impl Trace for RefCell<EnvironmentInner> {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        let Ok(inner) = self.try_borrow() else {
+            return;
+        };
+        if let Some(enclosing) = &inner.enclosing {
+            visit(enclosing.address());
+        }
+        for value in inner.locals.iter().chain(inner.globals.values()) {
+            value.trace(visit);
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut inner) = self.try_borrow_mut() {
+            inner.names.clear();
+            inner.locals.clear();
+            inner.globals.clear();
+            inner.enclosing = None;
+        }
     }
 }
 
 #[derive(Debug)]
 struct EnvironmentInner {
-    values: HashMap<String, Object>,
+    /// Names of the locals, only used to look them up by name from Rust.
+    names: Vec<String>,
+    locals: Vec<Object>,
+    globals: HashMap<String, Object>,
     enclosing: Option<Environment>,
 }
 
 impl EnvironmentInner {
     fn new() -> EnvironmentInner {
         EnvironmentInner {
-            values: HashMap::new(),
+            names: Vec::new(),
+            locals: Vec::new(),
+            globals: HashMap::new(),
             enclosing: None,
         }
     }
 
     fn new_child(enclosing: &Environment) -> EnvironmentInner {
         EnvironmentInner {
-            values: HashMap::new(),
             enclosing: Some(enclosing.clone()),
+            ..EnvironmentInner::new()
         }
     }
 
+    fn is_global(&self) -> bool {
+        self.enclosing.is_none()
+    }
+
     fn define(&mut self, name: String, value: Object) {
-        self.values.insert(name, value);
+        if self.is_global() {
+            self.globals.insert(name, value);
+        } else {
+            self.names.push(name);
+            self.locals.push(value);
+        }
     }
 
-    fn get(&self, name: &String) -> Result<Object, Error> {
-        let Some(value) = self.values.get(name) else {
+    /// The latest local with this name, which hides any it shadows.
+    fn local_index(&self, name: &String) -> Option<usize> {
+        self.names.iter().rposition(|local| local == name)
+    }
+
+    fn get(&self, name: &String) -> Result<Object, ErrorKind> {
+        let value = if self.is_global() {
+            self.globals.get(name)
+        } else {
+            self.local_index(name).map(|index| &self.locals[index])
+        };
+        let Some(value) = value else {
             let Some(enclosing) = &self.enclosing else {
-                return Err(Error::UndefinedVariable);
+                return Err(ErrorKind::UndefinedVariable { name: name.clone() });
             };
             return enclosing.get(name);
         };
         Ok(value.clone())
     }
 
-    fn variable_was_declared_in_this_scope(&self, name: &String) -> bool {
-        self.values.contains_key(name)
-    }
-
-    fn assign(&mut self, name: String, value: Object) -> Result<Object, Error> {
-        if self.variable_was_declared_in_this_scope(&name) {
-            let Some(val) = self.values.insert(name, value) else {
-                unreachable!("We already checked that the key is in the map")
-            };
-            Ok(val)
+    fn assign(&mut self, name: String, value: Object) -> Result<Object, ErrorKind> {
+        let variable = if self.is_global() {
+            self.globals.get_mut(&name)
+        } else {
+            self.local_index(&name).map(|index| &mut self.locals[index])
+        };
+        if let Some(variable) = variable {
+            Ok(std::mem::replace(variable, value))
         } else {
             let Some(enclosing) = &mut self.enclosing else {
-                return Err(Error::UndefinedVariable);
+                return Err(ErrorKind::UndefinedVariable { name });
             };
             enclosing.assign(name, value)
         }
@@ -107,7 +186,7 @@ impl EnvironmentInner {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::assert_matches::assert_matches;
+    use std::assert_matches;
     #[test]
     fn environment_define() {
         let mut env = Environment::new();
@@ -126,7 +205,10 @@ mod tests {
         assert!(result.is_ok());
         assert!(result_err.is_err());
         assert_eq!(result.unwrap(), Object::Number(20.0.into()));
-        assert_matches!(result_err.unwrap_err(), Error::UndefinedVariable);
+        assert_matches!(
+            result_err.unwrap_err(),
+            ErrorKind::UndefinedVariable { name } if name == "e"
+        );
     }
 
     #[test]
@@ -139,7 +221,10 @@ mod tests {
         assert!(result.is_ok());
         assert!(result_err.is_err());
         assert_eq!(result.unwrap(), Object::Number(20.0.into()));
-        assert_matches!(result_err.unwrap_err(), Error::UndefinedVariable);
+        assert_matches!(
+            result_err.unwrap_err(),
+            ErrorKind::UndefinedVariable { name } if name == "e"
+        );
     }
 
     #[test]
@@ -155,4 +240,59 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), Object::Number(10.0.into()));
     }
+
+    #[test]
+    fn locals_are_reached_through_slots() {
+        let globals = Environment::new();
+        let mut outer = globals.new_child();
+        outer.define("x".to_string(), Object::Number(1.0.into()));
+        outer.define("x".to_string(), Object::Number(2.0.into()));
+        let mut inner = outer.new_child();
+        let slot = |depth, index| Slot { depth, index };
+        assert_eq!(inner.get_at(slot(1, 0)), Some(Object::Number(1.0.into())));
+        assert_eq!(
+            inner.get(&"x".to_string()).unwrap(),
+            Object::Number(2.0.into())
+        );
+        assert_eq!(
+            inner.assign_at(slot(1, 1), Object::Nil),
+            Some(Object::Number(2.0.into()))
+        );
+        assert_eq!(outer.get_at(slot(0, 1)), Some(Object::Nil));
+        assert_eq!(inner.get_at(slot(0, 0)), None);
+        assert_eq!(inner.get_at(slot(3, 0)), None);
+    }
+
+    #[test]
+    fn globals_are_looked_up_by_name() {
+        let mut globals = Environment::new();
+        globals.define("x".to_string(), Object::Number(1.0.into()));
+        globals.define("x".to_string(), Object::Number(2.0.into()));
+        assert_eq!(globals.get_at(Slot { depth: 0, index: 0 }), None);
+        assert_eq!(
+            globals.get(&"x".to_string()).unwrap(),
+            Object::Number(2.0.into())
+        );
+    }
+
+    #[test]
+    fn variables_of_each_scope() {
+        let mut globals = Environment::new();
+        globals.define("b".to_string(), Object::Nil);
+        globals.define("a".to_string(), Object::Boolean(true));
+        let mut locals = globals.new_child();
+        locals.define("z".to_string(), Object::Nil);
+        locals.define("y".to_string(), Object::Nil);
+
+        let names = |environment: &Environment| {
+            environment
+                .variables()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&locals), ["z", "y"]);
+        assert_eq!(names(&locals.enclosing().unwrap()), ["a", "b"]);
+        assert!(globals.enclosing().is_none());
+    }
 }
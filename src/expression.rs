@@ -1,95 +1,350 @@
 use crate::number::Number;
+use crate::token::{Span, AND_KEYWORD, FALSE_KEYWORD, NIL_KEYWORD, OR_KEYWORD, TRUE_KEYWORD};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[derive(Clone, PartialEq, Debug, Eq, Hash)]
+/// Identifies a variable, assignment, `this` or `super` expression, so the resolver can record
+/// where its variable lives. Identical expressions in different scopes get different ids.
+pub type ExpressionId = usize;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns an id no other expression has. Ids are unique across parses, because functions from
+/// earlier REPL lines keep running next to new code.
+pub fn new_id() -> ExpressionId {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Clone, PartialEq, Debug, Eq, Hash, Serialize, Deserialize)]
 pub enum Expression {
+    #[serde(rename = "literal")]
     Literal(Literal),
+    #[serde(rename = "unary")]
     Unary {
         operator: UnaryOperator,
         expression: Box<Expression>,
+        #[serde(default)]
+        span: Span,
     },
+    #[serde(rename = "binary")]
     Binary {
         left: Box<Expression>,
         operator: BinaryOperator,
         right: Box<Expression>,
+        #[serde(default)]
+        span: Span,
     },
-    Variable(String),
+    #[serde(rename = "variable")]
+    Variable {
+        #[serde(skip, default = "new_id")]
+        id: ExpressionId,
+        identifier: String,
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "grouping")]
+    #[allow(dead_code)]
     Grouping(Box<Expression>),
+    #[serde(rename = "assignment")]
     Assignment {
+        #[serde(skip, default = "new_id")]
+        id: ExpressionId,
         identifier: String,
         value: Box<Expression>,
+        #[serde(default)]
+        span: Span,
     },
+    #[serde(rename = "call")]
     FunctionCall {
         function: Box<Expression>,
         arguments: Vec<Expression>,
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "get")]
+    Get {
+        object: Box<Expression>,
+        name: String,
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "set")]
+    Set {
+        object: Box<Expression>,
+        name: String,
+        value: Box<Expression>,
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "this")]
+    This {
+        #[serde(skip, default = "new_id")]
+        id: ExpressionId,
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "super")]
+    Super {
+        #[serde(skip, default = "new_id")]
+        id: ExpressionId,
+        method: String,
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "list")]
+    List {
+        elements: Vec<Expression>,
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "map")]
+    Map {
+        entries: Vec<(Expression, Expression)>,
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "index")]
+    Index {
+        object: Box<Expression>,
+        index: Box<Expression>,
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "set_index")]
+    SetIndex {
+        object: Box<Expression>,
+        index: Box<Expression>,
+        value: Box<Expression>,
+        #[serde(default)]
+        span: Span,
     },
 }
 
-#[derive(Clone, PartialEq, Debug, Eq, Hash)]
+#[derive(Clone, PartialEq, Debug, Eq, Hash, Serialize, Deserialize)]
 pub enum Literal {
+    #[serde(rename = "number")]
     Number(Number),
+    #[serde(rename = "string")]
     String(String),
+    #[serde(rename = "true")]
     True,
+    #[serde(rename = "false")]
     False,
+    #[serde(rename = "nil")]
     Nil,
 }
 
-#[derive(Clone, PartialEq, Debug, Eq, Hash)]
+#[derive(Clone, PartialEq, Debug, Eq, Hash, Serialize, Deserialize)]
 pub enum UnaryOperator {
+    #[serde(rename = "!")]
     Negation,
+    #[serde(rename = "-")]
     Minus,
 }
 
-#[derive(Clone, PartialEq, Debug, Eq, Hash)]
+#[derive(Clone, PartialEq, Debug, Eq, Hash, Serialize, Deserialize)]
 pub enum BinaryOperator {
+    #[serde(rename = "==")]
     Equality,
+    #[serde(rename = "!=")]
     Different,
+    #[serde(rename = "<")]
     Less,
+    #[serde(rename = "<=")]
     EqualOrLess,
+    #[serde(rename = ">")]
     Greater,
+    #[serde(rename = ">=")]
     EqualOrGreater,
+    #[serde(rename = "+")]
     Addition,
+    #[serde(rename = "-")]
     Subtraction,
+    #[serde(rename = "*")]
     Multiplication,
+    #[serde(rename = "/")]
     Division,
+    #[serde(rename = "or")]
+    Or,
+    #[serde(rename = "and")]
+    And,
+}
+
+/// How tightly an expression binds, from assignment, the loosest, to literals and variables.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Assignment,
     Or,
     And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
 }
 
 impl Expression {
+    /// Lox code that parses back to this expression. The parser drops parentheses, so they are
+    /// only added where precedence needs them.
     pub fn to_code(&self) -> String {
         match self {
             Expression::Literal(literal) => literal.to_code(),
             Expression::Unary {
                 operator,
                 expression,
-            } => format!("{}{}", operator.to_code(), expression.to_code()),
+                ..
+            } => match **expression {
+                // `--x` reads like a decrement, so a nested operator keeps its parentheses.
+                Expression::Unary { .. } => {
+                    format!("{}({})", operator.to_code(), expression.to_code())
+                }
+                _ => format!(
+                    "{}{}",
+                    operator.to_code(),
+                    expression.operand_to_code(Precedence::Unary)
+                ),
+            },
             Expression::Binary {
                 left,
                 operator,
                 right,
-            } => format!(
-                "{} {} {}",
-                left.to_code(),
-                operator.to_code(),
-                right.to_code()
-            ),
-            Expression::Variable(string) => format!("var {string} "),
-            Expression::Assignment { identifier, value } => {
+                ..
+            } => {
+                let precedence = operator.precedence();
+                // Comparisons group to the right, all other binary operators to the left.
+                let (left_precedence, right_precedence) = if precedence == Precedence::Comparison {
+                    (precedence.next(), precedence)
+                } else {
+                    (precedence, precedence.next())
+                };
+                format!(
+                    "{} {} {}",
+                    left.operand_to_code(left_precedence),
+                    operator.to_code(),
+                    right.operand_to_code(right_precedence)
+                )
+            }
+            Expression::Variable { identifier, .. } => identifier.clone(),
+            Expression::Assignment {
+                identifier, value, ..
+            } => {
                 format!("{identifier} = {}", value.to_code())
             }
             Expression::Grouping(expression) => format!("({})", expression.to_code()),
             Expression::FunctionCall {
                 function,
                 arguments,
+                ..
             } => format!(
                 "{}({})",
-                function.to_code(),
+                function.operand_to_code(Precedence::Call),
                 arguments
                     .iter()
                     .map(|arg| arg.to_code())
                     .intersperse(", ".to_string())
                     .collect::<String>()
             ),
+            Expression::Get { object, name, .. } => {
+                format!("{}.{name}", object.object_to_code())
+            }
+            Expression::Set {
+                object,
+                name,
+                value,
+                ..
+            } => format!("{}.{name} = {}", object.object_to_code(), value.to_code()),
+            Expression::This { .. } => "this".to_string(),
+            Expression::Super { method, .. } => format!("super.{method}"),
+            Expression::List { elements, .. } => format!(
+                "[{}]",
+                elements
+                    .iter()
+                    .map(|element| element.to_code())
+                    .intersperse(", ".to_string())
+                    .collect::<String>()
+            ),
+            Expression::Map { entries, .. } => format!(
+                "{{{}}}",
+                entries
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key.to_code(), value.to_code()))
+                    .intersperse(", ".to_string())
+                    .collect::<String>()
+            ),
+            Expression::Index { object, index, .. } => {
+                format!(
+                    "{}[{}]",
+                    object.operand_to_code(Precedence::Call),
+                    index.to_code()
+                )
+            }
+            Expression::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => format!(
+                "{}[{}] = {}",
+                object.operand_to_code(Precedence::Call),
+                index.to_code(),
+                value.to_code()
+            ),
+        }
+    }
+
+    fn precedence(&self) -> Precedence {
+        match self {
+            Expression::Assignment { .. }
+            | Expression::Set { .. }
+            | Expression::SetIndex { .. } => Precedence::Assignment,
+            Expression::Binary { operator, .. } => operator.precedence(),
+            Expression::Unary { .. } => Precedence::Unary,
+            Expression::FunctionCall { .. } | Expression::Get { .. } | Expression::Index { .. } => {
+                Precedence::Call
+            }
+            Expression::Literal(_)
+            | Expression::Variable { .. }
+            | Expression::Grouping(_)
+            | Expression::This { .. }
+            | Expression::Super { .. }
+            | Expression::List { .. }
+            | Expression::Map { .. } => Precedence::Primary,
+        }
+    }
+
+    /// The code of an operand, in parentheses if it binds less tightly than `minimum`.
+    fn operand_to_code(&self, minimum: Precedence) -> String {
+        if self.precedence() < minimum {
+            format!("({})", self.to_code())
+        } else {
+            self.to_code()
+        }
+    }
+
+    /// The code of the object of a property access. A number needs parentheses there, or the dot
+    /// would be scanned as its decimal point.
+    fn object_to_code(&self) -> String {
+        match self {
+            Expression::Literal(Literal::Number(_)) => format!("({})", self.to_code()),
+            _ => self.operand_to_code(Precedence::Call),
         }
     }
 }
@@ -99,9 +354,9 @@ impl Literal {
         match self {
             Literal::Number(number) => number.to_string(),
             Literal::String(string) => format!("\"{string}\""),
-            Literal::True => "True".to_string(),
-            Literal::False => "False".to_string(),
-            Literal::Nil => "Nil".to_string(),
+            Literal::True => TRUE_KEYWORD.to_string(),
+            Literal::False => FALSE_KEYWORD.to_string(),
+            Literal::Nil => NIL_KEYWORD.to_string(),
         }
     }
 }
@@ -128,13 +383,28 @@ impl BinaryOperator {
             BinaryOperator::Subtraction => "-".to_string(),
             BinaryOperator::Multiplication => "*".to_string(),
             BinaryOperator::Division => "/".to_string(),
-            BinaryOperator::Or => "or".to_string(),
-            BinaryOperator::And => "and".to_string(),
+            BinaryOperator::Or => OR_KEYWORD.to_string(),
+            BinaryOperator::And => AND_KEYWORD.to_string(),
+        }
+    }
+
+    fn precedence(&self) -> Precedence {
+        match self {
+            BinaryOperator::Or => Precedence::Or,
+            BinaryOperator::And => Precedence::And,
+            BinaryOperator::Equality | BinaryOperator::Different => Precedence::Equality,
+            BinaryOperator::Less
+            | BinaryOperator::EqualOrLess
+            | BinaryOperator::Greater
+            | BinaryOperator::EqualOrGreater => Precedence::Comparison,
+            BinaryOperator::Addition | BinaryOperator::Subtraction => Precedence::Term,
+            BinaryOperator::Multiplication | BinaryOperator::Division => Precedence::Factor,
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod tests {
     use super::*;
 
@@ -153,19 +423,19 @@ mod tests {
     #[test]
     fn true_expression_to_code() {
         let expression = Expression::Literal(Literal::True);
-        assert_eq!(expression.to_code(), "True".to_string());
+        assert_eq!(expression.to_code(), "true".to_string());
     }
 
     #[test]
     fn false_expression_to_code() {
         let expression = Expression::Literal(Literal::False);
-        assert_eq!(expression.to_code(), "False".to_string());
+        assert_eq!(expression.to_code(), "false".to_string());
     }
 
     #[test]
     fn nil_expression_to_code() {
         let expression = Expression::Literal(Literal::Nil);
-        assert_eq!(expression.to_code(), "Nil".to_string());
+        assert_eq!(expression.to_code(), "nil".to_string());
     }
 
     #[test]
@@ -174,8 +444,9 @@ mod tests {
         let expression = Expression::Unary {
             operator: UnaryOperator::Negation,
             expression: Box::new(literal),
+            span: Span::default(),
         };
-        assert_eq!(expression.to_code(), "!False".to_string());
+        assert_eq!(expression.to_code(), "!false".to_string());
     }
 
     #[test]
@@ -184,6 +455,7 @@ mod tests {
         let expression = Expression::Unary {
             operator: UnaryOperator::Minus,
             expression: Box::new(literal),
+            span: Span::default(),
         };
         assert_eq!(expression.to_code(), "-4.2".to_string());
     }
@@ -196,8 +468,9 @@ mod tests {
             left: Box::new(left),
             operator: BinaryOperator::Equality,
             right: Box::new(right),
+            span: Span::default(),
         };
-        assert_eq!(expression.to_code(), "6.66 == False".to_string());
+        assert_eq!(expression.to_code(), "6.66 == false".to_string());
     }
 
     #[test]
@@ -208,8 +481,9 @@ mod tests {
             left: Box::new(left),
             operator: BinaryOperator::Different,
             right: Box::new(right),
+            span: Span::default(),
         };
-        assert_eq!(expression.to_code(), "Nil != Nil".to_string());
+        assert_eq!(expression.to_code(), "nil != nil".to_string());
     }
 
     #[test]
@@ -220,6 +494,7 @@ mod tests {
             left: Box::new(left),
             operator: BinaryOperator::Less,
             right: Box::new(right),
+            span: Span::default(),
         };
         assert_eq!(expression.to_code(), "3.14 < 3.16".to_string());
     }
@@ -232,6 +507,7 @@ mod tests {
             left: Box::new(left),
             operator: BinaryOperator::EqualOrLess,
             right: Box::new(right),
+            span: Span::default(),
         };
         assert_eq!(expression.to_code(), "-3.16 <= -3.14".to_string());
     }
@@ -244,6 +520,7 @@ mod tests {
             left: Box::new(left),
             operator: BinaryOperator::Greater,
             right: Box::new(right),
+            span: Span::default(),
         };
         assert_eq!(expression.to_code(), "\"Hello\" > \"World\"".to_string());
     }
@@ -256,10 +533,11 @@ mod tests {
             left: Box::new(left),
             operator: BinaryOperator::EqualOrGreater,
             right: Box::new(right),
+            span: Span::default(),
         };
         assert_eq!(
             expression.to_code(),
-            "\"Hello, world!\" >= True".to_string()
+            "\"Hello, world!\" >= true".to_string()
         );
     }
 
@@ -271,6 +549,7 @@ mod tests {
             left: Box::new(left),
             operator: BinaryOperator::Addition,
             right: Box::new(right),
+            span: Span::default(),
         };
         assert_eq!(expression.to_code(), "1.2 + 3.4".to_string());
     }
@@ -283,6 +562,7 @@ mod tests {
             left: Box::new(left),
             operator: BinaryOperator::Subtraction,
             right: Box::new(right),
+            span: Span::default(),
         };
         assert_eq!(expression.to_code(), "0.1 - -0.1".to_string()); //TODO: Do we really want this behaviour? No
     }
@@ -295,8 +575,9 @@ mod tests {
             left: Box::new(left),
             operator: BinaryOperator::Multiplication,
             right: Box::new(right),
+            span: Span::default(),
         };
-        assert_eq!(expression.to_code(), "False * True".to_string());
+        assert_eq!(expression.to_code(), "false * true".to_string());
     }
 
     #[test]
@@ -309,6 +590,7 @@ mod tests {
             left: Box::new(left),
             operator: BinaryOperator::Division,
             right: Box::new(right),
+            span: Span::default(),
         };
         assert_eq!(
             expression.to_code(),
@@ -327,4 +609,134 @@ mod tests {
             "(\"The quick brown fox did WHAT!?\")".to_string()
         );
     }
+
+    fn variable(identifier: &str) -> Expression {
+        Expression::Variable {
+            id: new_id(),
+            identifier: identifier.to_string(),
+            span: Span::default(),
+        }
+    }
+
+    fn binary(left: Expression, operator: BinaryOperator, right: Expression) -> Expression {
+        Expression::Binary {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+            span: Span::default(),
+        }
+    }
+
+    #[test]
+    fn variable_expression_to_code() {
+        assert_eq!(variable("answer").to_code(), "answer");
+    }
+
+    #[test]
+    fn operands_that_bind_less_tightly_get_parentheses() {
+        let sum = binary(variable("a"), BinaryOperator::Addition, variable("b"));
+        let product = binary(sum.clone(), BinaryOperator::Multiplication, variable("c"));
+        assert_eq!(product.to_code(), "(a + b) * c");
+        let sum_of_product = binary(variable("c"), BinaryOperator::Addition, product);
+        assert_eq!(sum_of_product.to_code(), "c + (a + b) * c");
+        let negated = Expression::Unary {
+            operator: UnaryOperator::Minus,
+            expression: Box::new(sum.clone()),
+            span: Span::default(),
+        };
+        assert_eq!(negated.to_code(), "-(a + b)");
+        let get = Expression::Get {
+            object: Box::new(sum),
+            name: "length".to_string(),
+            span: Span::default(),
+        };
+        assert_eq!(get.to_code(), "(a + b).length");
+    }
+
+    #[test]
+    fn associativity_decides_parentheses_between_equal_operators() {
+        let left = binary(
+            binary(variable("a"), BinaryOperator::Subtraction, variable("b")),
+            BinaryOperator::Subtraction,
+            variable("c"),
+        );
+        assert_eq!(left.to_code(), "a - b - c");
+        let right = binary(
+            variable("a"),
+            BinaryOperator::Subtraction,
+            binary(variable("b"), BinaryOperator::Subtraction, variable("c")),
+        );
+        assert_eq!(right.to_code(), "a - (b - c)");
+        // Comparisons group to the right in this parser.
+        let comparison = binary(
+            binary(variable("a"), BinaryOperator::Less, variable("b")),
+            BinaryOperator::Less,
+            variable("c"),
+        );
+        assert_eq!(comparison.to_code(), "(a < b) < c");
+    }
+
+    #[test]
+    fn assignments_nest_to_the_right() {
+        let assignment = Expression::Assignment {
+            id: new_id(),
+            identifier: "a".to_string(),
+            value: Box::new(Expression::Set {
+                object: Box::new(variable("b")),
+                name: "c".to_string(),
+                value: Box::new(binary(variable("d"), BinaryOperator::Or, variable("e"))),
+                span: Span::default(),
+            }),
+            span: Span::default(),
+        };
+        assert_eq!(assignment.to_code(), "a = b.c = d or e");
+    }
+
+    #[test]
+    fn properties_of_numbers_keep_the_dot_apart() {
+        let get = Expression::Get {
+            object: Box::new(Expression::Literal(Literal::Number(1.0.into()))),
+            name: "x".to_string(),
+            span: Span::default(),
+        };
+        assert_eq!(get.to_code(), "(1).x");
+    }
+
+    #[test]
+    fn serialized_without_ids() {
+        let expression = Expression::Variable {
+            id: new_id(),
+            identifier: "a".to_string(),
+            span: Span::new(1, 1, 0, 1),
+        };
+        assert_eq!(
+            serde_json::to_string(&expression).unwrap(),
+            r#"{"variable":{"identifier":"a","span":{"line":1,"column":1,"start":0,"end":1}}}"#
+        );
+    }
+
+    #[test]
+    fn deserialized_with_new_ids_and_optional_spans() {
+        let json = r#"{"variable":{"identifier":"a"}}"#;
+        let first: Expression = serde_json::from_str(json).unwrap();
+        let second: Expression = serde_json::from_str(json).unwrap();
+        let (Expression::Variable { id, span, .. }, Expression::Variable { id: other_id, .. }) =
+            (first, second)
+        else {
+            panic!("expected variables");
+        };
+        assert_ne!(id, other_id);
+        assert_eq!(span, Span::default());
+    }
+
+    #[test]
+    fn literals_serialize_as_plain_values() {
+        let literal = Expression::Literal(Literal::Number(1.5.into()));
+        assert_eq!(
+            serde_json::to_string(&literal).unwrap(),
+            r#"{"literal":{"number":1.5}}"#
+        );
+        let literal: Expression = serde_json::from_str(r#"{"literal":"true"}"#).unwrap();
+        assert_eq!(literal, Expression::Literal(Literal::True));
+    }
 }
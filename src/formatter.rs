@@ -0,0 +1,666 @@
+//! Formats source code in one canonical layout: four spaces of indentation, one statement per
+//! line and opening braces on the line of the statement they belong to.
+//!
+//! The syntax tree has no place for comments, so it is printed without them and they are put
+//! back afterwards, next to the same tokens as in the source: a comment after code stays after
+//! the token it follows, and a comment on a line of its own stays on a line before the token
+//! after it. Blank lines between lines are kept, at most one in a row.
+
+use crate::diagnostics::{report, report_without_source};
+use crate::expression::{Expression, Literal};
+use crate::parser::Parser;
+use crate::result::Error;
+use crate::scanner::Scanner;
+use crate::statement::{ClassDeclaration, FunctionDeclaration, Statement};
+use crate::token::{Span, Token, TokenType, Trivia, CLASS_KEYWORD, FUN_KEYWORD, VAR_KEYWORD};
+use std::collections::VecDeque;
+use std::mem::{discriminant, Discriminant};
+
+const INDENTATION: &str = "    ";
+
+/// The exit code of `fmt --check` when a file isn't formatted, the same as other formatters use.
+pub const NOT_FORMATTED: i32 = 1;
+
+/// Formats a program. Source with errors is not formatted, since there is no syntax tree to
+/// print.
+pub fn format(source: &str) -> Result<String, Vec<Error>> {
+    let (tokens, trivia) = Scanner::new(source, 1)
+        .scan_tokens_and_trivia()
+        .map_err(|error| vec![error])?;
+    let positions = positions(&tokens);
+    let mut printer = Printer {
+        loops: loop_syntax(&tokens),
+        ..Printer::default()
+    };
+    let statements = Parser::new(tokens).parse().into_result()?;
+    printer.lines(&statements, Printer::statement);
+    Ok(restore_trivia(&printer.output, &positions, trivia))
+}
+
+/// Formats files in place, or with `check` only lists the files that aren't formatted. Returns
+/// the exit code: the one of the first error, or [`NOT_FORMATTED`] if `check` found a file to
+/// format.
+pub fn format_files(paths: &[String], check: bool) -> i32 {
+    let mut exit_code = exitcode::OK;
+    for path in paths {
+        let code = match format_file(path, check) {
+            Ok(true) => exitcode::OK,
+            Ok(false) => {
+                println!("{path} is not formatted");
+                NOT_FORMATTED
+            }
+            Err(code) => code,
+        };
+        if exit_code == exitcode::OK {
+            exit_code = code;
+        }
+    }
+    exit_code
+}
+
+/// Whether the file was already formatted, or the exit code of the error that stopped it from
+/// being formatted, after reporting it.
+fn format_file(path: &str, check: bool) -> Result<bool, i32> {
+    let io_error = |error: std::io::Error| {
+        let error = Error::from(error);
+        report_without_source(&error);
+        error.exit_code()
+    };
+    let source = std::fs::read_to_string(path).map_err(io_error)?;
+    let formatted = format(&source).map_err(|errors| {
+        report(path, &source, &errors);
+        errors[0].exit_code()
+    })?;
+    if formatted == source {
+        return Ok(true);
+    }
+    if check {
+        return Ok(false);
+    }
+    std::fs::write(path, formatted).map_err(io_error)?;
+    Ok(true)
+}
+
+/// How a loop was written, so it is printed the same way. The syntax tree has a `while` loop for
+/// each of them.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum LoopSyntax {
+    While,
+    For,
+    /// A `for` loop whose initializer is the statement before it, in a block of their own.
+    ForWithInitializer,
+}
+
+/// How each loop was written, in the order their keywords come in the tokens. The printer
+/// reaches the loops of the syntax tree in the same order.
+fn loop_syntax(tokens: &[Token]) -> VecDeque<LoopSyntax> {
+    tokens
+        .iter()
+        .enumerate()
+        .filter_map(|(index, token)| match token.token_type {
+            TokenType::While => Some(LoopSyntax::While),
+            // The initializer comes after the opening parenthesis, unless it is left out.
+            TokenType::For => match tokens.get(index + 2) {
+                Some(Token {
+                    token_type: TokenType::Semicolon,
+                    ..
+                }) => Some(LoopSyntax::For),
+                _ => Some(LoopSyntax::ForWithInitializer),
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// The kind of a token, without its value, and where it is.
+type Position = (Discriminant<TokenType>, Span);
+
+fn positions(tokens: &[Token]) -> Vec<Position> {
+    tokens
+        .iter()
+        .map(|token| (discriminant(&token.token_type), token.span))
+        .collect()
+}
+
+fn is_kind(position: &Position, token_type: &TokenType) -> bool {
+    position.0 == discriminant(token_type)
+}
+
+/// Pairs each token of the source with the same token of the printed code. Printing only adds
+/// and removes parentheses, so the other tokens come in the same order. The end of the source is
+/// always paired with the end of the code.
+fn pair_tokens(source: &[Position], printed: &[Position]) -> Vec<Option<usize>> {
+    let is_parenthesis = |position: &Position| {
+        is_kind(position, &TokenType::LeftParen) || is_kind(position, &TokenType::RightParen)
+    };
+    let mut pairs = vec![None; source.len()];
+    let (mut from, mut to) = (0, 0);
+    while from < source.len() && to < printed.len() {
+        if source[from].0 == printed[to].0
+            || !is_parenthesis(&source[from]) && !is_parenthesis(&printed[to])
+        {
+            pairs[from] = Some(to);
+            from += 1;
+            to += 1;
+        } else if is_parenthesis(&source[from]) {
+            from += 1;
+        } else {
+            to += 1;
+        }
+    }
+    pairs[source.len() - 1] = Some(printed.len() - 1);
+    pairs
+}
+
+/// The indentation of a part of a line that a comment moved onto a line of its own: one level
+/// deeper than the line, except for closing brackets and `else`, which line up with it.
+fn continued_indentation(indentation: &str, next: &Position) -> String {
+    let lines_up = [
+        TokenType::RightParen,
+        TokenType::RightBrace,
+        TokenType::RightBracket,
+        TokenType::Else,
+    ];
+    if lines_up.iter().any(|token_type| is_kind(next, token_type)) {
+        indentation.to_string()
+    } else {
+        format!("{indentation}{INDENTATION}")
+    }
+}
+
+/// Puts the comments and blank lines of the source back into the printed code, next to the same
+/// tokens. Comments in the middle of a line end it, and the rest of the line goes on the next
+/// one. Blank lines are dropped there and at the start and end of blocks.
+fn restore_trivia(code: &str, source: &[Position], trivia: Vec<(Trivia, Span)>) -> String {
+    let printed = positions(
+        &Scanner::new(code, 1)
+            .scan_tokens()
+            .expect("printed code can be scanned"),
+    );
+    let pairs = pair_tokens(source, &printed);
+    // The comments and blank lines on the lines before each printed token, and the comments
+    // after it on its line.
+    let mut before = vec![Vec::new(); printed.len()];
+    let mut after = vec![Vec::new(); printed.len()];
+    let mut next = 0;
+    for (trivia, span) in trivia {
+        while source[next].1.start < span.start {
+            next += 1;
+        }
+        let following = (next..source.len())
+            .find_map(|index| pairs[index])
+            .expect("the end of the source is paired");
+        match trivia {
+            // A parenthesis that was removed takes its comment with it to the token before.
+            Trivia::TrailingComment(text) => match (0..next).rev().find_map(|index| pairs[index]) {
+                Some(previous) => after[previous].push(text),
+                None => before[following].push(Trivia::Comment(text)),
+            },
+            trivia => before[following].push(trivia),
+        }
+    }
+
+    let mut output = String::new();
+    let mut indentation = "";
+    let mut previous_end = 0;
+    let mut line_broken = false;
+    for (index, position @ (_, span)) in printed.iter().enumerate() {
+        let gap = &code[previous_end..span.start];
+        let comments = before[index]
+            .iter()
+            .filter_map(|trivia| match trivia {
+                Trivia::Comment(text) | Trivia::TrailingComment(text) => Some(text),
+                Trivia::BlankLine => None,
+            })
+            .collect::<Vec<_>>();
+        if index == 0 || gap.contains('\n') {
+            indentation = &gap[gap.rfind('\n').map_or(0, |newline| newline + 1)..];
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            let ends_block =
+                is_kind(position, &TokenType::RightBrace) || is_kind(position, &TokenType::EOF);
+            // Comments before a closing brace are the last lines inside its block.
+            let comment_indentation = if is_kind(position, &TokenType::RightBrace) {
+                format!("{indentation}{INDENTATION}")
+            } else {
+                indentation.to_string()
+            };
+            let mut keep_blank_line =
+                index > 0 && !is_kind(&printed[index - 1], &TokenType::LeftBrace);
+            let mut blank_line = false;
+            for trivia in &before[index] {
+                match trivia {
+                    Trivia::BlankLine => blank_line = true,
+                    Trivia::Comment(text) | Trivia::TrailingComment(text) => {
+                        if blank_line && keep_blank_line {
+                            output.push('\n');
+                        }
+                        output.push_str(&format!("{comment_indentation}//{text}\n"));
+                        blank_line = false;
+                        keep_blank_line = true;
+                    }
+                }
+            }
+            if blank_line && keep_blank_line && !ends_block {
+                output.push('\n');
+            }
+            output.push_str(indentation);
+        } else if !comments.is_empty() {
+            output.truncate(output.trim_end_matches(' ').len());
+            if !output.ends_with('\n') {
+                output.push('\n');
+            }
+            for text in comments {
+                output.push_str(&format!("{indentation}{INDENTATION}//{text}\n"));
+            }
+            output.push_str(&continued_indentation(indentation, position));
+        } else if !line_broken {
+            output.push_str(gap);
+        }
+        output.push_str(&code[span.start..span.end]);
+        previous_end = span.end;
+
+        line_broken = false;
+        for (count, text) in after[index].iter().enumerate() {
+            if count == 0 {
+                output.push_str(&format!(" //{text}"));
+            } else {
+                output.push_str(&format!("\n{indentation}{INDENTATION}//{text}"));
+            }
+        }
+        match printed.get(index + 1) {
+            Some(next)
+                if !after[index].is_empty() && !code[span.end..next.1.start].contains('\n') =>
+            {
+                output.push('\n');
+                output.push_str(&continued_indentation(indentation, next));
+                line_broken = true;
+            }
+            _ => {}
+        }
+    }
+    output
+}
+
+#[derive(Default)]
+struct Printer {
+    output: String,
+    depth: usize,
+    loops: VecDeque<LoopSyntax>,
+}
+
+impl Printer {
+    /// Writes each item on a line of its own at the current depth.
+    fn lines<T>(&mut self, items: &[T], write: fn(&mut Self, &T)) {
+        for item in items {
+            self.indent();
+            write(self, item);
+            self.output.push('\n');
+        }
+    }
+
+    fn indent(&mut self) {
+        self.output.push_str(&INDENTATION.repeat(self.depth));
+    }
+
+    /// Writes items on lines between braces, or `{}` when there are none.
+    fn braced<T>(&mut self, items: &[T], write: fn(&mut Self, &T)) {
+        if items.is_empty() {
+            self.output.push_str("{}");
+            return;
+        }
+        self.output.push_str("{\n");
+        self.depth += 1;
+        self.lines(items, write);
+        self.depth -= 1;
+        self.indent();
+        self.output.push('}');
+    }
+
+    /// Writes a statement from the current position, without the line break after it.
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expression(expression) => {
+                let code = expression.to_code();
+                // A statement starting with a brace would be a block, not a map.
+                if code.starts_with('{') {
+                    self.output.push_str(&format!("({code});"));
+                } else {
+                    self.output.push_str(&format!("{code};"));
+                }
+            }
+            Statement::If {
+                condition,
+                then_statement,
+                else_statement,
+            } => {
+                self.output
+                    .push_str(&format!("if ({}) ", condition.to_code()));
+                self.statement(then_statement);
+                if let Some(else_statement) = else_statement {
+                    // `else` follows a closing brace, but not a semicolon.
+                    if self.output.ends_with('}') {
+                        self.output.push(' ');
+                    } else {
+                        self.output.push('\n');
+                        self.indent();
+                    }
+                    self.output.push_str("else ");
+                    self.statement(else_statement);
+                }
+            }
+            Statement::Print(expression) => self
+                .output
+                .push_str(&format!("print {};", expression.to_code())),
+            Statement::Return { expression, .. } => match expression {
+                Some(expression) => self
+                    .output
+                    .push_str(&format!("return {};", expression.to_code())),
+                None => self.output.push_str("return;"),
+            },
+            Statement::VariableDeclaration {
+                identifier,
+                expression,
+                ..
+            } => match expression {
+                Some(expression) => self.output.push_str(&format!(
+                    "{VAR_KEYWORD} {identifier} = {};",
+                    expression.to_code()
+                )),
+                None => self
+                    .output
+                    .push_str(&format!("{VAR_KEYWORD} {identifier};")),
+            },
+            Statement::FunctionDeclaration(declaration) => {
+                self.output.push_str(&format!("{FUN_KEYWORD} "));
+                self.function(declaration);
+            }
+            Statement::ClassDeclaration(declaration) => self.class(declaration),
+            Statement::While {
+                expression,
+                statement,
+                increment,
+            } => match self.loops.pop_front() {
+                Some(LoopSyntax::For | LoopSyntax::ForWithInitializer) => {
+                    self.for_loop(None, expression, increment.as_ref(), statement)
+                }
+                _ => {
+                    self.output
+                        .push_str(&format!("while ({}) ", expression.to_code()));
+                    self.statement(statement);
+                }
+            },
+            Statement::Break { .. } => self.output.push_str("break;"),
+            Statement::Continue { .. } => self.output.push_str("continue;"),
+            // An initializer can't have loops in it, so the loop after it is the next one.
+            Statement::Block(statements) => match statements.as_slice() {
+                [initializer
+                @ (Statement::VariableDeclaration { .. } | Statement::Expression(_)), Statement::While {
+                    expression,
+                    statement,
+                    increment,
+                }] if self.loops.front() == Some(&LoopSyntax::ForWithInitializer) => {
+                    self.loops.pop_front();
+                    self.for_loop(Some(initializer), expression, increment.as_ref(), statement)
+                }
+                statements => self.braced(statements, Self::statement),
+            },
+        }
+    }
+
+    /// Writes a `for` loop. A missing condition was parsed as `true`, which is written back as
+    /// a missing condition.
+    fn for_loop(
+        &mut self,
+        initializer: Option<&Statement>,
+        condition: &Expression,
+        increment: Option<&Expression>,
+        body: &Statement,
+    ) {
+        self.output.push_str("for (");
+        match initializer {
+            Some(initializer) => self.statement(initializer),
+            None => self.output.push(';'),
+        }
+        if condition != &Expression::Literal(Literal::True) {
+            self.output.push_str(&format!(" {}", condition.to_code()));
+        }
+        self.output.push(';');
+        if let Some(increment) = increment {
+            self.output.push_str(&format!(" {}", increment.to_code()));
+        }
+        self.output.push_str(") ");
+        self.statement(body);
+    }
+
+    /// Writes a function without the `fun` keyword, the way methods are written.
+    fn function(&mut self, declaration: &FunctionDeclaration) {
+        let parameters = declaration
+            .parameters
+            .iter()
+            .map(|parameter| parameter.name.as_str())
+            .intersperse(", ")
+            .collect::<String>();
+        self.output
+            .push_str(&format!("{}({parameters}) ", declaration.identifier));
+        self.statement(&declaration.body);
+    }
+
+    fn class(&mut self, declaration: &ClassDeclaration) {
+        self.output
+            .push_str(&format!("{CLASS_KEYWORD} {} ", declaration.identifier));
+        if let Some(superclass) = &declaration.superclass {
+            self.output
+                .push_str(&format!("< {} ", superclass.to_code()));
+        }
+        self.braced(&declaration.methods, Self::function);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::dump_syntax_tree;
+    use serde_json::Value;
+
+    /// The syntax tree of the source as JSON, without locations, which formatting changes.
+    fn syntax_tree(source: &str) -> Value {
+        fn remove_spans(value: &mut Value) {
+            match value {
+                Value::Object(object) => {
+                    object.remove("span");
+                    object.values_mut().for_each(remove_spans);
+                }
+                Value::Array(array) => array.iter_mut().for_each(remove_spans),
+                _ => {}
+            }
+        }
+        let mut tree = serde_json::from_str(&dump_syntax_tree(source).unwrap()).unwrap();
+        remove_spans(&mut tree);
+        tree
+    }
+
+    /// Formats the source and checks that the result means the same and is formatted.
+    fn formatted(source: &str) -> String {
+        let formatted = format(source).unwrap();
+        assert_eq!(syntax_tree(&formatted), syntax_tree(source), "{formatted}");
+        assert_eq!(format(&formatted).unwrap(), formatted);
+        formatted
+    }
+
+    #[test]
+    fn statements_get_their_own_lines_and_indentation() {
+        assert_eq!(
+            formatted("fun add(a,b){var sum=a+b;return sum;}print add(1,2);"),
+            "fun add(a, b) {\n    var sum = a + b;\n    return sum;\n}\nprint add(1, 2);\n"
+        );
+    }
+
+    #[test]
+    fn classes_and_methods() {
+        assert_eq!(
+            formatted("class B < A { init(x) { super.init(x); this.y = x * 2; } empty() {} }"),
+            "class B < A {\n    init(x) {\n        super.init(x);\n        this.y = x * 2;\n    }\n    empty() {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_eq!(
+            formatted("if (a) { print 1; } else if (b) print 2; else { print 3; }"),
+            "if (a) {\n    print 1;\n} else if (b) print 2;\nelse {\n    print 3;\n}\n"
+        );
+        assert_eq!(
+            formatted("while (i < 3) { i = i + 1; if (i == 2) continue; break; }"),
+            "while (i < 3) {\n    i = i + 1;\n    if (i == 2) continue;\n    break;\n}\n"
+        );
+    }
+
+    #[test]
+    fn for_loops_stay_for_loops() {
+        assert_eq!(
+            formatted("for (var i = 0; i < 3; i = i + 1) print i;"),
+            "for (var i = 0; i < 3; i = i + 1) print i;\n"
+        );
+        assert_eq!(formatted("for(;;){break;}"), "for (;;) {\n    break;\n}\n");
+        assert_eq!(
+            formatted("for (i = 0; i < 3;) {}"),
+            "for (i = 0; i < 3;) {}\n"
+        );
+        // A block that only looks like a loop with an initializer stays a block.
+        assert_eq!(
+            formatted("{ var i = 0; while (i < 3) i = i + 1; }"),
+            "{\n    var i = 0;\n    while (i < 3) i = i + 1;\n}\n"
+        );
+        assert_eq!(
+            formatted("{ fun f() { for (var i = 0;;) {} } while (true) {} }"),
+            "{\n    fun f() {\n        for (var i = 0;;) {}\n    }\n    while (true) {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn parentheses_are_kept_where_they_matter() {
+        assert_eq!(
+            formatted("print ((1 + 2)) * (3 - (4 - 5));"),
+            "print (1 + 2) * (3 - (4 - 5));\n"
+        );
+        assert_eq!(formatted("print -(a.b)[0]();"), "print -a.b[0]();\n");
+    }
+
+    #[test]
+    fn maps_at_the_start_of_a_statement_are_not_blocks() {
+        assert_eq!(
+            formatted("({\"a\": [1, 2]})[\"a\"];"),
+            "({\"a\": [1, 2]}[\"a\"]);\n"
+        );
+    }
+
+    #[test]
+    fn comments_are_kept() {
+        let source = "\
+// A counter.
+var count = 0; // starts at zero
+fun next() { // the next value
+    // count up
+    count = count + 1;
+    return count; // new value
+} // next
+class A {
+    // first
+    a() {}
+    // last
+}
+";
+        assert_eq!(
+            formatted(source),
+            "\
+// A counter.
+var count = 0; // starts at zero
+fun next() { // the next value
+    // count up
+    count = count + 1;
+    return count; // new value
+} // next
+class A {
+    // first
+    a() {}
+    // last
+}
+"
+        );
+    }
+
+    #[test]
+    fn single_blank_lines_are_kept_between_statements() {
+        assert_eq!(
+            formatted("\n\nvar a = 1;\n\n\n\nvar b = 2;\n{\n\n  print a;\n\n}\n\n"),
+            "var a = 1;\n\nvar b = 2;\n{\n    print a;\n}\n"
+        );
+    }
+
+    #[test]
+    fn code_with_errors_is_not_formatted() {
+        assert!(matches!(
+            format("print ;").unwrap_err().as_slice(),
+            [Error::ExpectedExpression { .. }]
+        ));
+    }
+
+    #[test]
+    fn comments_stay_after_the_token_they_follow() {
+        assert_eq!(
+            formatted("if (x) // after cond\n print 1; // then\nelse // else\n print 2;"),
+            "if (x) // after cond\n    print 1; // then\nelse // else\n    print 2;\n"
+        );
+        assert_eq!(
+            formatted("var x = 1 + // one\n 2; // two"),
+            "var x = 1 + // one\n    2; // two\n"
+        );
+        assert_eq!(
+            formatted("var xs = [\n  1, // one\n  2\n];\n"),
+            "var xs = [1, // one\n    2];\n"
+        );
+        assert_eq!(
+            formatted("if (true // yes\n) {\n  print 1;\n}\n"),
+            "if (true // yes\n) {\n    print 1;\n}\n"
+        );
+        assert_eq!(
+            formatted("fun f() { // nothing yet\n}\n"),
+            "fun f() { // nothing yet\n}\n"
+        );
+    }
+
+    #[test]
+    fn comments_on_lines_of_their_own_stay_before_the_next_token() {
+        assert_eq!(
+            formatted("print 1 +\n  // one\n  2 + // two\n  3; // three\nprint 4;\n"),
+            "print 1 +\n    // one\n    2 + // two\n    3; // three\nprint 4;\n"
+        );
+        assert_eq!(
+            formatted("class A {\n  // nothing yet\n}\n// the end\n"),
+            "class A {\n    // nothing yet\n}\n// the end\n"
+        );
+    }
+
+    #[test]
+    fn comments_in_removed_parentheses_are_kept() {
+        assert_eq!(
+            formatted("print ((1 // one\n) + 2);\n"),
+            "print 1 // one\n    + 2;\n"
+        );
+    }
+
+    #[test]
+    fn nested_unary_operators_keep_their_parentheses() {
+        assert_eq!(formatted("print -(-1);"), "print -(-1);\n");
+        assert_eq!(formatted("print !!true;"), "print !(!true);\n");
+        assert_eq!(formatted("print -(1 + 2);"), "print -(1 + 2);\n");
+    }
+
+    #[test]
+    fn empty_source_stays_empty() {
+        assert_eq!(formatted(""), "");
+        assert_eq!(formatted("\n\n"), "");
+    }
+}
@@ -0,0 +1,271 @@
+//! Garbage collection for heap objects: the tree-walker's environments, functions, classes and
+//! instances, the virtual machine's closures, upvalues, classes and instances, and the lists and
+//! maps both share.
+//!
+//! Objects are reference counted, which frees most garbage as soon as it is dropped, but not
+//! cycles. A function stored in the scope it captures, or a list containing itself, keeps itself
+//! alive forever. Every heap object is registered here when it is created, and [`collect`] finds
+//! and frees the ones that are only referenced by each other.
+//!
+//! The roots are whatever refers to the heap from outside of it: the interpreters' globals, the
+//! environments on the tree-walker's call stack, the virtual machine's stack and open upvalues,
+//! values being evaluated, and values held by Rust code embedding an interpreter. They don't need
+//! to be listed, because they show up as references that no heap object accounts for. Collection works in three steps:
+//!
+//! 1. Each object starts with its reference count, and every reference from another heap object
+//!    is subtracted from it. Objects with references left over are roots.
+//! 2. Everything reachable from the roots is marked.
+//! 3. Unmarked objects are garbage. Their references are cleared, which breaks the cycles so the
+//!    reference counts drop to zero and free them.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/// How many objects are tracked before the first automatic collection.
+const INITIAL_THRESHOLD: usize = 1024;
+
+/// A heap object that can refer to other heap objects.
+pub trait Trace {
+    /// Calls `visit` with the address of every heap object this one refers to.
+    fn trace(&self, visit: &mut dyn FnMut(Address));
+
+    /// Drops the references to other heap objects, to break the cycles garbage is part of.
+    fn clear(&self) {}
+}
+
+/// Identifies a heap object by the address of its reference-counted allocation.
+pub type Address = *const ();
+
+pub fn address<T: ?Sized>(object: &Rc<T>) -> Address {
+    Rc::as_ptr(object) as *const ()
+}
+
+/// Counts of what the collector has done so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GcStats {
+    /// Number of collections, automatic or requested.
+    pub collections: usize,
+    /// Heap objects currently alive.
+    pub live: usize,
+    /// Heap objects freed by collections, in total.
+    pub collected: usize,
+    /// Number of tracked objects that triggers the next automatic collection.
+    pub threshold: usize,
+}
+
+trait Tracked {
+    fn address(&self) -> Address;
+    fn strong_count(&self) -> usize;
+    fn trace(&self, visit: &mut dyn FnMut(Address));
+    fn clear(&self);
+}
+
+impl<T: ?Sized + Trace> Tracked for Weak<T> {
+    fn address(&self) -> Address {
+        self.as_ptr() as *const ()
+    }
+
+    fn strong_count(&self) -> usize {
+        Weak::strong_count(self)
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        if let Some(object) = self.upgrade() {
+            object.trace(visit);
+        }
+    }
+
+    fn clear(&self) {
+        if let Some(object) = self.upgrade() {
+            object.clear();
+        }
+    }
+}
+
+struct Heap {
+    objects: Vec<Box<dyn Tracked>>,
+    stats: GcStats,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Heap {
+            objects: Vec::new(),
+            stats: GcStats {
+                threshold: INITIAL_THRESHOLD,
+                ..GcStats::default()
+            },
+        }
+    }
+}
+
+impl Heap {
+    fn collect(&mut self) {
+        self.objects.retain(|object| object.strong_count() > 0);
+        let indices = self
+            .objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| (object.address(), index))
+            .collect::<HashMap<_, _>>();
+
+        let mut outside_references = self
+            .objects
+            .iter()
+            .map(|object| object.strong_count())
+            .collect::<Vec<_>>();
+        for object in &self.objects {
+            object.trace(&mut |child| {
+                if let Some(index) = indices.get(&child) {
+                    outside_references[*index] -= 1;
+                }
+            });
+        }
+
+        let mut reachable = vec![false; self.objects.len()];
+        let mut pending = (0..self.objects.len())
+            .filter(|index| outside_references[*index] > 0)
+            .collect::<Vec<_>>();
+        while let Some(index) = pending.pop() {
+            if std::mem::replace(&mut reachable[index], true) {
+                continue;
+            }
+            self.objects[index].trace(&mut |child| {
+                if let Some(child) = indices.get(&child) {
+                    pending.push(*child);
+                }
+            });
+        }
+
+        let (live, garbage): (Vec<_>, Vec<_>) = std::mem::take(&mut self.objects)
+            .into_iter()
+            .zip(reachable)
+            .partition(|(_, reachable)| *reachable);
+        for (object, _) in &garbage {
+            object.clear();
+        }
+        self.objects = live.into_iter().map(|(object, _)| object).collect();
+        self.stats.collections += 1;
+        self.stats.collected += garbage.len();
+        self.stats.live = self.objects.len();
+        self.stats.threshold = (self.objects.len() * 2).max(INITIAL_THRESHOLD);
+    }
+}
+
+thread_local! {
+    /// Heap objects are shared between interpreters on the same thread, like the `Rc`s they are
+    /// made of.
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::default());
+}
+
+/// Registers a new heap object, collecting garbage first if enough objects were created since
+/// the last collection.
+pub fn track<T: ?Sized + Trace + 'static>(object: &Rc<T>) {
+    HEAP.with_borrow_mut(|heap| {
+        if heap.objects.len() >= heap.stats.threshold {
+            heap.collect();
+        }
+        heap.objects.push(Box::new(Rc::downgrade(object)));
+    });
+}
+
+/// Frees every heap object that is only reachable from other garbage.
+pub fn collect() -> GcStats {
+    HEAP.with_borrow_mut(|heap| {
+        heap.collect();
+        heap.stats
+    })
+}
+
+pub fn stats() -> GcStats {
+    HEAP.with_borrow(|heap| GcStats {
+        live: heap
+            .objects
+            .iter()
+            .filter(|object| object.strong_count() > 0)
+            .count(),
+        ..heap.stats
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+    use crate::interpreter::Interpreter;
+    use crate::list::List;
+    use crate::object::Object;
+
+    #[test]
+    fn closure_stored_in_its_own_scope_is_collected() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("fun outer() { fun inner() { return inner; } return inner; } outer(); outer();")
+            .unwrap();
+        let before = stats().live;
+        let after = collect();
+        // Each call leaves its environment, the environment of its body and the inner function
+        // behind.
+        assert_eq!(after.collected, 6);
+        assert_eq!(after.live, before - 6);
+    }
+
+    #[test]
+    fn reachable_cycles_survive() {
+        let mut interpreter = Interpreter::new();
+        let output = interpreter.capture_output();
+        interpreter
+            .eval("fun count(n) { if (n > 0) return count(n - 1); return n; }")
+            .unwrap();
+        collect();
+        interpreter.eval("print count(3);").unwrap();
+        assert_eq!(output.contents(), "0\n");
+    }
+
+    #[test]
+    fn values_held_from_rust_are_roots() {
+        let mut environment = Environment::new();
+        let list = List::default();
+        list.push(Object::List(list.clone()));
+        environment.define("list".to_string(), Object::List(list.clone()));
+        drop(environment);
+        assert_eq!(collect().collected, 0);
+        assert_eq!(list.len(), 1);
+    }
+
+    #[test]
+    fn list_containing_itself_is_collected() {
+        let list = List::default();
+        list.push(Object::List(list.clone()));
+        assert_eq!(stats().live, 1);
+        drop(list);
+        assert_eq!(stats().live, 1);
+        assert_eq!(collect().collected, 1);
+        assert_eq!(stats().live, 0);
+    }
+
+    #[test]
+    fn instance_referring_to_itself_is_collected() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("class Node { init() { this.next = this; this.method = this.init; } } Node();")
+            .unwrap();
+        let first = collect();
+        assert!(first.collected >= 3);
+        let again = collect();
+        assert_eq!(again.collected, first.collected);
+        assert_eq!(again.collections, 2);
+    }
+
+    #[test]
+    fn collects_automatically() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("for (var i = 0; i < 2000; i = i + 1) { var xs = []; push(xs, xs); }")
+            .unwrap();
+        let stats = stats();
+        assert!(stats.collections >= 1);
+        assert!(stats.live < INITIAL_THRESHOLD);
+    }
+}
@@ -1,140 +1,169 @@
+use crate::backend::{Analysis, Backend};
+use crate::class::{Class, INITIALIZER_NAME};
 use crate::environment::Environment;
-use crate::expression::{BinaryOperator, Expression, Literal, UnaryOperator};
+use crate::expression::{BinaryOperator, Expression, ExpressionId, Literal, UnaryOperator};
+use crate::heap;
+use crate::heap::GcStats;
+use crate::list::List;
+use crate::map::Map;
+use crate::native;
+use crate::native::{Globals, NativeFunction, Runtime};
 use crate::object;
-use crate::object::{Callable, Function, Object};
-use crate::parser::*;
-use crate::resolver::Resolver;
+use crate::object::{Arity, Callable, Function, Object};
+use crate::output::{CapturedOutput, Output};
+use crate::resolver::{Locals, Slot};
 use crate::result::*;
-use crate::scanner::*;
-use crate::statement::Statement;
-use std::collections::HashMap;
+use crate::statement::{ClassDeclaration, Statement};
+use crate::stdlib;
+use crate::token::Span;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::io::Write;
+use std::path::Path;
 use std::rc::Rc;
 
+/// How deeply Lox functions can call each other, the same as in the virtual machine. Runaway
+/// recursion is an error instead of overflowing the stack of the thread running the interpreter.
+const MAX_CALL_DEPTH: usize = 1024;
+
+/// Each call takes several frames of the Rust stack, far more in debug builds, so calls continue
+/// on a newly allocated stack when less than this is left.
+pub(crate) const STACK_RED_ZONE: usize = 128 * 1024;
+pub(crate) const STACK_GROWTH: usize = 2 * 1024 * 1024;
+
+/// How a statement finished. Anything but `Normal` skips the rest of the enclosing statements,
+/// up to the loop or function call that handles it.
+#[derive(Debug)]
+pub(crate) enum ControlFlow {
+    Normal,
+    Return(Object),
+    Break,
+    Continue,
+}
+
+/// A tree-walking Lox interpreter. Globals and definitions persist between evaluations.
+///
+/// ```
+/// use rust_lox::{argument, Interpreter, Object};
+///
+/// let mut interpreter = Interpreter::new();
+/// let output = interpreter.capture_output();
+/// interpreter.define_native("twice", 1, |_, arguments| {
+///     let number: f64 = argument(&arguments, 0)?;
+///     Ok(Object::Number((number * 2.0).into()))
+/// });
+/// interpreter.eval("var answer = twice(21); print answer;").unwrap();
+/// assert_eq!(interpreter.get_global("answer"), Some(Object::Number(42.0.into())));
+/// assert_eq!(output.contents(), "42\n");
+/// ```
 #[derive(Clone)]
 pub struct Interpreter {
     globals: Environment,
-    pub environment: Environment,
-    locals: HashMap<Expression, usize>,
+    pub(crate) environment: Environment,
+    locals: Rc<Locals>,
+    output: Output,
+    /// How many function calls this interpreter is nested in.
+    call_depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
-        let mut globals = Environment::new();
-
-        #[derive(Debug)]
-        struct Clock;
-        impl Callable for Clock {
-            fn signature(&self) -> String {
-                "clock".to_string()
-            }
-            fn arity(&self) -> usize {
-                0
-            }
-            fn call(
-                &self,
-                _interpreter: &Interpreter,
-                _arguments: Vec<Object>,
-            ) -> Result<Object, object::Error> {
-                todo!()
-            }
-        }
-        globals.define("clock".to_string(), Object::Function(Rc::new(Clock {})));
-
+        let globals = Environment::new();
         let environment = globals.clone();
-        Interpreter {
+        let mut interpreter = Interpreter {
             globals,
             environment,
-            locals: HashMap::new(),
-        }
+            locals: Rc::default(),
+            output: Rc::new(RefCell::new(std::io::stdout())),
+            call_depth: 0,
+        };
+        native::define_builtins(&mut interpreter);
+        stdlib::define(&mut interpreter);
+        interpreter
     }
 
-    pub fn new_for_closure(&self, environment: Environment) -> Interpreter {
-        Interpreter {
+    /// An interpreter for the body of a function called from this one. Fails when calls are
+    /// nested too deeply.
+    pub(crate) fn new_for_closure(
+        &self,
+        environment: Environment,
+        locals: Rc<Locals>,
+    ) -> Result<Interpreter, object::ErrorKind> {
+        // The top level counts as a call, like the script's frame in the virtual machine.
+        if self.call_depth + 1 == MAX_CALL_DEPTH {
+            return Err(object::ErrorKind::StackOverflow);
+        }
+        Ok(Interpreter {
             globals: self.globals.clone(),
             environment,
-            locals: self.locals.clone(),
-        }
+            locals,
+            output: self.output.clone(),
+            call_depth: self.call_depth + 1,
+        })
     }
 
-    pub fn repl(mut self) -> Result<(), Error> {
-        for line_number in 0..usize::MAX {
-            let input = Self::read()?;
-            let result = self.eval(&input, line_number);
-            if let Err(errors) = result {
-                for error in errors {
-                    println!("ERROR: {error:?}");
-                }
-            }
-        }
-        Err(Error::OutOfLineNumbers)
+    /// Runs Lox source code. Errors are reported with line numbers starting at 1.
+    pub fn eval(&mut self, source: &str) -> Result<(), Vec<Error>> {
+        self.eval_from_line(source, 1)
     }
-    pub fn run_file(mut self, path: String) -> Result<(), Error> {
-        use std::fs::File;
-        use std::io::{BufRead, BufReader};
-
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        for (line_number, line) in reader.lines().enumerate() {
-            let result = self.eval(&line?, line_number);
-            if let Err(errors) = result {
-                for error in errors {
-                    println!("ERROR in line {line_number}: {error:?}");
-                }
-            }
-        }
-        Ok(())
+
+    /// Reads and runs a Lox file.
+    pub fn eval_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Vec<Error>> {
+        let source = std::fs::read_to_string(path).map_err(|error| vec![error.into()])?;
+        self.eval(&source)
     }
 
-    fn read() -> Result<String, Error> {
-        use std::io::{stdin, stdout, Write};
-        print!(">");
-        stdout().flush().unwrap();
-        let mut input = String::new();
-        stdin().read_line(&mut input)?;
-        let Some(input) = input.lines().next() else {
-            return Err(Error::KeyboardInterrupt);
-        };
-        Ok(input.into())
+    pub fn get_global(&self, name: &str) -> Option<Object> {
+        self.globals.get(&name.to_string()).ok()
     }
 
-    fn eval(&mut self, source: &str, line_number: usize) -> Result<(), Vec<Error>> {
-        let tokens = match Scanner::new(source, line_number).scan_tokens() {
-            Ok(tokens) => tokens,
-            Err(error) => return Err(vec![error]),
-        };
+    /// Defines a global variable, replacing any previous value.
+    pub fn set_global(&mut self, name: &str, value: Object) {
+        self.globals.define(name.to_string(), value);
+    }
 
-        let statements = match Parser::new(tokens).parse() {
-            Ok(statements) => statements,
-            Err(error) => return Err(vec![error]),
-        };
+    /// Defines a global function implemented by a Rust closure. Lox code calling it with a
+    /// number of arguments that `arity` doesn't allow gets an error before the closure runs.
+    ///
+    /// The closure can call back into Lox through the [`Runtime`] it is given. Errors it creates
+    /// from an [`ErrorKind`](object::ErrorKind) with `?` or `into()` point at the call.
+    pub fn define_native<F>(&mut self, name: &str, arity: impl Into<Arity>, function: F)
+    where
+        F: Fn(&mut dyn Runtime, Vec<Object>) -> Result<Object, object::Error> + 'static,
+    {
+        let native = NativeFunction::new(name, arity, function);
+        self.set_global(name, Object::Function(Rc::new(native)));
+    }
 
-        let mut resolver = Resolver::new(self);
-        let resolver_errors = statements
-            .iter()
-            .filter_map(|statement| resolver.resolve_statement(statement).err())
-            .collect::<Vec<_>>();
+    /// Frees the objects that are only reachable from each other, like a closure stored in the
+    /// scope it captures. This also happens automatically as objects are created.
+    pub fn collect_garbage(&mut self) -> GcStats {
+        heap::collect()
+    }
 
-        if !resolver_errors.is_empty() {
-            return Err(resolver_errors);
-        }
+    pub fn gc_stats(&self) -> GcStats {
+        heap::stats()
+    }
 
-        let errors = statements
-            .into_iter()
-            .filter_map(|statement| {
-                self.execute(statement)
-                    .map_err(Error::EvaluationError)
-                    .err()
-            })
-            .collect::<Vec<_>>();
-
-        if !errors.is_empty() {
-            return Err(errors);
-        }
+    /// Sends the output of `print` statements to `output` instead of stdout.
+    pub fn set_output<W: Write + 'static>(&mut self, output: W) {
+        self.output = Rc::new(RefCell::new(output));
+    }
 
-        Ok(())
+    /// Sends the output of `print` statements to memory, and returns a handle to read it.
+    pub fn capture_output(&mut self) -> CapturedOutput {
+        let captured = CapturedOutput::default();
+        self.set_output(captured.clone());
+        captured
     }
 
-    pub fn execute(&mut self, statement: Statement) -> Result<(), object::Error> {
+    pub(crate) fn execute(&mut self, statement: Statement) -> Result<ControlFlow, object::Error> {
         match statement {
             Statement::If {
                 condition,
@@ -142,21 +171,29 @@ impl Interpreter {
                 else_statement,
             } => {
                 if self.evaluate(condition)?.is_truthy() {
-                    self.execute(*then_statement)?;
+                    return self.execute(*then_statement);
                 } else if let Some(statement) = else_statement {
-                    self.execute(*statement)?;
+                    return self.execute(*statement);
                 }
             }
             Statement::Print(expression) => {
-                println!("{}", self.evaluate(expression)?);
+                let value = self.evaluate(expression)?;
+                writeln!(self.output.borrow_mut(), "{value}").map_err(|error| {
+                    object::Error::new(
+                        object::ErrorKind::FailedToWriteOutput {
+                            message: error.to_string(),
+                        },
+                        Span::default(),
+                    )
+                })?;
             }
-            Statement::Return(expression) => {
+            Statement::Return { expression, .. } => {
                 let value = if let Some(expression) = expression {
                     self.evaluate(expression)?
                 } else {
                     Object::Nil
                 };
-                return Err(object::Error::Return(value));
+                return Ok(ControlFlow::Return(value));
             }
             Statement::Expression(expression) => {
                 self.evaluate(expression)?;
@@ -164,6 +201,7 @@ impl Interpreter {
             Statement::VariableDeclaration {
                 identifier,
                 expression,
+                ..
             } => {
                 let value = if let Some(expression) = expression {
                     self.evaluate(expression)?
@@ -174,29 +212,93 @@ impl Interpreter {
             }
             Statement::FunctionDeclaration(function_declaration) => {
                 let identifier = function_declaration.identifier.clone();
-                let function = Object::Function(Rc::new(Function::new(
+                let function = Object::function(Function::new(
                     function_declaration,
                     self.environment.clone(),
-                )));
+                    self.locals.clone(),
+                    false,
+                ));
                 self.environment.define(identifier, function)
             }
+            Statement::ClassDeclaration(ClassDeclaration {
+                identifier,
+                superclass,
+                methods,
+                span,
+            }) => {
+                let superclass = match superclass {
+                    Some(superclass) => match self.evaluate(superclass)? {
+                        Object::Class(class) => Some(class),
+                        actual => {
+                            return Err(object::Error::new(
+                                object::ErrorKind::SuperclassMustBeAClass { actual },
+                                span,
+                            ))
+                        }
+                    },
+                    None => None,
+                };
+                let enclosing = self.environment.clone();
+                if let Some(superclass) = &superclass {
+                    self.environment = self.environment.new_child();
+                    self.environment
+                        .define("super".to_string(), Object::Class(superclass.clone()));
+                }
+                let methods = methods
+                    .into_iter()
+                    .map(|method| {
+                        let name = method.identifier.clone();
+                        let is_initializer = name == INITIALIZER_NAME;
+                        let function = Function::new(
+                            method,
+                            self.environment.clone(),
+                            self.locals.clone(),
+                            is_initializer,
+                        );
+                        (name, function)
+                    })
+                    .collect();
+                let class = Class::new(identifier.clone(), superclass, methods);
+                self.environment = enclosing;
+                self.environment.define(identifier, Object::Class(class));
+            }
             Statement::While {
                 expression,
                 statement,
+                increment,
             } => {
                 while self.evaluate(expression.clone())?.is_truthy() {
-                    self.execute(*statement.clone())?;
+                    match self.execute(*statement.clone())? {
+                        ControlFlow::Normal | ControlFlow::Continue => {}
+                        ControlFlow::Break => break,
+                        flow @ ControlFlow::Return(_) => return Ok(flow),
+                    }
+                    if let Some(increment) = &increment {
+                        self.evaluate(increment.clone())?;
+                    }
                 }
             }
+            Statement::Break { .. } => return Ok(ControlFlow::Break),
+            Statement::Continue { .. } => return Ok(ControlFlow::Continue),
             Statement::Block(statements) => {
                 self.environment = self.environment.new_child();
-                for statement in statements {
-                    self.execute(statement)?;
-                }
+                let flow = self.execute_all(statements);
+                // Also after an error, so the REPL doesn't keep running in the block's scope.
                 self.environment = self.environment.end().unwrap();
+                return flow;
             }
         };
-        Ok(())
+        Ok(ControlFlow::Normal)
+    }
+
+    fn execute_all(&mut self, statements: Vec<Statement>) -> Result<ControlFlow, object::Error> {
+        for statement in statements {
+            match self.execute(statement)? {
+                ControlFlow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(ControlFlow::Normal)
     }
 
     fn evaluate(&mut self, expression: Expression) -> Result<Object, object::Error> {
@@ -214,92 +316,352 @@ impl Interpreter {
             Expression::Unary {
                 operator,
                 expression,
+                span,
             } => {
                 let expresssion_value = self.evaluate(*expression)?;
                 match operator {
                     UnaryOperator::Negation => Ok(Object::Boolean(!expresssion_value.is_truthy())),
                     UnaryOperator::Minus => expresssion_value.unary_minus(),
                 }
+                .map_err(|kind| object::Error::new(kind, span))
             }
             Expression::Binary {
                 left,
                 operator,
                 right,
+                span,
             } => {
                 let left_value = self.evaluate(*left)?;
+                match operator {
+                    BinaryOperator::Or if left_value.is_truthy() => return Ok(left_value),
+                    BinaryOperator::And if !left_value.is_truthy() => return Ok(left_value),
+                    _ => {}
+                }
                 let right_value = self.evaluate(*right)?;
                 match operator {
                     BinaryOperator::Equality => Ok(Object::Boolean(left_value == right_value)),
                     BinaryOperator::Different => Ok(Object::Boolean(left_value != right_value)),
-                    BinaryOperator::Less => Ok(Object::Boolean(left_value < right_value)),
-                    BinaryOperator::EqualOrLess => Ok(Object::Boolean(left_value <= right_value)),
-                    BinaryOperator::Greater => Ok(Object::Boolean(left_value > right_value)),
+                    BinaryOperator::Less => {
+                        left_value.compare(&right_value).map(is(Ordering::is_lt))
+                    }
+                    BinaryOperator::EqualOrLess => {
+                        left_value.compare(&right_value).map(is(Ordering::is_le))
+                    }
+                    BinaryOperator::Greater => {
+                        left_value.compare(&right_value).map(is(Ordering::is_gt))
+                    }
                     BinaryOperator::EqualOrGreater => {
-                        Ok(Object::Boolean(left_value >= right_value))
+                        left_value.compare(&right_value).map(is(Ordering::is_ge))
                     }
                     BinaryOperator::Addition => left_value + right_value,
                     BinaryOperator::Subtraction => left_value - right_value,
                     BinaryOperator::Multiplication => left_value * right_value,
                     BinaryOperator::Division => left_value / right_value,
-                    BinaryOperator::Or => Ok(if left_value.is_truthy() {
-                        left_value
-                    } else {
-                        right_value
-                    }),
-                    BinaryOperator::And => Ok(if left_value.is_truthy() {
-                        right_value
-                    } else {
-                        left_value
-                    }),
+                    BinaryOperator::Or | BinaryOperator::And => Ok(right_value),
                 }
+                .map_err(|kind| object::Error::new(kind, span))
             }
-            Expression::Variable(string) => self.look_up_variable(string),
-            Expression::Assignment { identifier, value } => {
-                let object = self.evaluate(*value.clone())?;
-                let depth = self.locals.get(&Expression::Assignment {
-                    identifier: identifier.clone(),
-                    value,
-                });
-                match depth {
-                    Some(depth) => self.environment.assign_at(*depth, identifier, object),
-                    None => self.globals.assign(identifier, object),
+            Expression::Variable {
+                id,
+                identifier,
+                span,
+            } => self
+                .look_up_variable(&identifier, id)
+                .map_err(|kind| object::Error::new(kind, span)),
+            Expression::Assignment {
+                id,
+                identifier,
+                value,
+                span,
+            } => {
+                let object = self.evaluate(*value)?;
+                match self.locals.get(&id) {
+                    Some(slot) => self
+                        .environment
+                        .assign_at(*slot, object.clone())
+                        .ok_or(object::ErrorKind::UndefinedVariable { name: identifier }),
+                    None => self.globals.assign(identifier, object.clone()),
                 }
+                .map(|_| object)
+                .map_err(|kind| object::Error::new(kind, span))
             }
             Expression::Grouping(expression) => self.evaluate(*expression),
             Expression::FunctionCall {
                 function,
                 arguments,
+                span,
             } => {
-                let function_object = self.evaluate(*function)?;
-                let Object::Function(function) = function_object else {
-                    return Err(object::Error::AttemptedToCallUncallableExpression{ called: function_object });
-                };
-                if arguments.len() != function.arity() {
-                    return Err(object::Error::WrongNumberOfArguments {
-                        expected: function.arity(),
-                        actual: arguments.len(),
-                    });
-                }
+                let function = self.evaluate(*function)?;
                 let arguments = arguments
                     .into_iter()
                     .map(|arg| self.evaluate(arg))
                     .collect::<Result<Vec<Object>, object::Error>>()?;
-                function.call(&self, arguments)
+                self.call(function, arguments, span)
+            }
+            Expression::Get { object, name, span } => {
+                let instance = match self.evaluate(*object)? {
+                    Object::Instance(instance) => instance,
+                    actual => {
+                        return Err(object::Error::new(
+                            object::ErrorKind::OnlyInstancesHaveProperties { actual },
+                            span,
+                        ))
+                    }
+                };
+                instance
+                    .get(&name)
+                    .map_err(|kind| object::Error::new(kind, span))
+            }
+            Expression::Set {
+                object,
+                name,
+                value,
+                span,
+            } => {
+                let instance = match self.evaluate(*object)? {
+                    Object::Instance(instance) => instance,
+                    actual => {
+                        return Err(object::Error::new(
+                            object::ErrorKind::OnlyInstancesHaveProperties { actual },
+                            span,
+                        ))
+                    }
+                };
+                let value = self.evaluate(*value)?;
+                instance.set(name, value.clone());
+                Ok(value)
+            }
+            Expression::List { elements, .. } => {
+                let elements = elements
+                    .into_iter()
+                    .map(|element| self.evaluate(element))
+                    .collect::<Result<_, _>>()?;
+                Ok(Object::List(List::new(elements)))
+            }
+            Expression::Map { entries, span } => {
+                let map = Map::default();
+                for (key, value) in entries {
+                    let key = self.evaluate(key)?;
+                    let value = self.evaluate(value)?;
+                    map.set(&key, value)
+                        .map_err(|kind| object::Error::new(kind, span))?;
+                }
+                Ok(Object::Map(map))
+            }
+            Expression::Index {
+                object,
+                index,
+                span,
+            } => {
+                let object = self.evaluate(*object)?;
+                let index = self.evaluate(*index)?;
+                object
+                    .get_index(&index)
+                    .map_err(|kind| object::Error::new(kind, span))
+            }
+            Expression::SetIndex {
+                object,
+                index,
+                value,
+                span,
+            } => {
+                let object = self.evaluate(*object)?;
+                let index = self.evaluate(*index)?;
+                let value = self.evaluate(*value)?;
+                object
+                    .set_index(&index, value.clone())
+                    .map_err(|kind| object::Error::new(kind, span))?;
+                Ok(value)
+            }
+            Expression::This { id, span } => self
+                .look_up_variable("this", id)
+                .map_err(|kind| object::Error::new(kind, span)),
+            Expression::Super { id, method, span } => {
+                let Some(slot) = self.locals.get(&id).copied() else {
+                    return Err(object::Error::new(
+                        object::ErrorKind::UndefinedVariable {
+                            name: "super".to_string(),
+                        },
+                        span,
+                    ));
+                };
+                // `this` is the only variable of the scope right inside the one holding `super`.
+                let this = Slot {
+                    depth: slot.depth - 1,
+                    index: 0,
+                };
+                let (Some(Object::Class(superclass)), Some(Object::Instance(instance))) =
+                    (self.environment.get_at(slot), self.environment.get_at(this))
+                else {
+                    unreachable!("the resolver only resolves super inside subclasses")
+                };
+                let Some(function) = superclass.find_method(&method) else {
+                    return Err(object::Error::new(
+                        object::ErrorKind::UndefinedProperty { name: method },
+                        span,
+                    ));
+                };
+                Ok(Object::function(function.bind(instance)))
             }
         }
     }
 
-    pub fn resolve(&mut self, expression: Expression, depth: usize) {
-        self.locals.insert(expression, depth);
+    fn look_up_variable(
+        &self,
+        identifier: &str,
+        id: ExpressionId,
+    ) -> Result<Object, object::ErrorKind> {
+        let undefined = || object::ErrorKind::UndefinedVariable {
+            name: identifier.to_string(),
+        };
+        match self.locals.get(&id) {
+            Some(slot) => self.environment.get_at(*slot).ok_or_else(undefined),
+            None => self.globals.get(&identifier.to_string()),
+        }
+    }
+
+    fn call(
+        &mut self,
+        function: Object,
+        arguments: Vec<Object>,
+        span: Span,
+    ) -> Result<Object, object::Error> {
+        let function: Rc<dyn Callable> = match function {
+            Object::Function(function) => function,
+            Object::Class(class) => Rc::new(class),
+            function_object => {
+                return Err(object::Error::new(
+                    object::ErrorKind::AttemptedToCallUncallableExpression {
+                        called: function_object,
+                    },
+                    span,
+                ))
+            }
+        };
+        function
+            .arity()
+            .check(arguments.len())
+            .map_err(|kind| object::Error::new(kind, span))?;
+        function.call(self, arguments, span)
+    }
+}
+
+impl Runtime for Interpreter {
+    fn call(&mut self, function: &Object, arguments: Vec<Object>) -> Result<Object, object::Error> {
+        Interpreter::call(self, function.clone(), arguments, Span::default())
+    }
+}
+
+impl Globals for Interpreter {
+    fn set_global(&mut self, name: &str, value: Object) {
+        Interpreter::set_global(self, name, value);
+    }
+
+    fn define_native<F>(&mut self, name: &str, arity: impl Into<Arity>, function: F)
+    where
+        F: Fn(&mut dyn Runtime, Vec<Object>) -> Result<Object, object::Error> + 'static,
+    {
+        Interpreter::define_native(self, name, arity, function);
+    }
+}
+
+fn show_variables(environment: &Environment) -> Vec<(String, String)> {
+    environment
+        .variables()
+        .into_iter()
+        .map(|(name, value)| (name, value.repr()))
+        .collect()
+}
+
+/// Turns the result of [`Object::compare`] into the value of a comparison operator.
+fn is(operator: fn(Ordering) -> bool) -> impl Fn(Option<Ordering>) -> Object {
+    move |ordering| Object::Boolean(ordering.is_some_and(operator))
+}
+
+impl Backend for Interpreter {
+    fn run_for_value(&mut self, analysis: Analysis) -> Result<String, Vec<Error>> {
+        let Analysis {
+            statements, locals, ..
+        } = analysis;
+        // Functions keep the locals of the run that declared them, so each run only needs its own.
+        self.locals = Rc::new(locals);
+
+        // A `return` at the top level ends the script, like in the virtual machine.
+        let flow = self
+            .execute_all(statements)
+            .map_err(|error| vec![Error::EvaluationError(error)])?;
+
+        Ok(match flow {
+            ControlFlow::Return(value) => value.repr(),
+            _ => Object::Nil.repr(),
+        })
+    }
+
+    fn globals(&self) -> Vec<(String, String)> {
+        show_variables(&self.globals)
+    }
+
+    fn environment(&self) -> Vec<Vec<(String, String)>> {
+        let mut scopes = Vec::new();
+        let mut environment = Some(self.environment.clone());
+        while let Some(scope) = environment {
+            scopes.push(show_variables(&scope));
+            environment = scope.enclosing();
+        }
+        scopes
+    }
+
+    /// Native functions defined from Rust are forgotten too, but `print` keeps its output.
+    fn reset(&mut self) {
+        let output = self.output.clone();
+        *self = Interpreter::new();
+        self.output = output;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deep_recursion_overflows() {
+        let mut interpreter = Interpreter::new();
+        let errors = interpreter
+            .eval("fun f(n) { return f(n + 1); } f(0);")
+            .unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [Error::EvaluationError(object::Error {
+                kind: object::ErrorKind::StackOverflow,
+                ..
+            })]
+        ));
+        interpreter
+            .eval("fun g(n) { if (n == 0) return 0; return g(n - 1) + 1; } var depth = g(1022);")
+            .unwrap();
+        assert_eq!(
+            interpreter.get_global("depth"),
+            Some(Object::Number(1022.0.into()))
+        );
     }
 
-    fn look_up_variable(&self, variable_name: String) -> Result<Object, object::Error> {
-        let depth = self
-            .locals
-            .get(&Expression::Variable(variable_name.clone()));
-        match depth {
-            Some(depth) => self.environment.get_at(*depth, &variable_name),
-            None => self.globals.get(&variable_name),
+    #[test]
+    fn locals_are_only_kept_for_the_latest_run() {
+        let mut interpreter = Interpreter::new();
+        interpreter.capture_output();
+        interpreter
+            .eval("fun add(a, b) { var sum = a + b; return sum; }")
+            .unwrap();
+        for _ in 0..100 {
+            interpreter
+                .eval("{ var a = 1; var b = a + 1; print add(a, b); }")
+                .unwrap();
         }
+        assert!(interpreter.locals.len() < 10, "{:?}", interpreter.locals);
+        interpreter.eval("var result = add(1, 2);").unwrap();
+        assert_eq!(
+            interpreter.get_global("result"),
+            Some(Object::Number(3.0.into()))
+        );
     }
 }
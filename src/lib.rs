@@ -0,0 +1,53 @@
+//! A Lox interpreter based on the book [Crafting Interpreters](https://craftinginterpreters.com).
+//!
+//! The [`Interpreter`] runs Lox source code and can be extended from Rust with globals and
+//! native functions. The [`Vm`] is an alternative backend that compiles to bytecode.
+#![feature(iter_intersperse)]
+
+mod backend;
+pub mod check;
+mod chunk;
+mod class;
+mod compiler;
+pub mod diagnostics;
+mod environment;
+mod expression;
+pub mod formatter;
+mod heap;
+mod interpreter;
+mod list;
+mod map;
+mod map_stack;
+mod native;
+mod number;
+mod object;
+mod output;
+mod parser;
+mod repl;
+mod resolver;
+mod result;
+mod scanner;
+mod statement;
+mod stdlib;
+mod token;
+mod vm;
+
+pub use backend::{
+    analyze_syntax_tree, dump_syntax_tree, parse_syntax_tree, Analysis, Backend,
+    SYNTAX_TREE_VERSION,
+};
+pub use class::{Class, Instance};
+pub use expression::Expression;
+pub use heap::GcStats;
+pub use interpreter::Interpreter;
+pub use list::List;
+pub use map::Map;
+pub use native::{argument, call, Callback, FromObject, NativeFunction, Runtime};
+pub use number::Number;
+pub use object::{Arity, Callable, Error as RuntimeError, ErrorKind, Object};
+pub use output::CapturedOutput;
+pub use parser::Parsed;
+pub use result::{Error, Warning};
+pub use statement::Statement;
+pub use token::Span;
+pub use vm::Vm;
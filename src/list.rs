@@ -0,0 +1,277 @@
+use crate::heap::{address, track, Address, Trace};
+use crate::object::{ErrorKind, Object};
+use std::cell::RefCell;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+
+/// A Lox list. Copies share the same elements, so changes made through one are seen by all.
+#[derive(Clone)]
+pub struct List(Rc<RefCell<Vec<Object>>>);
+
+impl Default for List {
+    fn default() -> Self {
+        List::new(Vec::new())
+    }
+}
+
+impl List {
+    pub fn new(elements: Vec<Object>) -> List {
+        let list = Rc::new(RefCell::new(elements));
+        track(&list);
+        List(list)
+    }
+
+    pub fn address(&self) -> Address {
+        address(&self.0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    pub fn get(&self, index: &Object) -> Result<Object, ErrorKind> {
+        let index = self.index(index)?;
+        Ok(self.0.borrow()[index].clone())
+    }
+
+    pub fn set(&self, index: &Object, value: Object) -> Result<(), ErrorKind> {
+        let index = self.index(index)?;
+        self.0.borrow_mut()[index] = value;
+        Ok(())
+    }
+
+    pub fn push(&self, value: Object) {
+        self.0.borrow_mut().push(value);
+    }
+
+    pub fn pop(&self) -> Option<Object> {
+        self.0.borrow_mut().pop()
+    }
+
+    /// A copy of the elements, so Lox code called while going through them can change the list.
+    pub fn elements(&self) -> Vec<Object> {
+        self.0.borrow().clone()
+    }
+
+    pub fn replace(&self, elements: Vec<Object>) {
+        *self.0.borrow_mut() = elements;
+    }
+
+    fn index(&self, index: &Object) -> Result<usize, ErrorKind> {
+        checked_index(index, self.len())
+    }
+}
+
+/// Converts a Lox value to an index into a list of `length` elements.
+pub(crate) fn checked_index(index: &Object, length: usize) -> Result<usize, ErrorKind> {
+    let integer = match index {
+        Object::Number(number) if f64::from(*number).fract() == 0.0 => f64::from(*number),
+        _ => {
+            return Err(ErrorKind::InvalidIndex {
+                index: index.clone(),
+            })
+        }
+    };
+    if integer < 0.0 || integer >= length as f64 {
+        return Err(ErrorKind::IndexOutOfBounds {
+            index: integer as i64,
+            length,
+        });
+    }
+    Ok(integer as usize)
+}
+
+impl Trace for RefCell<Vec<Object>> {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        if let Ok(elements) = self.try_borrow() {
+            elements.iter().for_each(|element| element.trace(visit));
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut elements) = self.try_borrow_mut() {
+            elements.clear();
+        }
+    }
+}
+
+/// Lists are equal when they have equal elements.
+impl PartialEq for List {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+            || compare_collections(&*self.0, &*other.0, || {
+                *self.0.borrow() == *other.0.borrow()
+            })
+    }
+}
+
+thread_local! {
+    /// Lists being printed, so a list that contains itself is printed as `[...]` instead of
+    /// recursing forever.
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+
+    /// Pairs of collections being compared, so comparing collections that contain themselves
+    /// stops instead of recursing forever.
+    static COMPARING: RefCell<Vec<(*const (), *const ())>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Compares two collections with `compare`. If they are already being compared further up, they
+/// are assumed to be equal: any difference is found by the comparison in progress.
+pub(crate) fn compare_collections<T>(
+    collection: &T,
+    other: &T,
+    compare: impl FnOnce() -> bool,
+) -> bool {
+    let pair = (
+        collection as *const T as *const (),
+        other as *const T as *const (),
+    );
+    if COMPARING.with_borrow(|comparing| comparing.contains(&pair)) {
+        return true;
+    }
+    COMPARING.with_borrow_mut(|comparing| comparing.push(pair));
+    let equal = compare();
+    COMPARING.with_borrow_mut(|comparing| comparing.pop());
+    equal
+}
+
+/// Prints a collection with `print`, or `cycle` if the collection is already being printed.
+pub(crate) fn print_collection<T>(
+    collection: &T,
+    formatter: &mut Formatter<'_>,
+    cycle: &str,
+    print: impl FnOnce(&mut Formatter<'_>) -> std::fmt::Result,
+) -> std::fmt::Result {
+    let pointer = collection as *const T as *const ();
+    if PRINTING.with_borrow(|printing| printing.contains(&pointer)) {
+        return write!(formatter, "{cycle}");
+    }
+    PRINTING.with_borrow_mut(|printing| printing.push(pointer));
+    let result = print(formatter);
+    PRINTING.with_borrow_mut(|printing| printing.pop());
+    result
+}
+
+/// Prints `[a, b, c]`, printing each element with `element`.
+pub(crate) fn print_elements<T>(
+    elements: &RefCell<Vec<T>>,
+    formatter: &mut Formatter<'_>,
+    element: fn(&T, &mut Formatter<'_>) -> std::fmt::Result,
+) -> std::fmt::Result {
+    print_collection(elements, formatter, "[...]", |formatter| {
+        write!(formatter, "[")?;
+        for (index, value) in elements.borrow().iter().enumerate() {
+            if index > 0 {
+                write!(formatter, ", ")?;
+            }
+            element(value, formatter)?;
+        }
+        write!(formatter, "]")
+    })
+}
+
+impl Display for List {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        print_elements(&self.0, formatter, |value, formatter| {
+            write!(formatter, "{}", value.repr())
+        })
+    }
+}
+
+impl Debug for List {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "List(")?;
+        print_elements(&self.0, formatter, |value, formatter| {
+            write!(formatter, "{value:?}")
+        })?;
+        write!(formatter, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches;
+
+    fn number(value: f64) -> Object {
+        Object::Number(value.into())
+    }
+
+    #[test]
+    fn get_and_set() {
+        let list = List::new(vec![number(1.0), number(2.0)]);
+        assert_eq!(list.get(&number(1.0)).unwrap(), number(2.0));
+        list.set(&number(0.0), Object::Nil).unwrap();
+        assert_eq!(list.get(&number(0.0)).unwrap(), Object::Nil);
+    }
+
+    #[test]
+    fn index_out_of_bounds() {
+        let list = List::new(vec![number(1.0)]);
+        assert_matches!(
+            list.get(&number(1.0)).unwrap_err(),
+            ErrorKind::IndexOutOfBounds {
+                index: 1,
+                length: 1
+            }
+        );
+        assert_matches!(
+            list.set(&number(-1.0), Object::Nil).unwrap_err(),
+            ErrorKind::IndexOutOfBounds { index: -1, .. }
+        );
+    }
+
+    #[test]
+    fn invalid_index() {
+        let list = List::new(vec![number(1.0)]);
+        assert_matches!(
+            list.get(&number(0.5)).unwrap_err(),
+            ErrorKind::InvalidIndex { .. }
+        );
+        assert_matches!(
+            list.get(&Object::String("0".to_string())).unwrap_err(),
+            ErrorKind::InvalidIndex { .. }
+        );
+    }
+
+    #[test]
+    fn copies_share_elements() {
+        let list = List::default();
+        let copy = list.clone();
+        copy.push(number(1.0));
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.pop(), Some(number(1.0)));
+        assert!(copy.is_empty());
+    }
+
+    #[test]
+    fn equality_compares_elements() {
+        assert_eq!(List::new(vec![number(1.0)]), List::new(vec![number(1.0)]));
+        assert_ne!(List::new(vec![number(1.0)]), List::new(vec![number(2.0)]));
+        assert_ne!(List::new(vec![number(1.0)]), List::default());
+    }
+
+    #[test]
+    fn equality_of_lists_containing_themselves() {
+        let cyclic = || {
+            let list = List::new(vec![number(1.0)]);
+            list.push(Object::List(list.clone()));
+            list
+        };
+        assert_eq!(cyclic(), cyclic());
+        let different = List::new(vec![number(2.0)]);
+        different.push(Object::List(different.clone()));
+        assert_ne!(cyclic(), different);
+    }
+
+    #[test]
+    fn display_list_containing_itself() {
+        let list = List::new(vec![Object::Nil]);
+        list.push(Object::List(list.clone()));
+        assert_eq!(list.to_string(), "[nil, [...]]");
+    }
+}
@@ -1,45 +1,112 @@
-#![feature(assert_matches)]
-#![feature(iter_intersperse)]
 extern crate exitcode;
 
-use clap::Parser as ClapParser;
-
-mod environment;
-mod expression;
-mod interpreter;
-mod map_stack;
-mod number;
-mod object;
-mod parser;
-mod resolver;
-mod result;
-mod scanner;
-mod statement;
-mod token;
+use clap::{Parser as ClapParser, Subcommand, ValueEnum};
+use rust_lox::{check, diagnostics, dump_syntax_tree, formatter, Backend, Error, Interpreter, Vm};
 
 /// Lox interpreter written in Rust
 #[derive(ClapParser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Path of the file to be executed
     file_path: Option<String>,
+    /// How the program is executed
+    #[arg(long, value_enum, default_value_t = BackendKind::TreeWalk)]
+    backend: BackendKind,
+    /// Print the syntax tree of the file as JSON instead of running it
+    #[arg(long, requires = "file_path")]
+    dump_ast: bool,
+    /// Run a syntax tree printed by `--dump-ast` instead of source code
+    #[arg(long, requires = "file_path", conflicts_with = "dump_ast")]
+    from_ast: bool,
+    /// Only look for errors and warnings in the files, without running them
+    #[arg(long, num_args = 1.., value_name = "FILE", conflicts_with = "file_path")]
+    check: Vec<String>,
+    /// How `--check` reports what it finds [default: human]
+    #[arg(long, value_enum, requires = "check", conflicts_with = "file_path")]
+    format: Option<Format>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Rewrite files in the canonical layout, keeping their comments
+    Fmt {
+        /// Files to format
+        #[arg(required = true, value_name = "FILE")]
+        files: Vec<String>,
+        /// Don't change the files, only list those that aren't formatted and fail if there are any
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum BackendKind {
+    /// Walk the syntax tree directly
+    TreeWalk,
+    /// Compile to bytecode and run it on a stack-based virtual machine
+    Vm,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    /// The same messages as when running the files, followed by a summary
+    Human,
+    /// A JSON array with one object per error or warning
+    Json,
 }
 
 fn main() {
-    use interpreter::Interpreter;
     use std::process::exit;
 
     let args = Args::parse();
 
-    let interpreter = Interpreter::new();
+    if let Some(Command::Fmt { files, check }) = &args.command {
+        exit(formatter::format_files(files, *check));
+    }
+
+    if !args.check.is_empty() {
+        let checks = check::check_files(&args.check);
+        match args.format.unwrap_or(Format::Human) {
+            Format::Human => check::print_human(&checks),
+            Format::Json => println!("{}", check::to_json(&checks)),
+        }
+        exit(check::exit_code(&checks));
+    }
+
+    let mut backend: Box<dyn Backend> = match args.backend {
+        BackendKind::TreeWalk => Box::new(Interpreter::new()),
+        BackendKind::Vm => Box::new(Vm::new()),
+    };
 
     let program_result = match args.file_path {
-        None => interpreter.repl(),
-        Some(file) => interpreter.run_file(file),
+        None => backend.repl().map(|()| exitcode::OK),
+        Some(file) if args.dump_ast => dump_ast(file),
+        Some(file) if args.from_ast => backend.run_syntax_tree_file(file),
+        Some(file) => backend.run_file(file),
     };
 
-    if let Err(error) = program_result {
-        println!("{error:?}");
-        exit(error.exit_code());
+    match program_result {
+        Ok(exit_code) => exit(exit_code),
+        Err(error) => {
+            diagnostics::report_without_source(&error);
+            exit(error.exit_code());
+        }
+    }
+}
+
+/// Returns the exit code of the first syntax error, or success if there were none.
+fn dump_ast(path: String) -> Result<i32, Error> {
+    let source = std::fs::read_to_string(&path)?;
+    match dump_syntax_tree(&source) {
+        Ok(json) => {
+            println!("{json}");
+            Ok(exitcode::OK)
+        }
+        Err(errors) => {
+            diagnostics::report(&path, &source, &errors);
+            Ok(errors[0].exit_code())
+        }
     }
 }
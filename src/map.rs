@@ -0,0 +1,328 @@
+use crate::heap::{address, track, Address, Trace};
+use crate::list::{compare_collections, print_collection};
+use crate::number::Number;
+use crate::object::{ErrorKind, Object};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+
+/// A value that can be used as a map key. Only values compared by content can be keys, so
+/// numbers, strings, booleans and nil.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Number(Number),
+    String(String),
+    Boolean(bool),
+    Nil,
+}
+
+impl TryFrom<&Object> for Key {
+    type Error = ErrorKind;
+
+    fn try_from(object: &Object) -> Result<Self, Self::Error> {
+        match object {
+            Object::Number(number) => Ok(Key::Number(*number)),
+            Object::String(string) => Ok(Key::String(string.clone())),
+            Object::Boolean(boolean) => Ok(Key::Boolean(*boolean)),
+            Object::Nil => Ok(Key::Nil),
+            _ => Err(ErrorKind::UnhashableKey {
+                key: object.clone(),
+            }),
+        }
+    }
+}
+
+impl From<Key> for Object {
+    fn from(key: Key) -> Self {
+        match key {
+            Key::Number(number) => Object::Number(number),
+            Key::String(string) => Object::String(string),
+            Key::Boolean(boolean) => Object::Boolean(boolean),
+            Key::Nil => Object::Nil,
+        }
+    }
+}
+
+/// A hash map that remembers insertion order, so iterating over it is deterministic.
+#[derive(Clone, Debug)]
+pub struct OrderedMap<V> {
+    entries: Vec<(Key, V)>,
+    indices: HashMap<Key, usize>,
+}
+
+impl<V> Default for OrderedMap<V> {
+    fn default() -> Self {
+        OrderedMap {
+            entries: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+}
+
+impl<V> OrderedMap<V> {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&V> {
+        self.indices.get(key).map(|index| &self.entries[*index].1)
+    }
+
+    pub fn contains_key(&self, key: &Key) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    /// Sets the value of a key. A key that is already present keeps its position.
+    pub fn insert(&mut self, key: Key, value: V) {
+        match self.indices.get(&key) {
+            Some(index) => self.entries[*index].1 = value,
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &Key) -> Option<V> {
+        let index = self.indices.remove(key)?;
+        let (_, value) = self.entries.remove(index);
+        for later in self.indices.values_mut() {
+            if *later > index {
+                *later -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &V)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+/// Maps are equal when they have the same keys with equal values, in any order.
+impl<V: PartialEq> PartialEq for OrderedMap<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+/// Prints `{k: v}`, printing each value with `value`.
+pub(crate) fn print_entries<V>(
+    map: &RefCell<OrderedMap<V>>,
+    formatter: &mut Formatter<'_>,
+    value: fn(&V, &mut Formatter<'_>) -> std::fmt::Result,
+) -> std::fmt::Result {
+    print_collection(map, formatter, "{...}", |formatter| {
+        write!(formatter, "{{")?;
+        for (index, (key, entry)) in map.borrow().iter().enumerate() {
+            if index > 0 {
+                write!(formatter, ", ")?;
+            }
+            write!(formatter, "{}: ", Object::from(key.clone()).repr())?;
+            value(entry, formatter)?;
+        }
+        write!(formatter, "}}")
+    })
+}
+
+/// A Lox map. Like lists, copies share the same entries.
+#[derive(Clone)]
+pub struct Map(Rc<RefCell<OrderedMap<Object>>>);
+
+impl Default for Map {
+    fn default() -> Self {
+        Map::new(OrderedMap::default())
+    }
+}
+
+impl Map {
+    pub fn new(entries: OrderedMap<Object>) -> Map {
+        let map = Rc::new(RefCell::new(entries));
+        track(&map);
+        Map(map)
+    }
+
+    pub fn address(&self) -> Address {
+        address(&self.0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, key: &Object) -> Result<Object, ErrorKind> {
+        self.0
+            .borrow()
+            .get(&Key::try_from(key)?)
+            .cloned()
+            .ok_or(ErrorKind::KeyNotFound { key: key.clone() })
+    }
+
+    pub fn set(&self, key: &Object, value: Object) -> Result<(), ErrorKind> {
+        self.0.borrow_mut().insert(Key::try_from(key)?, value);
+        Ok(())
+    }
+
+    pub fn has(&self, key: &Object) -> Result<bool, ErrorKind> {
+        Ok(self.0.borrow().contains_key(&Key::try_from(key)?))
+    }
+
+    pub fn remove(&self, key: &Object) -> Result<Option<Object>, ErrorKind> {
+        Ok(self.0.borrow_mut().remove(&Key::try_from(key)?))
+    }
+
+    pub fn keys(&self) -> Vec<Object> {
+        self.0
+            .borrow()
+            .iter()
+            .map(|(key, _)| key.clone().into())
+            .collect()
+    }
+
+    pub fn values(&self) -> Vec<Object> {
+        self.0
+            .borrow()
+            .iter()
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+impl Trace for RefCell<OrderedMap<Object>> {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        if let Ok(map) = self.try_borrow() {
+            map.iter().for_each(|(_, value)| value.trace(visit));
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut map) = self.try_borrow_mut() {
+            *map = OrderedMap::default();
+        }
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+            || compare_collections(&*self.0, &*other.0, || {
+                *self.0.borrow() == *other.0.borrow()
+            })
+    }
+}
+
+impl Display for Map {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        print_entries(&self.0, formatter, |value, formatter| {
+            write!(formatter, "{}", value.repr())
+        })
+    }
+}
+
+impl Debug for Map {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "Map(")?;
+        print_entries(&self.0, formatter, |value, formatter| {
+            write!(formatter, "{value:?}")
+        })?;
+        write!(formatter, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::List;
+    use std::assert_matches;
+
+    fn string(value: &str) -> Object {
+        Object::String(value.to_string())
+    }
+
+    fn number(value: f64) -> Object {
+        Object::Number(value.into())
+    }
+
+    #[test]
+    fn set_and_get() {
+        let map = Map::default();
+        map.set(&string("a"), number(1.0)).unwrap();
+        map.set(&number(1.0), string("one")).unwrap();
+        assert_eq!(map.get(&string("a")).unwrap(), number(1.0));
+        assert_eq!(map.get(&number(1.0)).unwrap(), string("one"));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn missing_key() {
+        assert_matches!(
+            Map::default().get(&string("a")).unwrap_err(),
+            ErrorKind::KeyNotFound { .. }
+        );
+    }
+
+    #[test]
+    fn unhashable_key() {
+        let key = Object::List(List::default());
+        assert_matches!(
+            Map::default().set(&key, Object::Nil).unwrap_err(),
+            ErrorKind::UnhashableKey { .. }
+        );
+    }
+
+    #[test]
+    fn keeps_insertion_order() {
+        let map = Map::default();
+        for key in ["c", "a", "b"] {
+            map.set(&string(key), Object::Nil).unwrap();
+        }
+        map.set(&string("c"), number(1.0)).unwrap();
+        assert_eq!(map.keys(), vec![string("c"), string("a"), string("b")]);
+        assert_eq!(map.remove(&string("a")).unwrap(), Some(Object::Nil));
+        map.set(&string("a"), Object::Nil).unwrap();
+        assert_eq!(map.keys(), vec![string("c"), string("b"), string("a")]);
+        assert_eq!(map.values(), vec![number(1.0), Object::Nil, Object::Nil]);
+        assert_eq!(map.get(&string("b")).unwrap(), Object::Nil);
+    }
+
+    #[test]
+    fn equality_ignores_order() {
+        let first = Map::default();
+        first.set(&string("a"), number(1.0)).unwrap();
+        first.set(&string("b"), number(2.0)).unwrap();
+        let second = Map::default();
+        second.set(&string("b"), number(2.0)).unwrap();
+        second.set(&string("a"), number(1.0)).unwrap();
+        assert_eq!(first, second);
+        second.set(&string("a"), number(3.0)).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn equality_of_maps_containing_themselves() {
+        let cyclic = || {
+            let map = Map::default();
+            map.set(&Object::Nil, Object::Map(map.clone())).unwrap();
+            map
+        };
+        assert_eq!(cyclic(), cyclic());
+        let different = cyclic();
+        different.set(&string("a"), number(1.0)).unwrap();
+        assert_ne!(cyclic(), different);
+    }
+
+    #[test]
+    fn display_map_containing_itself() {
+        let map = Map::default();
+        map.set(&Object::Nil, Object::Map(map.clone())).unwrap();
+        assert_eq!(map.to_string(), "{nil: {...}}");
+    }
+}
@@ -24,16 +24,24 @@ impl<K: Eq + Hash, V> MapStack<K, V> {
         self.0.last()?.get(key)
     }
 
-    pub fn get_in_any(&self, key: &K) -> Option<(usize, &V)> {
+    pub fn get_in_top_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.0.last_mut()?.get_mut(key)
+    }
+
+    pub fn get_in_any_mut(&mut self, key: &K) -> Option<(usize, &mut V)> {
         self.0
-            .iter()
+            .iter_mut()
             .rev()
             .enumerate()
-            .find_map(|(depth, hash_map)| Some((depth, hash_map.get(key)?)))
+            .find_map(|(depth, hash_map)| Some((depth, hash_map.get_mut(key)?)))
     }
 
-    pub fn any_contains(&self, key: &K) -> Option<usize> {
-        let (depth, _) = self.get_in_any(key)?;
-        Some(depth)
+    /// Looks in every map but the top one.
+    pub fn get_in_enclosing(&self, key: &K) -> Option<&V> {
+        self.0
+            .iter()
+            .rev()
+            .skip(1)
+            .find_map(|hash_map| hash_map.get(key))
     }
 }
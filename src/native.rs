@@ -0,0 +1,398 @@
+use crate::heap::{Address, Trace};
+use crate::interpreter::Interpreter;
+use crate::list::List;
+use crate::map::Map;
+use crate::number::Number;
+use crate::object::{Arity, Callable, Error, ErrorKind, Object};
+use crate::token::Span;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type NativeClosure = dyn Fn(&mut dyn Runtime, Vec<Object>) -> Result<Object, Error>;
+
+/// The backend a native function runs in, either the [`Interpreter`] or the
+/// [`Vm`](crate::Vm). Natives use it to call back into Lox.
+pub trait Runtime {
+    /// Calls a Lox function or class, checking the number of arguments like a call in Lox code
+    /// would.
+    fn call(&mut self, function: &Object, arguments: Vec<Object>) -> Result<Object, Error>;
+}
+
+/// Where native functions and constants are defined: the globals of either backend.
+pub(crate) trait Globals {
+    fn set_global(&mut self, name: &str, value: Object);
+
+    fn define_native<F>(&mut self, name: &str, arity: impl Into<Arity>, function: F)
+    where
+        F: Fn(&mut dyn Runtime, Vec<Object>) -> Result<Object, Error> + 'static;
+}
+
+/// A function implemented in Rust and callable from Lox.
+pub struct NativeFunction {
+    name: String,
+    arity: Arity,
+    function: Rc<NativeClosure>,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: &str, arity: impl Into<Arity>, function: F) -> NativeFunction
+    where
+        F: Fn(&mut dyn Runtime, Vec<Object>) -> Result<Object, Error> + 'static,
+    {
+        NativeFunction {
+            name: name.to_string(),
+            arity: arity.into(),
+            function: Rc::new(function),
+        }
+    }
+
+    /// Runs the native. `span` is where it is called from, for the errors it raises itself.
+    pub(crate) fn run(
+        &self,
+        runtime: &mut dyn Runtime,
+        arguments: Vec<Object>,
+        span: Span,
+    ) -> Result<Object, Error> {
+        (self.function)(runtime, arguments).map_err(|error| {
+            // Errors raised by the native itself have no location; errors from Lox functions it
+            // called back into keep theirs.
+            if error.span == Span::default() {
+                Error::new(error.kind, span)
+            } else {
+                error
+            }
+        })
+    }
+}
+
+impl Debug for NativeFunction {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "NativeFunction({})", self.name)
+    }
+}
+
+/// Natives are opaque to the garbage collector. Anything their closure captures stays alive.
+impl Trace for NativeFunction {
+    fn trace(&self, _visit: &mut dyn FnMut(Address)) {}
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "<native fn>")
+    }
+}
+
+impl Callable for NativeFunction {
+    fn signature(&self) -> String {
+        self.name.clone()
+    }
+
+    fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+        span: Span,
+    ) -> Result<Object, Error> {
+        self.run(interpreter, arguments, span)
+    }
+}
+
+/// A Rust type that native functions can take as an argument.
+pub trait FromObject: Sized {
+    /// How a value of this type is described in errors, like "a number".
+    const EXPECTED: &'static str;
+
+    /// Converts the object, or gives it back if it has the wrong type.
+    fn from_object(object: Object) -> Result<Self, Object>;
+}
+
+impl FromObject for Object {
+    const EXPECTED: &'static str = "any value";
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        Ok(object)
+    }
+}
+
+impl FromObject for Number {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        match object {
+            Object::Number(number) => Ok(number),
+            _ => Err(object),
+        }
+    }
+}
+
+impl FromObject for f64 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        Number::from_object(object).map(f64::from)
+    }
+}
+
+impl FromObject for i64 {
+    const EXPECTED: &'static str = "an integer";
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        match object {
+            Object::Number(number) if f64::from(number).fract() == 0.0 => {
+                Ok(f64::from(number) as i64)
+            }
+            _ => Err(object),
+        }
+    }
+}
+
+impl FromObject for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        match object {
+            Object::String(string) => Ok(string),
+            _ => Err(object),
+        }
+    }
+}
+
+impl FromObject for bool {
+    const EXPECTED: &'static str = "a boolean";
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        match object {
+            Object::Boolean(boolean) => Ok(boolean),
+            _ => Err(object),
+        }
+    }
+}
+
+impl FromObject for Rc<dyn Callable> {
+    const EXPECTED: &'static str = "a function";
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        match object {
+            Object::Function(function) => Ok(function),
+            Object::Class(class) => Ok(Rc::new(class)),
+            _ => Err(object),
+        }
+    }
+}
+
+/// A function or class that a native calls back with [`call`]. Unlike `Rc<dyn Callable>`, it
+/// can also be a function of the [`Vm`](crate::Vm).
+#[derive(Clone, Debug)]
+pub struct Callback(Object);
+
+impl FromObject for Callback {
+    const EXPECTED: &'static str = "a function";
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        match &object {
+            Object::Function(_) | Object::Class(_) => Ok(Callback(object)),
+            Object::Vm(value) if value.is_callable() => Ok(Callback(object)),
+            _ => Err(object),
+        }
+    }
+}
+
+impl FromObject for List {
+    const EXPECTED: &'static str = "a list";
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        match object {
+            Object::List(list) => Ok(list),
+            _ => Err(object),
+        }
+    }
+}
+
+impl FromObject for Map {
+    const EXPECTED: &'static str = "a map";
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        match object {
+            Object::Map(map) => Ok(map),
+            _ => Err(object),
+        }
+    }
+}
+
+/// `nil` converts to `None`, anything else must convert to `T`.
+impl<T: FromObject> FromObject for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_object(object: Object) -> Result<Self, Object> {
+        match object {
+            Object::Nil => Ok(None),
+            object => T::from_object(object).map(Some),
+        }
+    }
+}
+
+/// Converts the argument at `index` to `T`. A missing argument counts as `nil`, so optional
+/// arguments of variadic natives can be read as `Option<T>`.
+pub fn argument<T: FromObject>(arguments: &[Object], index: usize) -> Result<T, ErrorKind> {
+    let object = arguments.get(index).cloned().unwrap_or(Object::Nil);
+    T::from_object(object).map_err(|actual| ErrorKind::WrongArgumentType {
+        index,
+        expected: T::EXPECTED,
+        actual,
+    })
+}
+
+/// Calls a Lox function from a native, checking the number of arguments like a call in Lox code
+/// would.
+pub fn call(
+    runtime: &mut dyn Runtime,
+    function: &Callback,
+    arguments: Vec<Object>,
+) -> Result<Object, Error> {
+    runtime.call(&function.0, arguments)
+}
+
+/// Defines the native functions every backend starts with.
+pub(crate) fn define_builtins(globals: &mut impl Globals) {
+    globals.define_native("clock", 0, |_, _| {
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Object::Number(elapsed.as_secs_f64().into()))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches;
+
+    #[test]
+    fn call_native_function() {
+        let double = NativeFunction::new("double", 1, |_, arguments| {
+            let number = arguments[0].to_number_value()?;
+            Ok(Object::Number(number + number))
+        });
+        assert_eq!(double.signature(), "double");
+        assert_eq!(double.arity(), Arity::Fixed(1));
+        let result = double.call(
+            &mut Interpreter::new(),
+            vec![Object::Number(2.0.into())],
+            Span::default(),
+        );
+        assert_eq!(result.unwrap(), Object::Number(4.0.into()));
+    }
+
+    #[test]
+    fn native_errors_point_at_the_call() {
+        let fail = NativeFunction::new("fail", 0, |_, _| Err(ErrorKind::DivisionByZero.into()));
+        let span = Span::new(3, 4, 10, 11);
+        let error = fail
+            .call(&mut Interpreter::new(), vec![], span)
+            .unwrap_err();
+        assert_matches!(error.kind, ErrorKind::DivisionByZero);
+        assert_eq!(error.span, span);
+    }
+
+    #[test]
+    fn errors_from_callbacks_keep_their_span() {
+        let call = NativeFunction::new("call", 1, |runtime, arguments| {
+            let function: Callback = argument(&arguments, 0)?;
+            super::call(runtime, &function, vec![])
+        });
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("fun f() { return 1 / 0; }").unwrap();
+        let f = interpreter.get_global("f").unwrap();
+        let error = call
+            .call(&mut interpreter, vec![f], Span::new(5, 1, 40, 41))
+            .unwrap_err();
+        assert_matches!(error.kind, ErrorKind::DivisionByZero);
+        assert_eq!(error.span.line, 1);
+    }
+
+    #[test]
+    fn variadic_native_function() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define_native("count", Arity::AtLeast(1), |_, arguments| {
+            Ok(Object::Number((arguments.len() as f64).into()))
+        });
+        interpreter
+            .eval("var a = count(1); var b = count(1, 2, 3);")
+            .unwrap();
+        assert_eq!(
+            interpreter.get_global("a"),
+            Some(Object::Number(1.0.into()))
+        );
+        assert_eq!(
+            interpreter.get_global("b"),
+            Some(Object::Number(3.0.into()))
+        );
+        let errors = interpreter.eval("count();").unwrap_err();
+        assert_matches!(
+            errors.as_slice(),
+            [crate::result::Error::EvaluationError(Error {
+                kind: ErrorKind::NotEnoughArguments {
+                    minimum: 1,
+                    actual: 0
+                },
+                ..
+            })]
+        );
+    }
+
+    #[test]
+    fn typed_arguments() {
+        let arguments = vec![
+            Object::Number(2.0.into()),
+            Object::String("a".to_string()),
+            Object::Boolean(true),
+            Object::Nil,
+        ];
+        assert_eq!(argument::<f64>(&arguments, 0).unwrap(), 2.0);
+        assert_eq!(argument::<i64>(&arguments, 0).unwrap(), 2);
+        assert_eq!(argument::<String>(&arguments, 1).unwrap(), "a");
+        assert!(argument::<bool>(&arguments, 2).unwrap());
+        assert_eq!(argument::<Option<f64>>(&arguments, 3).unwrap(), None);
+        assert_eq!(argument::<Option<f64>>(&arguments, 4).unwrap(), None);
+        assert_eq!(argument::<Object>(&arguments, 3).unwrap(), Object::Nil);
+    }
+
+    #[test]
+    fn typed_argument_errors() {
+        let arguments = vec![Object::Number(2.5.into()), Object::Nil];
+        let error = argument::<String>(&arguments, 0).unwrap_err();
+        assert_matches!(
+            &error,
+            ErrorKind::WrongArgumentType {
+                index: 0,
+                expected: "a string",
+                actual: Object::Number(_)
+            }
+        );
+        assert_eq!(error.to_string(), "Argument 1 must be a string, found 2.5.");
+        assert_matches!(
+            argument::<i64>(&arguments, 0).unwrap_err(),
+            ErrorKind::WrongArgumentType {
+                expected: "an integer",
+                ..
+            }
+        );
+        assert_matches!(
+            argument::<f64>(&arguments, 1).unwrap_err(),
+            ErrorKind::WrongArgumentType { index: 1, .. }
+        );
+    }
+
+    #[test]
+    fn clock_returns_seconds_since_the_epoch() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("var now = clock();").unwrap();
+        let now: f64 = argument(&[interpreter.get_global("now").unwrap()], 0).unwrap();
+        assert!(now > 1_600_000_000.0);
+    }
+}
@@ -1,7 +1,11 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
-use std::ops::{Add, Div, Mul, Neg};
+use std::ops::{Add, Div, Mul, Neg, Sub};
 
-#[derive(Clone, Copy, Debug, PartialOrd)]
+/// Serialized as a plain number.
+#[derive(Clone, Copy, Debug, PartialOrd, Serialize, Deserialize)]
+#[serde(from = "f64", into = "f64")]
 pub struct Number(f64);
 
 impl Number {
@@ -16,6 +20,12 @@ impl From<f64> for Number {
     }
 }
 
+impl From<Number> for f64 {
+    fn from(value: Number) -> Self {
+        value.0
+    }
+}
+
 impl Hash for Number {
     fn hash<H>(&self, state: &mut H)
     where
@@ -33,9 +43,9 @@ impl PartialEq for Number {
 
 impl Eq for Number {}
 
-impl ToString for Number {
-    fn to_string(&self) -> String {
-        self.0.to_string()
+impl Display for Number {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{}", self.0)
     }
 }
 
@@ -55,6 +65,14 @@ impl Add for Number {
     }
 }
 
+impl Sub for Number {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Number(self.0 - rhs.0)
+    }
+}
+
 impl Mul for Number {
     type Output = Self;
 
@@ -1,61 +1,149 @@
+use crate::class::{Class, Instance};
 use crate::environment::Environment;
-use crate::interpreter::Interpreter;
+use crate::heap::{address, track, Address, Trace};
+use crate::interpreter::{ControlFlow, Interpreter, STACK_GROWTH, STACK_RED_ZONE};
+use crate::list::List;
+use crate::map::Map;
 use crate::number::Number;
+use crate::resolver::Locals;
 use crate::statement::FunctionDeclaration;
+use crate::token::Span;
+use crate::vm::Value;
+use std::cmp::Ordering;
 use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
 
-pub trait Callable: Debug {
+/// How many arguments a function takes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arity {
+    Fixed(usize),
+    /// Variadic, with a minimum number of arguments.
+    AtLeast(usize),
+}
+
+impl Arity {
+    /// Checks that a call with `count` arguments is allowed.
+    pub fn check(self, count: usize) -> Result<(), ErrorKind> {
+        match self {
+            Arity::Fixed(expected) if count != expected => Err(ErrorKind::WrongNumberOfArguments {
+                expected,
+                actual: count,
+            }),
+            Arity::AtLeast(minimum) if count < minimum => Err(ErrorKind::NotEnoughArguments {
+                minimum,
+                actual: count,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<usize> for Arity {
+    fn from(count: usize) -> Self {
+        Arity::Fixed(count)
+    }
+}
+
+/// Something that can be called from Lox. Its `Display` is how `print` shows it.
+pub trait Callable: Debug + Display + Trace {
     fn signature(&self) -> String;
-    fn arity(&self) -> usize;
-    fn call(&self, interpreter: &Interpreter, arguments: Vec<Object>) -> Result<Object, Error>;
+    fn arity(&self) -> Arity;
+    /// Calls the function. `span` is where it is called from, for errors that don't come from
+    /// Lox code.
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+        span: Span,
+    ) -> Result<Object, Error>;
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Function {
-    declaration: FunctionDeclaration,
+    declaration: Rc<FunctionDeclaration>,
     closure: Environment,
+    /// Where the resolver put the variables of the code this function was declared in, which
+    /// includes its body.
+    locals: Rc<Locals>,
+    is_initializer: bool,
 }
 
 impl Function {
-    pub fn new(declaration: FunctionDeclaration, closure: Environment) -> Function {
+    pub fn new(
+        declaration: FunctionDeclaration,
+        closure: Environment,
+        locals: Rc<Locals>,
+        is_initializer: bool,
+    ) -> Function {
+        Function {
+            declaration: Rc::new(declaration),
+            closure,
+            locals,
+            is_initializer,
+        }
+    }
+
+    pub fn bind(&self, instance: Instance) -> Function {
+        let mut closure = self.closure.new_child();
+        closure.define("this".to_string(), Object::Instance(instance));
         Function {
-            declaration,
+            declaration: self.declaration.clone(),
             closure,
+            locals: self.locals.clone(),
+            is_initializer: self.is_initializer,
         }
     }
 }
 
+impl Trace for Function {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        visit(self.closure.address());
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "<fn {}>", self.declaration.identifier)
+    }
+}
+
 impl Callable for Function {
     fn signature(&self) -> String {
         self.declaration.identifier.clone() // TODO: add parameter information
     }
 
-    fn arity(&self) -> usize {
-        self.declaration.parameters.len()
+    fn arity(&self) -> Arity {
+        Arity::Fixed(self.declaration.parameters.len())
     }
 
-    fn call(&self, interpreter: &Interpreter, arguments: Vec<Object>) -> Result<Object, Error> {
-        let mut interpreter = interpreter.new_for_closure(self.closure.new_child());
-        for (parameter_name, parameter_value) in self
-            .declaration
-            .parameters
-            .iter()
-            .zip(arguments.into_iter())
-        {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+        span: Span,
+    ) -> Result<Object, Error> {
+        let mut interpreter = interpreter
+            .new_for_closure(self.closure.new_child(), self.locals.clone())
+            .map_err(|kind| Error::new(kind, span))?;
+        for (parameter, value) in self.declaration.parameters.iter().zip(arguments) {
             interpreter
                 .environment
-                .define(parameter_name.clone(), parameter_value);
+                .define(parameter.name.clone(), value);
         }
-        let execution_result = interpreter.execute(*self.declaration.body.clone());
-        //crazy stuff, I know
-        if let Err(error) = execution_result {
-            return match error {
-                Error::Return(object) => Ok(object),
-                _ => Err(error),
-            };
+        let body = *self.declaration.body.clone();
+        let result =
+            stacker::maybe_grow(STACK_RED_ZONE, STACK_GROWTH, || interpreter.execute(body));
+        let value = match result? {
+            ControlFlow::Return(value) => value,
+            _ => Object::Nil,
+        };
+        if self.is_initializer {
+            return self
+                .closure
+                .get(&"this".to_string())
+                .map_err(|kind| Error::new(kind, span));
         }
-        Ok(Object::Nil)
+        Ok(value)
     }
 }
 
@@ -65,7 +153,14 @@ pub enum Object {
     String(String),
     Boolean(bool),
     Function(Rc<dyn Callable>),
+    Class(Class),
+    Instance(Instance),
+    List(List),
+    Map(Map),
     Nil,
+    /// A function, class or instance of the [`Vm`](crate::Vm), stored in a list or map or passed
+    /// to a native function.
+    Vm(Box<Value>),
 }
 
 impl PartialEq for Object {
@@ -74,146 +169,423 @@ impl PartialEq for Object {
             (Object::Number(num), Object::Number(other_num)) => num == other_num,
             (Object::String(str), Object::String(other_str)) => str == other_str,
             (Object::Boolean(bool), Object::Boolean(other_bool)) => bool == other_bool,
+            (Object::Function(function), Object::Function(other_function)) => {
+                address(function) == address(other_function)
+            }
+            (Object::Class(class), Object::Class(other_class)) => class == other_class,
+            (Object::Instance(instance), Object::Instance(other_instance)) => {
+                instance == other_instance
+            }
+            (Object::List(list), Object::List(other_list)) => list == other_list,
+            (Object::Map(map), Object::Map(other_map)) => map == other_map,
             (Object::Nil, Object::Nil) => true,
-            _ => todo!(),
+            (Object::Vm(value), Object::Vm(other_value)) => value == other_value,
+            _ => false,
         }
     }
 }
 
 impl Display for Object {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
-        if let Object::Function(function) = self {
-            write!(formatter, "<fn {}>", function.signature())
-        } else {
-            write!(formatter, "{self:?}")
+        match self {
+            Object::Number(number) => write!(formatter, "{number}"),
+            Object::String(string) => write!(formatter, "{string}"),
+            Object::Boolean(boolean) => write!(formatter, "{boolean}"),
+            Object::Function(function) => write!(formatter, "{function}"),
+            Object::Class(class) => write!(formatter, "{class}"),
+            Object::Instance(instance) => write!(formatter, "{instance}"),
+            Object::List(list) => write!(formatter, "{list}"),
+            Object::Map(map) => write!(formatter, "{map}"),
+            Object::Nil => write!(formatter, "nil"),
+            Object::Vm(value) => write!(formatter, "{value}"),
         }
     }
 }
 
 impl Object {
-    pub fn unary_minus(self) -> Result<Object, Error> {
+    /// Wraps a function defined in Lox, registering it with the garbage collector since it holds
+    /// on to the environment it closes over.
+    pub fn function<F: Callable + 'static>(function: F) -> Object {
+        let function: Rc<dyn Callable> = Rc::new(function);
+        track(&function);
+        Object::Function(function)
+    }
+    /// The value as the REPL shows it. Like `print`, except that strings are quoted and escaped,
+    /// so `"1"` can be told apart from `1`. Elements of lists and maps are always shown this way.
+    pub fn repr(&self) -> String {
+        match self {
+            Object::String(string) => format!("{string:?}"),
+            object => object.to_string(),
+        }
+    }
+    /// Calls `visit` with the heap object this value refers to, if any.
+    pub fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        match self {
+            Object::Function(function) => visit(address(function)),
+            Object::Class(class) => visit(class.address()),
+            Object::Instance(instance) => visit(instance.address()),
+            Object::List(list) => visit(list.address()),
+            Object::Map(map) => visit(map.address()),
+            Object::Vm(value) => value.trace(visit),
+            Object::Number(_) | Object::String(_) | Object::Boolean(_) | Object::Nil => {}
+        }
+    }
+    pub fn unary_minus(self) -> Result<Object, ErrorKind> {
         let number = self.to_number_value()?;
-        Ok(Object::Number((-number).into()))
+        Ok(Object::Number(-number))
     }
     pub fn is_truthy(&self) -> bool {
         match self {
             Object::Number(_) => true,
             Object::String(_) => true,
             Object::Boolean(boolean) => *boolean,
-            Object::Function(_) => todo!(),
+            Object::Function(_) => true,
+            Object::Class(_) => true,
+            Object::Instance(_) => true,
+            Object::List(_) => true,
+            Object::Map(_) => true,
             Object::Nil => false,
+            Object::Vm(_) => true,
         }
     }
-    pub fn to_number_value(&self) -> Result<Number, Error> {
+    pub fn to_number_value(&self) -> Result<Number, ErrorKind> {
         match self {
             Object::Number(number) => Ok(*number),
-            _ => Err(Error::ExpectedNumber {
+            _ => Err(ErrorKind::ExpectedNumber {
                 actual: self.clone(),
             }),
         }
     }
-    pub fn string_value(self) -> Result<String, Error> {
+    /// The value of `self[index]`.
+    pub fn get_index(&self, index: &Object) -> Result<Object, ErrorKind> {
         match self {
-            Object::String(string) => Ok(string),
-            _ => Err(Error::ExpectedString { actual: self }),
+            Object::List(list) => list.get(index),
+            Object::Map(map) => map.get(index),
+            _ => Err(ErrorKind::NotIndexable {
+                actual: self.clone(),
+            }),
         }
     }
-}
-
-impl PartialOrd for Object {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    /// Does `self[index] = value`.
+    pub fn set_index(&self, index: &Object, value: Object) -> Result<(), ErrorKind> {
+        match self {
+            Object::List(list) => list.set(index, value),
+            Object::Map(map) => map.set(index, value),
+            _ => Err(ErrorKind::NotIndexable {
+                actual: self.clone(),
+            }),
+        }
+    }
+    /// Orders two numbers, for `<`, `<=`, `>` and `>=`. Any other values are an error. `None`
+    /// means one of the numbers is NaN, which makes every comparison false.
+    pub fn compare(&self, other: &Object) -> Result<Option<Ordering>, ErrorKind> {
+        match (self, other) {
+            (Object::Number(number), Object::Number(other_number)) => {
+                Ok(number.partial_cmp(other_number))
+            }
+            _ => Err(ErrorKind::CannotCompare {
+                left: self.clone(),
+                right: other.clone(),
+            }),
+        }
+    }
+    pub fn string_value(self) -> Result<String, ErrorKind> {
         match self {
-            Object::Number(number) => number.partial_cmp(&other.to_number_value().ok()?),
-            _ => None,
+            Object::String(string) => Ok(string),
+            _ => Err(ErrorKind::ExpectedString { actual: self }),
         }
     }
 }
 
 impl std::ops::Add for Object {
-    type Output = Result<Self, Error>;
+    type Output = Result<Self, ErrorKind>;
 
     fn add(self, rhs: Self) -> Self::Output {
         match self {
             Object::Number(number) => Ok(Object::Number(number + rhs.to_number_value()?)),
             Object::String(string) => Ok(Object::String(string + &rhs.string_value()?)),
-            _ => Err(Error::ExpectedNumberOrString { actual: self }),
+            _ => Err(ErrorKind::ExpectedNumberOrString { actual: self }),
         }
     }
 }
 
 impl std::ops::Sub for Object {
-    type Output = Result<Self, Error>;
+    type Output = Result<Self, ErrorKind>;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        self + rhs.unary_minus()?
+        match self {
+            Object::Number(number) => Ok(Object::Number(number - rhs.to_number_value()?)),
+            _ => Err(ErrorKind::ExpectedNumber { actual: self }),
+        }
     }
 }
 
 impl std::ops::Mul for Object {
-    type Output = Result<Self, Error>;
+    type Output = Result<Self, ErrorKind>;
 
     fn mul(self, rhs: Self) -> Self::Output {
         match self {
             Object::Number(number) => Ok(Object::Number(number * rhs.to_number_value()?)),
-            _ => Err(Error::ExpectedNumber { actual: self }),
+            _ => Err(ErrorKind::ExpectedNumber { actual: self }),
         }
     }
 }
 
 impl std::ops::Div for Object {
-    type Output = Result<Self, Error>;
+    type Output = Result<Self, ErrorKind>;
 
     fn div(self, rhs: Self) -> Self::Output {
         match self {
             Object::Number(number) => {
                 let divisor = rhs.to_number_value()?;
                 if divisor == 0.0.into() {
-                    return Err(Error::DivisionByZero);
+                    return Err(ErrorKind::DivisionByZero);
                 }
                 Ok(Object::Number(number / divisor))
             }
-            _ => Err(Error::ExpectedNumber { actual: self }),
+            _ => Err(ErrorKind::ExpectedNumber { actual: self }),
         }
     }
 }
 
 #[derive(Debug)]
-pub enum Error {
-    AttemptedToCallUncallableExpression { called: Object },
-    ExpectedNumber { actual: Object },
-    ExpectedString { actual: Object },
-    ExpectedNumberOrString { actual: Object },
-    UndefinedVariable,
-    DivisionByZero,
-    WrongNumberOfArguments { expected: usize, actual: usize },
-    Return(Object), //Not an error, just a weird way to return a value
+pub struct Error {
+    pub kind: ErrorKind,
+    pub span: Span,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, span: Span) -> Error {
+        Error { kind, span }
+    }
+}
+
+/// An error without a location yet. Native functions return these, and the call fills in its own
+/// span.
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error::new(kind, Span::default())
+    }
 }
 
 impl Display for Error {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{} at {}", self.kind, self.span)
+    }
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    AttemptedToCallUncallableExpression {
+        called: Object,
+    },
+    ExpectedNumber {
+        actual: Object,
+    },
+    ExpectedString {
+        actual: Object,
+    },
+    ExpectedNumberOrString {
+        actual: Object,
+    },
+    UndefinedVariable {
+        name: String,
+    },
+    UndefinedProperty {
+        name: String,
+    },
+    OnlyInstancesHaveProperties {
+        actual: Object,
+    },
+    SuperclassMustBeAClass {
+        actual: Object,
+    },
+    DivisionByZero,
+    WrongNumberOfArguments {
+        expected: usize,
+        actual: usize,
+    },
+    NotEnoughArguments {
+        minimum: usize,
+        actual: usize,
+    },
+    WrongArgumentType {
+        index: usize,
+        expected: &'static str,
+        actual: Object,
+    },
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+    },
+    ReversedRange {
+        start: i64,
+        end: i64,
+    },
+    InvalidCodePoint {
+        code: i64,
+    },
+    InvalidIndex {
+        index: Object,
+    },
+    NotIndexable {
+        actual: Object,
+    },
+    PopFromEmptyList,
+    UnhashableKey {
+        key: Object,
+    },
+    KeyNotFound {
+        key: Object,
+    },
+    CannotCompare {
+        left: Object,
+        right: Object,
+    },
+    StackOverflow,
+    FailedToWriteOutput {
+        message: String,
+    },
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
         match self {
-            Error::AttemptedToCallUncallableExpression { called } => {
+            ErrorKind::AttemptedToCallUncallableExpression { .. } => "E0400",
+            ErrorKind::ExpectedNumber { .. } => "E0401",
+            ErrorKind::ExpectedString { .. } => "E0402",
+            ErrorKind::ExpectedNumberOrString { .. } => "E0403",
+            ErrorKind::UndefinedVariable { .. } => "E0404",
+            ErrorKind::UndefinedProperty { .. } => "E0405",
+            ErrorKind::OnlyInstancesHaveProperties { .. } => "E0406",
+            ErrorKind::SuperclassMustBeAClass { .. } => "E0407",
+            ErrorKind::DivisionByZero => "E0408",
+            ErrorKind::WrongNumberOfArguments { .. } => "E0409",
+            ErrorKind::StackOverflow => "E0410",
+            ErrorKind::FailedToWriteOutput { .. } => "E0412",
+            ErrorKind::NotEnoughArguments { .. } => "E0413",
+            ErrorKind::WrongArgumentType { .. } => "E0414",
+            ErrorKind::IndexOutOfBounds { .. } => "E0415",
+            ErrorKind::InvalidCodePoint { .. } => "E0416",
+            ErrorKind::InvalidIndex { .. } => "E0417",
+            ErrorKind::NotIndexable { .. } => "E0418",
+            ErrorKind::PopFromEmptyList => "E0419",
+            ErrorKind::UnhashableKey { .. } => "E0420",
+            ErrorKind::KeyNotFound { .. } => "E0421",
+            ErrorKind::CannotCompare { .. } => "E0422",
+            ErrorKind::ReversedRange { .. } => "E0423",
+        }
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::AttemptedToCallUncallableExpression { called } => {
+                write!(
+                    formatter,
+                    "Attempted to call uncallable expression {}.",
+                    called.repr()
+                )
+            }
+            ErrorKind::ExpectedNumber { actual } => {
+                write!(formatter, "Expected number, found {}.", actual.repr())
+            }
+            ErrorKind::ExpectedString { actual } => {
+                write!(formatter, "Expected string, found {}.", actual.repr())
+            }
+            ErrorKind::ExpectedNumberOrString { actual } => {
+                write!(
+                    formatter,
+                    "Expected number or string, found {}.",
+                    actual.repr()
+                )
+            }
+            ErrorKind::UndefinedVariable { name } => {
+                write!(formatter, "Undefined variable '{name}'.")
+            }
+            ErrorKind::UndefinedProperty { name } => {
+                write!(formatter, "Undefined property '{name}'.")
+            }
+            ErrorKind::OnlyInstancesHaveProperties { actual } => {
+                write!(
+                    formatter,
+                    "Only instances have properties, found {}.",
+                    actual.repr()
+                )
+            }
+            ErrorKind::SuperclassMustBeAClass { actual } => {
+                write!(
+                    formatter,
+                    "Superclass must be a class, found {}.",
+                    actual.repr()
+                )
+            }
+            ErrorKind::DivisionByZero => write!(formatter, "Division by zero."),
+            ErrorKind::WrongNumberOfArguments { expected, actual } => {
+                write!(formatter, "Wrong number of arguments. Function expects {} arguments, but got called with {} arguments.", expected, actual)
+            }
+            ErrorKind::NotEnoughArguments { minimum, actual } => {
+                write!(formatter, "Not enough arguments. Function expects at least {minimum} arguments, but got called with {actual} arguments.")
+            }
+            ErrorKind::WrongArgumentType {
+                index,
+                expected,
+                actual,
+            } => {
+                write!(
+                    formatter,
+                    "Argument {} must be {expected}, found {}.",
+                    index + 1,
+                    actual.repr()
+                )
+            }
+            ErrorKind::IndexOutOfBounds { index, length } => {
                 write!(
                     formatter,
-                    "Attempted to call uncallable expresion {}.",
-                    called
+                    "Index {index} is out of bounds for length {length}."
                 )
             }
-            Error::ExpectedNumber { actual } => {
-                write!(formatter, "Expected number, found {actual}.")
+            ErrorKind::ReversedRange { start, end } => {
+                write!(
+                    formatter,
+                    "Range ends at {end}, before it starts at {start}."
+                )
             }
-            Error::ExpectedString { actual } => {
-                write!(formatter, "Expected string, found {actual}.")
+            ErrorKind::InvalidCodePoint { code } => {
+                write!(formatter, "{code} is not a valid Unicode code point.")
             }
-            Error::ExpectedNumberOrString { actual } => {
-                write!(formatter, "Expected number or string, found {actual}.")
+            ErrorKind::InvalidIndex { index } => {
+                write!(
+                    formatter,
+                    "Index must be an integer, found {}.",
+                    index.repr()
+                )
             }
-            Error::UndefinedVariable => write!(formatter, "UndefinedVariable."),
-            Error::DivisionByZero => write!(formatter, "Division by zero."),
-            Error::WrongNumberOfArguments { expected, actual } => {
-                write!(formatter, "Wrong number of arguments. Function expects {} arguments, but got called with {} arguments", expected, actual)
+            ErrorKind::NotIndexable { actual } => {
+                write!(
+                    formatter,
+                    "Only lists and maps can be indexed, found {}.",
+                    actual.repr()
+                )
+            }
+            ErrorKind::PopFromEmptyList => write!(formatter, "Can't pop from an empty list."),
+            ErrorKind::UnhashableKey { key } => write!(
+                formatter,
+                "Only numbers, strings, booleans and nil can be map keys, found {}.",
+                key.repr()
+            ),
+            ErrorKind::KeyNotFound { key } => write!(formatter, "Key {} not found.", key.repr()),
+            ErrorKind::CannotCompare { left, right } => write!(
+                formatter,
+                "Only numbers can be compared, found {} and {}.",
+                left.repr(),
+                right.repr()
+            ),
+            ErrorKind::StackOverflow => write!(formatter, "Stack overflow."),
+            ErrorKind::FailedToWriteOutput { message } => {
+                write!(formatter, "Failed to write output: {message}.")
             }
-            Error::Return(..) => panic!("This should never be called."),
         }
     }
 }
@@ -221,6 +593,10 @@ impl Display for Error {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::map::OrderedMap;
+    use crate::native::NativeFunction;
+    use std::assert_matches;
+    use std::collections::HashMap;
 
     #[test]
     fn unary_minus() {
@@ -254,12 +630,12 @@ mod tests {
 
     #[test]
     fn is_truthy_false() {
-        assert_eq!(Object::Boolean(false).is_truthy(), false);
+        assert!(!Object::Boolean(false).is_truthy());
     }
 
     #[test]
     fn is_truthy_nil() {
-        assert_eq!(Object::Nil.is_truthy(), false);
+        assert!(!Object::Nil.is_truthy());
     }
 
     #[test]
@@ -302,12 +678,140 @@ mod tests {
 
     #[test]
     fn greater() {
-        assert!(Object::Number(1.0.into()) > Object::Number(0.0.into()))
+        let ordering = Object::Number(1.0.into()).compare(&Object::Number(0.0.into()));
+        assert_eq!(ordering.unwrap(), Some(Ordering::Greater))
     }
 
     #[test]
     fn greater_nil() {
-        assert!(!(Object::Nil > Object::Nil))
+        assert_matches!(
+            Object::Nil.compare(&Object::Nil),
+            Err(ErrorKind::CannotCompare { .. })
+        )
+    }
+
+    #[test]
+    fn compare_nan() {
+        let nan = Object::Number(f64::NAN.into());
+        assert_eq!(nan.compare(&Object::Number(1.0.into())).unwrap(), None)
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Object::Number(3.0.into()).to_string(), "3");
+        assert_eq!(Object::Number((-0.5).into()).to_string(), "-0.5");
+        assert_eq!(Object::String("hi".to_string()).to_string(), "hi");
+        assert_eq!(Object::Boolean(true).to_string(), "true");
+        assert_eq!(Object::Nil.to_string(), "nil");
+        let native = NativeFunction::new("clock", 0, |_, _| Ok(Object::Nil));
+        assert_eq!(Object::Function(Rc::new(native)).to_string(), "<native fn>");
+        let class = Class::new("Point".to_string(), None, HashMap::new());
+        assert_eq!(Object::Class(class.clone()).to_string(), "Point");
+        assert_eq!(
+            Object::Instance(Instance::new(class)).to_string(),
+            "Point instance"
+        );
+    }
+
+    #[test]
+    fn repr_quotes_strings_only() {
+        assert_eq!(
+            Object::String("a \"b\"\n".to_string()).repr(),
+            r#""a \"b\"\n""#
+        );
+        assert_eq!(Object::Number(1.5.into()).repr(), "1.5");
+        assert_eq!(Object::Nil.repr(), "nil");
+    }
+
+    #[test]
+    fn collections_show_their_elements_with_repr() {
+        let list = List::new(vec![
+            Object::String("1".to_string()),
+            Object::Number(1.0.into()),
+        ]);
+        assert_eq!(Object::List(list.clone()).to_string(), r#"["1", 1]"#);
+        assert_eq!(Object::List(list).repr(), r#"["1", 1]"#);
+    }
+
+    #[test]
+    fn error_messages_show_values_with_repr() {
+        let error = ErrorKind::ExpectedNumber {
+            actual: Object::String("1".to_string()),
+        };
+        assert_eq!(error.to_string(), r#"Expected number, found "1"."#);
+    }
+
+    /// One or two values of every kind of object, so properties can be checked over all pairs.
+    fn samples() -> Vec<Object> {
+        let class = Class::new("A".to_string(), None, HashMap::new());
+        vec![
+            Object::Number(0.0.into()),
+            Object::Number(1.0.into()),
+            Object::String("".to_string()),
+            Object::String("a".to_string()),
+            Object::Boolean(true),
+            Object::Boolean(false),
+            Object::Function(Rc::new(NativeFunction::new("f", 0, |_, _| Ok(Object::Nil)))),
+            Object::Function(Rc::new(NativeFunction::new("f", 0, |_, _| Ok(Object::Nil)))),
+            Object::Class(class.clone()),
+            Object::Class(Class::new("A".to_string(), None, HashMap::new())),
+            Object::Instance(Instance::new(class.clone())),
+            Object::Instance(Instance::new(class)),
+            Object::List(List::new(vec![])),
+            Object::List(List::new(vec![Object::Nil])),
+            Object::Map(Map::new(OrderedMap::default())),
+            Object::Nil,
+        ]
+    }
+
+    #[test]
+    fn equality_is_reflexive() {
+        for object in samples() {
+            assert_eq!(object, object.clone(), "{object:?}");
+        }
+    }
+
+    #[test]
+    fn equality_is_symmetric_and_distinguishes_samples() {
+        let samples = samples();
+        for (i, left) in samples.iter().enumerate() {
+            for (j, right) in samples.iter().enumerate() {
+                assert_eq!(left == right, right == left, "{left:?} and {right:?}");
+                assert_eq!(left == right, i == j, "{left:?} and {right:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn functions_are_equal_by_identity() {
+        let function: Rc<dyn Callable> =
+            Rc::new(NativeFunction::new("f", 0, |_, _| Ok(Object::Nil)));
+        assert_eq!(
+            Object::Function(function.clone()),
+            Object::Function(function)
+        )
+    }
+
+    #[test]
+    fn only_false_and_nil_are_falsey() {
+        for object in samples() {
+            let falsey = matches!(object, Object::Boolean(false) | Object::Nil);
+            assert_eq!(object.is_truthy(), !falsey, "{object:?}");
+        }
+    }
+
+    #[test]
+    fn only_numbers_can_be_compared() {
+        for left in samples() {
+            for right in samples() {
+                let numbers = matches!((&left, &right), (Object::Number(_), Object::Number(_)));
+                assert_eq!(
+                    left.compare(&right).is_ok(),
+                    numbers,
+                    "{left:?} and {right:?}"
+                );
+            }
+        }
     }
 
     #[test]
@@ -0,0 +1,46 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+/// Where `print` statements write to, shared by an interpreter and the closures it creates.
+pub type Output = Rc<RefCell<dyn Write>>;
+
+/// An in-memory output whose contents can be read back after running a program.
+#[derive(Clone, Default)]
+pub struct CapturedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl CapturedOutput {
+    /// Everything printed so far, with invalid UTF-8 replaced.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+}
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buffer)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captured_output_is_shared_between_clones() {
+        let captured = CapturedOutput::default();
+        let mut writer = captured.clone();
+        writeln!(writer, "hello").unwrap();
+        assert_eq!(captured.contents(), "hello\n");
+        captured.clear();
+        assert_eq!(captured.contents(), "");
+    }
+}
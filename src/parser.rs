@@ -1,15 +1,33 @@
 use crate::expression::*;
 use crate::result::Error;
-use crate::result::Error::ExpectedEndOfExpression;
-use crate::statement::{FunctionDeclaration, Statement};
+use crate::statement::{ClassDeclaration, FunctionDeclaration, Parameter, Statement};
 use crate::token::*;
 
 const MAXIMUM_NUMBER_OR_PARAMETERS: usize = 255;
 
+/// Everything the parser managed to read. When there are errors, `statements` holds the
+/// declarations that parsed successfully around them.
+#[derive(Debug)]
+pub struct Parsed {
+    pub statements: Vec<Statement>,
+    pub errors: Vec<Error>,
+}
+
+impl Parsed {
+    pub fn into_result(self) -> Result<Vec<Statement>, Vec<Error>> {
+        if self.errors.is_empty() {
+            Ok(self.statements)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
     errors: Vec<Error>,
+    block_depth: usize,
 }
 
 impl Parser {
@@ -18,78 +36,180 @@ impl Parser {
             tokens,
             current: 0,
             errors: vec![],
+            block_depth: 0,
         }
     }
 
-    pub fn parse(mut self) -> Result<Vec<Statement>, Error> {
+    pub fn parse(mut self) -> Parsed {
         let mut statements = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+        Parsed {
+            statements,
+            errors: self.errors,
+        }
+    }
+
+    /// Parses a declaration. On a syntax error, the error is recorded and the parser skips to
+    /// the start of the next statement.
+    fn declaration(&mut self) -> Option<Statement> {
+        let start = self.current;
+        match self.declaration_or_error() {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize(start);
+                None
+            }
         }
-        Ok(statements)
     }
 
-    fn declaration(&mut self) -> Result<Statement, Error> {
-        let result = if self.match_token(TokenType::Fun) {
+    fn declaration_or_error(&mut self) -> Result<Statement, Error> {
+        if self.match_token(TokenType::Class) {
+            self.class_declaration()
+        } else if self.match_token(TokenType::Fun) {
             self.function_declaration()
         } else if self.match_token(TokenType::Var) {
             self.variable_declaration()
         } else {
             self.statement()
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Statement, Error> {
+        let span = self.previous_span();
+        if !self.match_identifier() {
+            return Err(Error::ExpectedIdentifier {
+                span: self.peek().span,
+            });
+        }
+        let TokenType::Identifier(identifier) = self.previous() else {
+            unreachable!()
+        };
+        let identifier = identifier.clone();
+
+        let superclass = if self.match_token(TokenType::Less) {
+            if !self.match_identifier() {
+                return Err(Error::ExpectedIdentifier {
+                    span: self.peek().span,
+                });
+            }
+            let TokenType::Identifier(superclass) = self.previous() else {
+                unreachable!()
+            };
+            Some(Expression::Variable {
+                id: new_id(),
+                identifier: superclass.clone(),
+                span: self.previous_span(),
+            })
+        } else {
+            None
         };
-        if result.is_err() {
-            self.synchronize();
+
+        if !self.match_token(TokenType::LeftBrace) {
+            return Err(Error::ExpectedLeftBrace {
+                span: self.peek().span,
+            });
+        }
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function()?);
         }
-        result
+        if !self.match_token(TokenType::RightBrace) {
+            return Err(Error::ExpectedRightBrace {
+                span: self.peek().span,
+            });
+        }
+
+        Ok(Statement::ClassDeclaration(ClassDeclaration {
+            identifier,
+            superclass,
+            methods,
+            span,
+        }))
     }
 
     fn function_declaration(&mut self) -> Result<Statement, Error> {
+        Ok(Statement::FunctionDeclaration(self.function()?))
+    }
+
+    fn function(&mut self) -> Result<FunctionDeclaration, Error> {
         if !self.match_identifier() {
-            return Err(Error::ExpectedIdentifier);
+            return Err(Error::ExpectedIdentifier {
+                span: self.peek().span,
+            });
         }
-        let TokenType::Identifier(identifier) = self.previous() else { unreachable!() };
+        let TokenType::Identifier(identifier) = self.previous() else {
+            unreachable!()
+        };
         let identifier = identifier.clone();
+        let span = self.previous_span();
         if !self.match_token(TokenType::LeftParen) {
-            return Err(Error::ExpectedLeftParen);
+            return Err(Error::ExpectedLeftParen {
+                span: self.peek().span,
+            });
         }
 
         let mut parameters = Vec::new();
         if !self.match_token(TokenType::RightParen) {
             loop {
-                if parameters.len() >= MAXIMUM_NUMBER_OR_PARAMETERS {
-                    return Err(Error::TooManyArguments(parameters.len()));
+                if parameters.len() == MAXIMUM_NUMBER_OR_PARAMETERS {
+                    self.errors.push(Error::TooManyArguments {
+                        count: parameters.len(),
+                        span: self.peek().span,
+                    });
                 }
                 if !self.match_identifier() {
-                    return Err(Error::ExpectedIdentifier);
+                    return Err(Error::ExpectedIdentifier {
+                        span: self.peek().span,
+                    });
                 }
-                let TokenType::Identifier(parameter) = self.previous() else { unreachable!() };
-                parameters.push(parameter.clone());
+                let TokenType::Identifier(parameter) = self.previous() else {
+                    unreachable!()
+                };
+                parameters.push(Parameter {
+                    name: parameter.clone(),
+                    span: self.previous_span(),
+                });
                 if !self.match_token(TokenType::Comma) {
                     break;
                 }
             }
             if !self.match_token(TokenType::RightParen) {
-                return Err(Error::ExpectedRightParen);
+                return Err(Error::ExpectedRightParen {
+                    span: self.peek().span,
+                });
             }
         }
 
         if !self.match_token(TokenType::LeftBrace) {
-            return Err(Error::ExpectedLeftBrace);
+            return Err(Error::ExpectedLeftBrace {
+                span: self.peek().span,
+            });
         }
         let body = Box::new(self.block()?);
-        Ok(Statement::FunctionDeclaration(FunctionDeclaration {
+        Ok(FunctionDeclaration {
             identifier,
             parameters,
             body,
-        }))
+            span,
+        })
     }
 
     fn variable_declaration(&mut self) -> Result<Statement, Error> {
         if !self.match_identifier() {
-            return Err(Error::ExpectedIdentifier);
+            return Err(Error::ExpectedIdentifier {
+                span: self.peek().span,
+            });
         }
-        let TokenType::Identifier(identifier) = self.previous() else { unreachable!() };
+        let TokenType::Identifier(identifier) = self.previous() else {
+            unreachable!()
+        };
         let identifier = identifier.clone();
+        let span = self.previous_span();
         let initializer = if self.match_token(TokenType::Equal) {
             Some(self.expression()?)
         } else {
@@ -97,11 +217,14 @@ impl Parser {
         };
 
         if !self.match_token(TokenType::Semicolon) {
-            return Err(Error::ExpectedEndOfExpression);
+            return Err(Error::ExpectedEndOfExpression {
+                span: self.peek().span,
+            });
         }
         Ok(Statement::VariableDeclaration {
             identifier,
             expression: initializer,
+            span,
         })
     }
 
@@ -114,6 +237,10 @@ impl Parser {
             self.print_statement()
         } else if self.match_token(TokenType::Return) {
             self.return_statement()
+        } else if self.match_token(TokenType::Break) {
+            self.break_statement()
+        } else if self.match_token(TokenType::Continue) {
+            self.continue_statement()
         } else if self.match_token(TokenType::While) {
             self.while_statement()
         } else if self.match_token(TokenType::LeftBrace) {
@@ -125,7 +252,9 @@ impl Parser {
 
     fn for_statement(&mut self) -> Result<Statement, Error> {
         if !self.match_token(TokenType::LeftParen) {
-            return Err(Error::ExpectedLeftParen);
+            return Err(Error::ExpectedLeftParen {
+                span: self.peek().span,
+            });
         }
 
         let initializer = if self.match_token(TokenType::Semicolon) {
@@ -143,7 +272,9 @@ impl Parser {
         };
 
         if !self.match_token(TokenType::Semicolon) {
-            return Err(Error::ExpectedEndOfExpression);
+            return Err(Error::ExpectedEndOfExpression {
+                span: self.peek().span,
+            });
         }
 
         let increment = if self.check(TokenType::RightParen) {
@@ -153,20 +284,17 @@ impl Parser {
         };
 
         if !self.match_token(TokenType::RightParen) {
-            return Err(Error::ExpectedRightParen);
+            return Err(Error::ExpectedRightParen {
+                span: self.peek().span,
+            });
         }
 
         let body = self.statement()?;
 
-        let while_body = if let Some(expression) = increment {
-            Statement::Block(vec![body, Statement::Expression(expression)])
-        } else {
-            body
-        };
-
         let while_loop = Statement::While {
             expression: condition,
-            statement: Box::new(while_body),
+            statement: Box::new(body),
+            increment,
         };
 
         let for_loop = if let Some(statement) = initializer {
@@ -180,11 +308,15 @@ impl Parser {
 
     fn if_statement(&mut self) -> Result<Statement, Error> {
         if !self.match_token(TokenType::LeftParen) {
-            return Err(Error::ExpectedLeftParen);
+            return Err(Error::ExpectedLeftParen {
+                span: self.peek().span,
+            });
         }
         let condition = self.expression()?;
         if !self.match_token(TokenType::RightParen) {
-            return Err(Error::ExpectedRightParen);
+            return Err(Error::ExpectedRightParen {
+                span: self.peek().span,
+            });
         }
         let then_statement = self.statement()?;
         let then_statement = Box::new(then_statement);
@@ -202,58 +334,95 @@ impl Parser {
 
     fn block(&mut self) -> Result<Statement, Error> {
         let mut statements = Vec::new();
+        self.block_depth += 1;
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
-            statements.push(self.declaration()?);
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
+        self.block_depth -= 1;
         if self.match_token(TokenType::RightBrace) {
-            self.advance();
             Ok(Statement::Block(statements))
         } else {
-            Err(Error::ExpectedRightBrace)
+            Err(Error::ExpectedRightBrace {
+                span: self.peek().span,
+            })
         }
     }
     fn print_statement(&mut self) -> Result<Statement, Error> {
-        let value = self.expression();
+        let value = self.expression()?;
         if !self.match_token(TokenType::Semicolon) {
-            Err(Error::ExpectedEndOfExpression)
+            Err(Error::ExpectedEndOfExpression {
+                span: self.peek().span,
+            })
         } else {
-            Ok(Statement::Print(value?))
+            Ok(Statement::Print(value))
         }
     }
 
     fn return_statement(&mut self) -> Result<Statement, Error> {
+        let span = self.previous_span();
         let expression = if self.check(TokenType::Semicolon) {
             None
         } else {
             Some(self.expression()?)
         };
         if !self.match_token(TokenType::Semicolon) {
-            return Err(ExpectedEndOfExpression);
+            return Err(Error::ExpectedEndOfExpression {
+                span: self.peek().span,
+            });
         }
-        Ok(Statement::Return(expression))
+        Ok(Statement::Return { expression, span })
+    }
+
+    fn break_statement(&mut self) -> Result<Statement, Error> {
+        let span = self.previous_span();
+        if !self.match_token(TokenType::Semicolon) {
+            return Err(Error::ExpectedEndOfExpression {
+                span: self.peek().span,
+            });
+        }
+        Ok(Statement::Break { span })
+    }
+
+    fn continue_statement(&mut self) -> Result<Statement, Error> {
+        let span = self.previous_span();
+        if !self.match_token(TokenType::Semicolon) {
+            return Err(Error::ExpectedEndOfExpression {
+                span: self.peek().span,
+            });
+        }
+        Ok(Statement::Continue { span })
     }
 
     fn while_statement(&mut self) -> Result<Statement, Error> {
         if !self.match_token(TokenType::LeftParen) {
-            return Err(Error::ExpectedLeftParen);
+            return Err(Error::ExpectedLeftParen {
+                span: self.peek().span,
+            });
         }
         let expression = self.expression()?;
         if !self.match_token(TokenType::RightParen) {
-            return Err(Error::ExpectedRightParen);
+            return Err(Error::ExpectedRightParen {
+                span: self.peek().span,
+            });
         }
         let statement = Box::new(self.statement()?);
         Ok(Statement::While {
             expression,
             statement,
+            increment: None,
         })
     }
 
     fn expression_statement(&mut self) -> Result<Statement, Error> {
-        let value = self.expression();
+        let value = self.expression()?;
         if self.match_token(TokenType::Semicolon) {
-            Ok(Statement::Expression(value?))
+            Ok(Statement::Expression(value))
         } else {
-            Err(Error::ExpectedEndOfExpression)
+            Err(Error::ExpectedEndOfExpression {
+                span: self.peek().span,
+            })
         }
     }
 
@@ -265,14 +434,34 @@ impl Parser {
         let expr = self.or()?;
 
         if self.match_token(TokenType::Equal) {
+            let equals = self.previous_span();
             let value = self.assignment()?;
-            return if let Expression::Variable(identifier) = expr {
-                Ok(Expression::Assignment {
+            return match expr {
+                Expression::Variable {
+                    identifier, span, ..
+                } => Ok(Expression::Assignment {
+                    id: new_id(),
                     identifier,
                     value: Box::new(value),
-                })
-            } else {
-                Err(Error::InvalidAssignmentTarget)
+                    span,
+                }),
+                Expression::Get { object, name, span } => Ok(Expression::Set {
+                    object,
+                    name,
+                    value: Box::new(value),
+                    span,
+                }),
+                Expression::Index {
+                    object,
+                    index,
+                    span,
+                } => Ok(Expression::SetIndex {
+                    object,
+                    index,
+                    value: Box::new(value),
+                    span,
+                }),
+                _ => Err(Error::InvalidAssignmentTarget { span: equals }),
             };
         }
         Ok(expr)
@@ -281,11 +470,13 @@ impl Parser {
     fn or(&mut self) -> Result<Expression, Error> {
         let mut expr = self.and()?;
         while self.match_token(TokenType::Or) {
+            let span = self.previous_span();
             let right = self.and()?;
             expr = Expression::Binary {
                 left: Box::new(expr),
                 operator: BinaryOperator::Or,
                 right: Box::new(right),
+                span,
             };
         }
         Ok(expr)
@@ -294,11 +485,13 @@ impl Parser {
     fn and(&mut self) -> Result<Expression, Error> {
         let mut expr = self.equality()?;
         while self.match_token(TokenType::And) {
+            let span = self.previous_span();
             let right = self.equality()?;
             expr = Expression::Binary {
                 left: Box::new(expr),
                 operator: BinaryOperator::And,
                 right: Box::new(right),
+                span,
             };
         }
         Ok(expr)
@@ -307,6 +500,7 @@ impl Parser {
     fn equality(&mut self) -> Result<Expression, Error> {
         let mut expr = self.comparison()?;
         while self.match_token(TokenType::BangEqual) || self.match_token(TokenType::EqualEqual) {
+            let span = self.previous_span();
             let operator_token_type = self.previous();
             let operator = match operator_token_type {
                 TokenType::BangEqual => BinaryOperator::Different,
@@ -319,6 +513,7 @@ impl Parser {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
+                span,
             };
         }
         Ok(expr)
@@ -331,6 +526,7 @@ impl Parser {
             || self.match_token(TokenType::Less)
             || self.match_token(TokenType::LessEqual)
         {
+            let span = self.previous_span();
             let operator_token_type = self.previous();
             let operator = match operator_token_type {
                 TokenType::Greater => BinaryOperator::Greater,
@@ -345,6 +541,7 @@ impl Parser {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
+                span,
             };
         }
         Ok(expr)
@@ -353,6 +550,7 @@ impl Parser {
     fn term(&mut self) -> Result<Expression, Error> {
         let mut expr = self.factor()?;
         while self.match_token(TokenType::Minus) || self.match_token(TokenType::Plus) {
+            let span = self.previous_span();
             let operator = match self.previous() {
                 TokenType::Minus => BinaryOperator::Subtraction,
                 TokenType::Plus => BinaryOperator::Addition,
@@ -363,6 +561,7 @@ impl Parser {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
+                span,
             };
         }
         Ok(expr)
@@ -371,6 +570,7 @@ impl Parser {
     fn factor(&mut self) -> Result<Expression, Error> {
         let mut expr = self.unary()?;
         while self.match_token(TokenType::Slash) || self.match_token(TokenType::Star) {
+            let span = self.previous_span();
             let operator = match self.previous() {
                 TokenType::Slash => BinaryOperator::Division,
                 TokenType::Star => BinaryOperator::Multiplication,
@@ -381,6 +581,7 @@ impl Parser {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
+                span,
             };
         }
         Ok(expr)
@@ -388,6 +589,7 @@ impl Parser {
 
     fn unary(&mut self) -> Result<Expression, Error> {
         if self.match_token(TokenType::Bang) || self.match_token(TokenType::Minus) {
+            let span = self.previous_span();
             let operator = match self.previous() {
                 TokenType::Bang => UnaryOperator::Negation,
                 TokenType::Minus => UnaryOperator::Minus,
@@ -397,6 +599,7 @@ impl Parser {
             Ok(Expression::Unary {
                 operator,
                 expression: Box::new(expression),
+                span,
             })
         } else {
             self.call()
@@ -409,6 +612,33 @@ impl Parser {
         loop {
             if self.match_token(TokenType::LeftParen) {
                 expression = self.finish_call(expression)?;
+            } else if self.match_token(TokenType::Dot) {
+                if !self.match_identifier() {
+                    return Err(Error::ExpectedIdentifier {
+                        span: self.peek().span,
+                    });
+                }
+                let TokenType::Identifier(name) = self.previous() else {
+                    unreachable!()
+                };
+                expression = Expression::Get {
+                    object: Box::new(expression),
+                    name: name.clone(),
+                    span: self.previous_span(),
+                };
+            } else if self.match_token(TokenType::LeftBracket) {
+                let span = self.previous_span();
+                let index = self.expression()?;
+                if !self.match_token(TokenType::RightBracket) {
+                    return Err(Error::ExpectedRightBracket {
+                        span: self.peek().span,
+                    });
+                }
+                expression = Expression::Index {
+                    object: Box::new(expression),
+                    index: Box::new(index),
+                    span,
+                };
             } else {
                 break;
             }
@@ -416,13 +646,62 @@ impl Parser {
         Ok(expression)
     }
 
+    fn list(&mut self) -> Result<Expression, Error> {
+        let span = self.previous_span();
+        let mut elements = Vec::new();
+        if !self.check(TokenType::RightBracket) {
+            loop {
+                elements.push(self.expression()?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        if !self.match_token(TokenType::RightBracket) {
+            return Err(Error::ExpectedRightBracket {
+                span: self.peek().span,
+            });
+        }
+        Ok(Expression::List { elements, span })
+    }
+
+    /// A map literal. Only expressions can start with a '{' here, since statements that do are
+    /// parsed as blocks before getting to expressions.
+    fn map(&mut self) -> Result<Expression, Error> {
+        let span = self.previous_span();
+        let mut entries = Vec::new();
+        if !self.check(TokenType::RightBrace) {
+            loop {
+                let key = self.expression()?;
+                if !self.match_token(TokenType::Colon) {
+                    return Err(Error::ExpectedColon {
+                        span: self.peek().span,
+                    });
+                }
+                entries.push((key, self.expression()?));
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        if !self.match_token(TokenType::RightBrace) {
+            return Err(Error::ExpectedRightBrace {
+                span: self.peek().span,
+            });
+        }
+        Ok(Expression::Map { entries, span })
+    }
+
     fn finish_call(&mut self, function: Expression) -> Result<Expression, Error> {
         let mut arguments = Vec::<Expression>::new();
 
         if !self.check(TokenType::RightParen) {
             loop {
-                if arguments.len() >= MAXIMUM_NUMBER_OR_PARAMETERS {
-                    self.errors.push(Error::TooManyArguments(arguments.len()));
+                if arguments.len() == MAXIMUM_NUMBER_OR_PARAMETERS {
+                    self.errors.push(Error::TooManyArguments {
+                        count: arguments.len(),
+                        span: self.peek().span,
+                    });
                 }
                 arguments.push(self.expression()?);
                 if !self.match_token(TokenType::Comma) {
@@ -435,9 +714,12 @@ impl Parser {
             Ok(Expression::FunctionCall {
                 function: Box::new(function),
                 arguments,
+                span: self.previous_span(),
             })
         } else {
-            Err(Error::ExpectedRightParen)
+            Err(Error::ExpectedRightParen {
+                span: self.peek().span,
+            })
         }
     }
 
@@ -449,26 +731,65 @@ impl Parser {
         } else if self.match_token(TokenType::Nil) {
             Ok(Expression::Literal(Literal::Nil))
         } else if self.match_number() {
-            let TokenType::Number { value, .. } = self.previous() else { unreachable!() };
+            let TokenType::Number { value, .. } = self.previous() else {
+                unreachable!()
+            };
             Ok(Expression::Literal(Literal::Number((*value).into())))
         } else if self.match_string() {
-            let TokenType::String(string) = self.previous() else { unreachable!() };
+            let TokenType::String(string) = self.previous() else {
+                unreachable!()
+            };
             Ok(Expression::Literal(Literal::String(string.clone())))
         } else if self.match_identifier() {
-            let TokenType::Identifier(string) = self.previous() else { unreachable!() };
-            Ok(Expression::Variable(string.clone()))
+            let TokenType::Identifier(identifier) = self.previous() else {
+                unreachable!()
+            };
+            Ok(Expression::Variable {
+                id: new_id(),
+                identifier: identifier.clone(),
+                span: self.previous_span(),
+            })
+        } else if self.match_token(TokenType::This) {
+            Ok(Expression::This {
+                id: new_id(),
+                span: self.previous_span(),
+            })
+        } else if self.match_token(TokenType::Super) {
+            let span = self.previous_span();
+            if !self.match_token(TokenType::Dot) {
+                return Err(Error::ExpectedDot {
+                    span: self.peek().span,
+                });
+            }
+            if !self.match_identifier() {
+                return Err(Error::ExpectedIdentifier {
+                    span: self.peek().span,
+                });
+            }
+            let TokenType::Identifier(method) = self.previous() else {
+                unreachable!()
+            };
+            Ok(Expression::Super {
+                id: new_id(),
+                method: method.clone(),
+                span,
+            })
+        } else if self.match_token(TokenType::LeftBracket) {
+            self.list()
+        } else if self.match_token(TokenType::LeftBrace) {
+            self.map()
         } else if self.match_token(TokenType::LeftParen) {
             let expression = self.expression();
             if self.match_token(TokenType::RightParen) {
                 expression
             } else {
                 Err(Error::UnmatchedParenthesis {
-                    position: self.peek().start,
+                    span: self.peek().span,
                 })
             }
         } else {
             Err(Error::ExpectedExpression {
-                position: self.peek().start,
+                span: self.peek().span,
             })
         }
     }
@@ -496,7 +817,7 @@ impl Parser {
         if self.is_at_end() {
             return false;
         }
-        let TokenType::Number{..} = self.peek().token_type else {
+        let TokenType::Number { .. } = self.peek().token_type else {
             return false;
         };
         self.advance();
@@ -540,12 +861,24 @@ impl Parser {
         &self.tokens[self.current - 1].token_type
     }
 
-    fn synchronize(&mut self) {
-        self.advance();
+    fn previous_span(&self) -> Span {
+        self.tokens[self.current - 1].span
+    }
+
+    /// Skips tokens until the start of the next statement, so that parsing can resume after an
+    /// error in the declaration that started at `start`. A closing brace is left in place for the
+    /// enclosing block, if there is one.
+    fn synchronize(&mut self, start: usize) {
+        if self.current == start {
+            self.advance();
+        }
         while !self.is_at_end() {
             if self.previous() == &TokenType::Semicolon {
                 return;
             }
+            if self.block_depth > 0 && self.check(TokenType::RightBrace) {
+                return;
+            }
             if matches!(
                 self.peek().token_type,
                 TokenType::Class
@@ -556,11 +889,13 @@ impl Parser {
                     | TokenType::While
                     | TokenType::Print
                     | TokenType::Return
+                    | TokenType::Break
+                    | TokenType::Continue
             ) {
                 return;
             }
+            self.advance();
         }
-        self.advance();
     }
 }
 
@@ -573,22 +908,22 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         assert!(result.is_ok());
         assert_eq!(
             result.unwrap(),
@@ -603,37 +938,38 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Plus,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Binary {
             left: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
             operator: BinaryOperator::Addition,
             right: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -644,37 +980,38 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Minus,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Binary {
             left: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
             operator: BinaryOperator::Subtraction,
             right: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -685,37 +1022,38 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Star,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Binary {
             left: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
             operator: BinaryOperator::Multiplication,
             right: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -726,37 +1064,38 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Slash,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Binary {
             left: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
             operator: BinaryOperator::Division,
             right: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -766,37 +1105,38 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EqualEqual,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Binary {
             left: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
             operator: BinaryOperator::Equality,
             right: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -806,37 +1146,38 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::BangEqual,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Binary {
             left: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
             operator: BinaryOperator::Different,
             right: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -846,37 +1187,38 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::LessEqual,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Binary {
             left: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
             operator: BinaryOperator::EqualOrLess,
             right: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -886,37 +1228,38 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Less,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Binary {
             left: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
             operator: BinaryOperator::Less,
             right: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -926,37 +1269,38 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Greater,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Binary {
             left: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
             operator: BinaryOperator::Greater,
             right: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -966,37 +1310,38 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::GreaterEqual,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Binary {
             left: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
             operator: BinaryOperator::EqualOrGreater,
             right: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -1006,26 +1351,27 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Bang,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::True,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Unary {
             operator: UnaryOperator::Negation,
             expression: Box::new(Expression::Literal(Literal::True)),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -1035,29 +1381,30 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Minus,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Number {
-                    value: 123 as f64,
+                    value: 123.0,
                     length: 5,
                 },
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Unary {
             operator: UnaryOperator::Minus,
             expression: Box::new(Expression::Literal(Literal::Number(123.0.into()))),
+            span: Span::default(),
         })];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -1068,19 +1415,19 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::False,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Literal(Literal::False))];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -1091,19 +1438,19 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Nil,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Literal(Literal::Nil))];
         assert!(result.is_ok());
         assert_eq!(expr, result.unwrap());
@@ -1114,19 +1461,19 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::String("baseado".to_string()),
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Literal(Literal::String(
             "baseado".to_string(),
         )))];
@@ -1139,27 +1486,27 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::LeftParen,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::String("baseado".to_string()),
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::RightParen,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Semicolon,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         let expr = vec![Statement::Expression(Expression::Literal(Literal::String(
             "baseado".to_string(),
         )))];
@@ -1172,19 +1519,19 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::LeftParen,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::String("baseado".to_string()),
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         assert!(result.is_err());
     }
 
@@ -1193,23 +1540,285 @@ mod tests {
         let tokens = vec![
             Token {
                 token_type: TokenType::Var,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Identifier("i".to_string()),
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::Equal,
-                start: 0,
+                span: Span::default(),
             },
             Token {
                 token_type: TokenType::EOF,
-                start: 0,
+                span: Span::default(),
             },
         ];
         let parser = Parser::new(tokens);
-        let result = parser.parse();
+        let result = parser.parse().into_result();
         assert!(result.is_err());
     }
+
+    #[test]
+    fn statement_after_block() {
+        let tokens = vec![
+            Token {
+                token_type: TokenType::LeftBrace,
+                span: Span::default(),
+            },
+            Token {
+                token_type: TokenType::RightBrace,
+                span: Span::default(),
+            },
+            Token {
+                token_type: TokenType::Nil,
+                span: Span::default(),
+            },
+            Token {
+                token_type: TokenType::Semicolon,
+                span: Span::default(),
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span::default(),
+            },
+        ];
+        let parser = Parser::new(tokens);
+        let result = parser.parse().into_result();
+        let expr = vec![
+            Statement::Block(vec![]),
+            Statement::Expression(Expression::Literal(Literal::Nil)),
+        ];
+        assert!(result.is_ok());
+        assert_eq!(expr, result.unwrap());
+    }
+
+    fn parse_source(source: &str) -> Parsed {
+        let tokens = crate::scanner::Scanner::new(source, 1)
+            .scan_tokens()
+            .unwrap();
+        Parser::new(tokens).parse()
+    }
+
+    #[test]
+    fn class_declaration() {
+        let result =
+            parse_source("class B < A { init(x) { this.x = x; } get() { return super.get(); } }")
+                .into_result();
+        assert!(result.is_ok());
+        let statements = result.unwrap();
+        let [Statement::ClassDeclaration(class)] = statements.as_slice() else {
+            panic!();
+        };
+        assert_eq!(class.identifier, "B");
+        assert!(matches!(
+            &class.superclass,
+            Some(Expression::Variable { identifier, .. }) if identifier == "A"
+        ));
+        let method_names = class
+            .methods
+            .iter()
+            .map(|method| method.identifier.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(method_names, vec!["init", "get"]);
+    }
+
+    #[test]
+    fn property_assignment() {
+        let result = parse_source("a.b.c = 1;").into_result();
+        assert!(result.is_ok());
+        let statements = result.unwrap();
+        let [Statement::Expression(Expression::Set { object, name, .. })] = statements.as_slice()
+        else {
+            panic!();
+        };
+        assert_eq!(name, "c");
+        assert!(matches!(**object, Expression::Get { ref name, .. } if name == "b"));
+    }
+
+    #[test]
+    fn for_loop_keeps_its_increment_apart_from_the_body() {
+        let result = parse_source("for (var i = 0; i < 3; i = i + 1) continue;").into_result();
+        assert!(result.is_ok());
+        let statements = result.unwrap();
+        let [Statement::Block(block)] = statements.as_slice() else {
+            panic!();
+        };
+        let [Statement::VariableDeclaration { .. }, Statement::While {
+            statement,
+            increment,
+            ..
+        }] = block.as_slice()
+        else {
+            panic!();
+        };
+        assert!(matches!(**statement, Statement::Continue { .. }));
+        assert!(matches!(increment, Some(Expression::Assignment { .. })));
+    }
+
+    #[test]
+    fn break_requires_a_semicolon() {
+        let parsed = parse_source("while (true) break");
+        assert!(matches!(
+            parsed.errors.as_slice(),
+            [Error::ExpectedEndOfExpression { .. }]
+        ));
+    }
+
+    #[test]
+    fn super_without_method() {
+        let parsed = parse_source("super;");
+        assert!(matches!(
+            parsed.errors.as_slice(),
+            [Error::ExpectedDot { .. }]
+        ));
+    }
+
+    #[test]
+    fn reports_every_statement_error() {
+        let parsed = parse_source("var = 1;\nprint 1;\nprint (2;\nvar b = 3;");
+        assert!(matches!(
+            parsed.errors.as_slice(),
+            [
+                Error::ExpectedIdentifier { .. },
+                Error::UnmatchedParenthesis { .. }
+            ]
+        ));
+        assert_eq!(parsed.statements.len(), 2);
+        assert!(
+            matches!(parsed.statements[1], Statement::VariableDeclaration { ref identifier, .. } if identifier == "b")
+        );
+    }
+
+    #[test]
+    fn recovers_inside_blocks() {
+        let parsed = parse_source("{ print ; print 1; } print 2;");
+        assert!(matches!(
+            parsed.errors.as_slice(),
+            [Error::ExpectedExpression { .. }]
+        ));
+        let [Statement::Block(block), Statement::Print(_)] = parsed.statements.as_slice() else {
+            panic!("{:?}", parsed.statements);
+        };
+        assert_eq!(block.len(), 1);
+    }
+
+    #[test]
+    fn recovers_after_error_before_closing_brace() {
+        let parsed = parse_source("fun f() { print 1 } print 2;");
+        assert!(matches!(
+            parsed.errors.as_slice(),
+            [Error::ExpectedEndOfExpression { .. }]
+        ));
+        assert!(matches!(
+            parsed.statements.as_slice(),
+            [Statement::FunctionDeclaration(_), Statement::Print(_)]
+        ));
+    }
+
+    #[test]
+    fn too_many_arguments_is_reported_once() {
+        let arguments = vec!["1"; 300].join(", ");
+        let parsed = parse_source(&format!("f({arguments});"));
+        assert!(matches!(
+            parsed.errors.as_slice(),
+            [Error::TooManyArguments { count: 255, .. }]
+        ));
+        assert_eq!(parsed.statements.len(), 1);
+    }
+
+    #[test]
+    fn does_not_skip_the_statement_after_a_missing_semicolon() {
+        let parsed = parse_source("print 1\nprint 2;");
+        assert!(matches!(
+            parsed.errors.as_slice(),
+            [Error::ExpectedEndOfExpression { .. }]
+        ));
+        assert_eq!(parsed.statements.len(), 1);
+    }
+
+    #[test]
+    fn unterminated_statement_at_end_of_file() {
+        let parsed = parse_source("print 1");
+        assert_eq!(parsed.errors.len(), 1);
+        assert!(parsed.statements.is_empty());
+    }
+
+    #[test]
+    fn list_literal() {
+        let statements = parse_source("[1, [], x];").into_result().unwrap();
+        let [Statement::Expression(Expression::List { elements, .. })] = statements.as_slice()
+        else {
+            panic!("{statements:?}");
+        };
+        assert!(matches!(
+            elements.as_slice(),
+            [
+                Expression::Literal(Literal::Number(_)),
+                Expression::List { elements, .. },
+                Expression::Variable { .. }
+            ] if elements.is_empty()
+        ));
+    }
+
+    #[test]
+    fn index_and_index_assignment() {
+        let statements = parse_source("xs[0][1] = f()[2];").into_result().unwrap();
+        let [Statement::Expression(Expression::SetIndex { object, value, .. })] =
+            statements.as_slice()
+        else {
+            panic!("{statements:?}");
+        };
+        assert!(matches!(**object, Expression::Index { .. }));
+        assert!(matches!(**value, Expression::Index { .. }));
+    }
+
+    #[test]
+    fn unclosed_list() {
+        let parsed = parse_source("var xs = [1, 2;");
+        assert!(matches!(
+            parsed.errors.as_slice(),
+            [Error::ExpectedRightBracket { .. }]
+        ));
+        let parsed = parse_source("xs[1;");
+        assert!(matches!(
+            parsed.errors.as_slice(),
+            [Error::ExpectedRightBracket { .. }]
+        ));
+    }
+
+    #[test]
+    fn map_literal() {
+        let statements = parse_source("var m = {\"a\": 1, b: [2]};")
+            .into_result()
+            .unwrap();
+        let [Statement::VariableDeclaration {
+            expression: Some(Expression::Map { entries, .. }),
+            ..
+        }] = statements.as_slice()
+        else {
+            panic!("{statements:?}");
+        };
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(
+            entries[1],
+            (Expression::Variable { .. }, Expression::List { .. })
+        ));
+    }
+
+    #[test]
+    fn brace_at_statement_start_is_a_block() {
+        let statements = parse_source("{}").into_result().unwrap();
+        assert_eq!(statements, vec![Statement::Block(vec![])]);
+    }
+
+    #[test]
+    fn map_entry_without_colon() {
+        let parsed = parse_source("var m = {\"a\" 1};");
+        assert!(matches!(
+            parsed.errors.as_slice(),
+            [Error::ExpectedColon { .. }]
+        ));
+    }
 }
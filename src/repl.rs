@@ -0,0 +1,461 @@
+//! The interactive prompt. Input is read with line editing and history, lines are collected until
+//! brackets and strings are closed, and the value of a bare expression is shown after it runs.
+//! Lines starting with `:` are commands for inspecting the session, see [`Command`].
+
+use crate::backend::{analyze, dump_syntax_tree, Backend};
+use crate::diagnostics::{report, report_diagnostics, report_without_source, Diagnostic};
+use crate::result::Error;
+use crate::scanner::Scanner;
+use crate::token::Span;
+use crate::token::TokenType;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::path::PathBuf;
+use std::time::Instant;
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+const FILE_NAME: &str = "<stdin>";
+/// Where the history is kept, in the home directory.
+const HISTORY_FILE: &str = ".lox_history";
+
+const HELP: &str = "\
+:globals        list the global variables
+:env            list the variables in each scope, innermost first
+:ast <code>     show the syntax tree of the code as JSON
+:tokens <code>  show the tokens of the code
+:load <file>    run a file in this session
+:reset          forget everything defined so far
+:time <code>    run the code and show how long it took
+:help           show this list";
+
+/// A line starting with `:`, handled by the REPL instead of being run as Lox.
+#[derive(Debug, PartialEq)]
+enum Command<'a> {
+    Globals,
+    Environment,
+    Ast(&'a str),
+    Tokens(&'a str),
+    Load(&'a str),
+    Reset,
+    Time(&'a str),
+    Help,
+}
+
+impl<'a> Command<'a> {
+    /// Parses a command, or returns `None` for Lox code. Unknown commands and missing arguments
+    /// are errors with a message for the user.
+    fn parse(line: &'a str) -> Option<Result<Command<'a>, String>> {
+        let line = line.trim().strip_prefix(':')?;
+        let (name, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = argument.trim();
+        let with_argument = |command: fn(&'a str) -> Command<'a>| {
+            if argument.is_empty() {
+                Err(format!(
+                    ":{name} needs an argument. Type :help for a list of commands."
+                ))
+            } else {
+                Ok(command(argument))
+            }
+        };
+        Some(match name {
+            "globals" => Ok(Command::Globals),
+            "env" => Ok(Command::Environment),
+            "ast" => with_argument(Command::Ast),
+            "tokens" => with_argument(Command::Tokens),
+            "load" => with_argument(Command::Load),
+            "reset" => Ok(Command::Reset),
+            "time" => with_argument(Command::Time),
+            "help" => Ok(Command::Help),
+            _ => Err(format!(
+                "Unknown command :{name}. Type :help for a list of commands."
+            )),
+        })
+    }
+
+    fn execute<B: Backend + ?Sized>(self, backend: &mut B, session: &mut Session) {
+        match self {
+            Command::Globals => print_variables(&backend.globals(), ""),
+            Command::Environment => {
+                let scopes = backend.environment();
+                for (depth, scope) in scopes.iter().enumerate() {
+                    if depth + 1 == scopes.len() {
+                        println!("global scope:");
+                    } else {
+                        println!("scope {depth}:");
+                    }
+                    print_variables(scope, "  ");
+                }
+            }
+            Command::Ast(code) => {
+                let source = as_statement(code);
+                match dump_syntax_tree(&source) {
+                    Ok(json) => println!("{json}"),
+                    Err(errors) => report(FILE_NAME, &source, &errors),
+                }
+            }
+            Command::Tokens(code) => match Scanner::new(code, 1).scan_tokens() {
+                Ok(tokens) => tokens
+                    .iter()
+                    .for_each(|token| println!("{} {:?}", token.span, token.token_type)),
+                Err(error) => report(FILE_NAME, code, &[error]),
+            },
+            Command::Load(path) => match std::fs::read_to_string(path) {
+                Ok(source) => session.load(backend, path, &source),
+                Err(error) => report_without_source(&error.into()),
+            },
+            Command::Reset => backend.reset(),
+            Command::Time(code) => {
+                let start = Instant::now();
+                session.evaluate(backend, &format!("{code}\n"));
+                println!("took {:?}", start.elapsed());
+            }
+            Command::Help => println!("{HELP}"),
+        }
+    }
+}
+
+/// Adds the `;` that ends a statement, so commands can be given an expression like `1 + 2`.
+fn as_statement(code: &str) -> String {
+    if code.ends_with([';', '}']) {
+        code.to_string()
+    } else {
+        format!("{code};")
+    }
+}
+
+/// Code run in this session, typed at the prompt or loaded from a file.
+struct Entry {
+    /// The file the code was loaded from, or `None` if it was typed.
+    file: Option<String>,
+    /// The line number the code was run with. Every entry gets lines of its own, so the line of
+    /// a span tells which entry it is in.
+    first_line: usize,
+    source: String,
+}
+
+impl Entry {
+    fn contains(&self, line: usize) -> bool {
+        (self.first_line..self.first_line + self.source.lines().count()).contains(&line)
+    }
+
+    /// Reports the diagnostic with this entry's name and source. Typed lines are numbered across
+    /// the session, but the lines of a file are numbered from its start.
+    fn report(&self, mut diagnostic: Diagnostic) {
+        let name = match &self.file {
+            Some(file) => {
+                if let Some(span) = &mut diagnostic.span {
+                    span.line = span.line - self.first_line + 1;
+                }
+                file
+            }
+            None => FILE_NAME,
+        };
+        report_diagnostics(name, Some(&self.source), [diagnostic]);
+    }
+}
+
+/// The code run so far. Errors in functions defined by an earlier entry are shown with the name
+/// and line of that entry.
+struct Session {
+    /// The line number of the next entry.
+    line_number: usize,
+    entries: Vec<Entry>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            line_number: 1,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl Session {
+    /// Runs the input like a file, but also prints the value of a final expression. The `;` after
+    /// a bare expression like `1 + 2` can be left out.
+    fn evaluate<B: Backend + ?Sized>(&mut self, backend: &mut B, input: &str) {
+        let line_number = self.add_entry(None, input);
+        let result = analyze(input, line_number)
+            .or_else(|errors| {
+                let statement = format!("{};\n", input.trim_end());
+                analyze(&statement, line_number).map_err(|_| errors)
+            })
+            .and_then(|mut analysis| {
+                self.report(analysis.warnings().iter().map(Diagnostic::from));
+                let show_value = analysis.return_last_expression();
+                let value = backend.run_for_value(analysis)?;
+                if show_value {
+                    println!("{value}");
+                }
+                Ok(())
+            });
+        self.report(
+            result
+                .err()
+                .unwrap_or_default()
+                .iter()
+                .map(Diagnostic::from),
+        );
+    }
+
+    /// Runs a file in this session.
+    fn load<B: Backend + ?Sized>(&mut self, backend: &mut B, path: &str, source: &str) {
+        let line_number = self.add_entry(Some(path), source);
+        let result = analyze(source, line_number).and_then(|analysis| {
+            self.report(analysis.warnings().iter().map(Diagnostic::from));
+            backend.run(analysis)
+        });
+        self.report(
+            result
+                .err()
+                .unwrap_or_default()
+                .iter()
+                .map(Diagnostic::from),
+        );
+    }
+
+    /// Records the code, and returns the line number to run it with.
+    fn add_entry(&mut self, file: Option<&str>, source: &str) -> usize {
+        let first_line = self.line_number;
+        self.line_number += source.lines().count().max(1);
+        self.entries.push(Entry {
+            file: file.map(str::to_string),
+            first_line,
+            source: source.to_string(),
+        });
+        first_line
+    }
+
+    /// Reports each diagnostic with the entry it points into.
+    fn report(&self, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+        for diagnostic in diagnostics {
+            match diagnostic.span.and_then(|span| self.entry_at(span)) {
+                Some(entry) => entry.report(diagnostic),
+                None => report_diagnostics(FILE_NAME, None, [diagnostic]),
+            }
+        }
+    }
+
+    /// The entry the span is in.
+    fn entry_at(&self, span: Span) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.contains(span.line))
+    }
+}
+
+fn print_variables(variables: &[(String, String)], indentation: &str) {
+    for (name, value) in variables {
+        println!("{indentation}{name} = {value}");
+    }
+}
+
+pub(crate) fn run<B: Backend + ?Sized>(backend: &mut B) -> Result<(), Error> {
+    let mut editor = DefaultEditor::new().map_err(readline_error)?;
+    let history = std::env::home_dir().map(|home| home.join(HISTORY_FILE));
+    if let Some(path) = &history {
+        // There is no history the first time the REPL runs, which is fine.
+        let _ = editor.load_history(path);
+    }
+    let result = read_eval_print(backend, &mut editor);
+    save_history(&mut editor, history);
+    result
+}
+
+fn read_eval_print<B: Backend + ?Sized>(
+    backend: &mut B,
+    editor: &mut DefaultEditor,
+) -> Result<(), Error> {
+    let mut session = Session::default();
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                // Commands are only recognized at the start of the input, not on continuation lines.
+                if let Some(command) = Command::parse(&line).filter(|_| input.is_empty()) {
+                    editor.add_history_entry(&line).map_err(readline_error)?;
+                    match command {
+                        Ok(command) => command.execute(backend, &mut session),
+                        Err(message) => println!("{message}"),
+                    }
+                    continue;
+                }
+                input.push_str(&line);
+                input.push('\n');
+                if is_incomplete(&input) {
+                    continue;
+                }
+                if !input.trim().is_empty() {
+                    editor
+                        .add_history_entry(input.trim_end())
+                        .map_err(readline_error)?;
+                    session.evaluate(backend, &input);
+                }
+                input.clear();
+            }
+            // Ctrl-C throws away what was typed so far, but keeps the REPL running.
+            Err(ReadlineError::Interrupted) => input.clear(),
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(error) => return Err(readline_error(error)),
+        }
+    }
+}
+
+/// Whether the input has unclosed brackets or an unterminated string, so it continues on the next
+/// line. Other errors are left for the parser to report once the input is run.
+fn is_incomplete(input: &str) -> bool {
+    let tokens = match Scanner::new(input, 1).scan_tokens() {
+        Ok(tokens) => tokens,
+        Err(Error::UnterminatedString { .. }) => return true,
+        Err(_) => return false,
+    };
+    let depth = tokens
+        .iter()
+        .fold(0, |depth, token| match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => depth + 1,
+            TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => depth - 1,
+            _ => depth,
+        });
+    depth > 0
+}
+
+/// History is a convenience, so failing to save it doesn't make the REPL fail.
+fn save_history(editor: &mut DefaultEditor, path: Option<PathBuf>) {
+    if let Some(path) = path {
+        let _ = editor.save_history(&path);
+    }
+}
+
+fn readline_error(error: ReadlineError) -> Error {
+    match error {
+        ReadlineError::Io(error) => error.into(),
+        error => std::io::Error::other(error).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::object::Object;
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!(Command::parse(":globals"), Some(Ok(Command::Globals)));
+        assert_eq!(Command::parse("  :env  "), Some(Ok(Command::Environment)));
+        assert_eq!(
+            Command::parse(":time  fib(20) "),
+            Some(Ok(Command::Time("fib(20)")))
+        );
+        assert_eq!(
+            Command::parse(":load dir/file name.lox"),
+            Some(Ok(Command::Load("dir/file name.lox")))
+        );
+    }
+
+    #[test]
+    fn lox_code_is_not_a_command() {
+        assert_eq!(Command::parse("print 1;"), None);
+        assert_eq!(Command::parse("a ? :b"), None);
+    }
+
+    #[test]
+    fn bad_commands_are_errors() {
+        assert!(matches!(Command::parse(":nope"), Some(Err(_))));
+        assert!(matches!(Command::parse(":ast"), Some(Err(_))));
+        assert!(matches!(Command::parse(":tokens  "), Some(Err(_))));
+    }
+
+    #[test]
+    fn expressions_become_statements() {
+        assert_eq!(as_statement("1 + 2"), "1 + 2;");
+        assert_eq!(as_statement("print 1;"), "print 1;");
+        assert_eq!(as_statement("{ print 1; }"), "{ print 1; }");
+    }
+
+    #[test]
+    fn semicolon_after_the_last_statement_is_optional() {
+        let mut interpreter = Interpreter::new();
+        let mut session = Session::default();
+        session.evaluate(&mut interpreter, "var a = 1;\n");
+        session.evaluate(&mut interpreter, "a = a + 2\n");
+        assert_eq!(
+            interpreter.get_global("a"),
+            Some(Object::Number(3.0.into()))
+        );
+    }
+
+    #[test]
+    fn errors_are_shown_with_the_entry_they_are_in() {
+        let mut interpreter = Interpreter::new();
+        let mut session = Session::default();
+        session.evaluate(&mut interpreter, "fun f() {\n  return nil + 1;\n}\n");
+        session.evaluate(&mut interpreter, "var b = 2;\n");
+        let source = |line| {
+            let entry = session.entry_at(Span::new(line, 1, 0, 0))?;
+            Some((entry.file.as_deref(), entry.source.as_str()))
+        };
+        assert_eq!(source(2), Some((None, "fun f() {\n  return nil + 1;\n}\n")));
+        assert_eq!(source(4), Some((None, "var b = 2;\n")));
+        assert_eq!(source(5), None);
+    }
+
+    #[test]
+    fn loaded_files_get_lines_of_their_own() {
+        let mut interpreter = Interpreter::new();
+        let mut session = Session::default();
+        session.evaluate(&mut interpreter, "var a = 1;\n");
+        session.load(&mut interpreter, "lib.lox", "fun f() {\n  return a;\n}\n");
+        session.evaluate(&mut interpreter, "var b = f();\n");
+        let entry = session.entry_at(Span::new(3, 1, 0, 0)).unwrap();
+        assert_eq!(entry.file.as_deref(), Some("lib.lox"));
+        assert_eq!(entry.first_line, 2);
+        assert!(session
+            .entry_at(Span::new(5, 1, 0, 0))
+            .unwrap()
+            .file
+            .is_none());
+        assert_eq!(
+            interpreter.get_global("b"),
+            Some(Object::Number(1.0.into()))
+        );
+    }
+
+    #[test]
+    fn balanced_input_is_complete() {
+        assert!(!is_incomplete("print 1;\n"));
+        assert!(!is_incomplete("fun f() { return [1, (2)]; }\n"));
+        assert!(!is_incomplete("\n"));
+    }
+
+    #[test]
+    fn unclosed_brackets_continue() {
+        assert!(is_incomplete("fun f() {\n"));
+        assert!(is_incomplete("print (1 +\n"));
+        assert!(is_incomplete("var xs = [1,\n"));
+        assert!(is_incomplete("{ if (true) {\n}\n"));
+    }
+
+    #[test]
+    fn unterminated_strings_continue() {
+        assert!(is_incomplete("print \"two\n"));
+        assert!(!is_incomplete("print \"two\nlines\";\n"));
+    }
+
+    #[test]
+    fn brackets_in_strings_and_comments_are_ignored() {
+        assert!(!is_incomplete("print \"{\";\n"));
+        assert!(!is_incomplete("print 1; // (\n"));
+    }
+
+    #[test]
+    fn other_errors_are_left_to_the_parser() {
+        assert!(!is_incomplete("print @;\n"));
+        assert!(!is_incomplete("}\n"));
+    }
+}
@@ -1,8 +1,22 @@
-use crate::expression::Expression;
-use crate::interpreter::Interpreter;
+use crate::class::INITIALIZER_NAME;
+use crate::expression::{Expression, ExpressionId};
 use crate::map_stack::MapStack;
-use crate::result::Error;
-use crate::statement::{FunctionDeclaration, Statement};
+use crate::result::{Error, Warning};
+use crate::statement::{ClassDeclaration, FunctionDeclaration, Parameter, Statement};
+use crate::token::Span;
+use std::collections::HashMap;
+
+/// Where a local variable lives at runtime: how many environments up from the one accessing
+/// it, and its index among the variables of that environment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+/// The slot of every local variable access, keyed by the id of the accessing expression.
+/// Accesses that aren't in the map are globals.
+pub type Locals = HashMap<ExpressionId, Slot>;
 
 #[derive(PartialEq)]
 enum VariableStatus {
@@ -10,19 +24,68 @@ enum VariableStatus {
     Defined,
 }
 
-pub struct Resolver<'a> {
-    interpreter: &'a mut Interpreter,
-    scopes: MapStack<String, VariableStatus>,
+/// Where a local was declared in the source. Locals the user didn't declare, like `this`, have
+/// none.
+#[derive(Clone, Copy)]
+enum Declaration {
+    Variable(Span),
+    Parameter(Span),
+    Class(Span),
+}
+
+struct Local {
+    index: usize,
+    status: VariableStatus,
+    declaration: Option<Declaration>,
+    used: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
 }
 
-impl<'a> Resolver<'a> {
-    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver {
+    locals: Locals,
+    scopes: MapStack<String, Local>,
+    /// How many variables each scope declared, counting shadowed ones, which still take a slot.
+    scope_sizes: Vec<usize>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    /// Number of loops around the current statement, within the current function.
+    loop_depth: usize,
+    warnings: Vec<Warning>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
         Resolver {
-            interpreter,
+            locals: HashMap::new(),
             scopes: MapStack::new(),
+            scope_sizes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            loop_depth: 0,
+            warnings: Vec::new(),
         }
     }
 
+    /// Returns the resolved locals and the warnings, in source order.
+    pub fn finish(mut self) -> (Locals, Vec<Warning>) {
+        self.warnings.sort_by_key(|warning| warning.span().start);
+        (self.locals, self.warnings)
+    }
+
     pub fn resolve_statement(&mut self, statement: &Statement) -> Result<(), Error> {
         match statement {
             Statement::Expression(expression) => self.resolve_expression(expression)?,
@@ -40,16 +103,23 @@ impl<'a> Resolver<'a> {
             Statement::Print(expression) => {
                 self.resolve_expression(expression)?;
             }
-            Statement::Return(expression) => {
+            Statement::Return { expression, span } => {
+                if self.current_function == FunctionType::None {
+                    self.warnings.push(Warning::TopLevelReturn { span: *span });
+                }
                 if let Some(expression) = expression {
+                    if self.current_function == FunctionType::Initializer {
+                        return Err(Error::ReturnFromInitializer { span: *span });
+                    }
                     self.resolve_expression(expression)?
                 }
             }
             Statement::VariableDeclaration {
                 identifier,
                 expression,
+                span,
             } => {
-                self.declare(identifier);
+                self.declare(identifier, Declaration::Variable(*span))?;
                 if let Some(initializer) = expression {
                     self.resolve_expression(initializer)?;
                 }
@@ -59,24 +129,48 @@ impl<'a> Resolver<'a> {
                 identifier,
                 parameters,
                 body,
+                span,
             }) => {
-                self.declare(identifier); //TODO: this line makes no difference, right?
+                self.declare(identifier, Declaration::Variable(*span))?;
                 self.define(identifier);
-                self.resolve_function(parameters, body)?;
+                self.resolve_function(parameters, body, FunctionType::Function)?;
+            }
+            Statement::ClassDeclaration(class_declaration) => {
+                self.resolve_class(class_declaration)?;
             }
             Statement::While {
                 expression,
                 statement,
+                increment,
             } => {
                 self.resolve_expression(expression)?;
-                self.resolve_statement(statement)?;
+                self.loop_depth += 1;
+                let result = self.resolve_statement(statement);
+                self.loop_depth -= 1;
+                result?;
+                if let Some(increment) = increment {
+                    self.resolve_expression(increment)?;
+                }
+            }
+            Statement::Break { span } => {
+                if self.loop_depth == 0 {
+                    return Err(Error::BreakOutsideLoop { span: *span });
+                }
+            }
+            Statement::Continue { span } => {
+                if self.loop_depth == 0 {
+                    return Err(Error::ContinueOutsideLoop { span: *span });
+                }
             }
             Statement::Block(statements) => {
+                self.warn_about_unreachable_code(statements);
                 self.begin_scope();
-                for statement in statements {
-                    self.resolve_statement(statement)?;
-                }
+                let result = statements
+                    .iter()
+                    .try_for_each(|statement| self.resolve_statement(statement));
+                // Also after an error, so the following statements aren't resolved in this scope.
                 self.end_scope();
+                result?;
             }
         }
         Ok(())
@@ -85,82 +179,436 @@ impl<'a> Resolver<'a> {
     fn resolve_expression(&mut self, expression: &Expression) -> Result<(), Error> {
         match expression {
             Expression::Literal(_) => {}
-            Expression::Unary {
-                operator,
-                expression,
-            } => {
+            Expression::Unary { expression, .. } => {
                 self.resolve_expression(expression)?;
             }
-            Expression::Binary {
-                left,
-                operator,
-                right,
-            } => {
+            Expression::Binary { left, right, .. } => {
                 self.resolve_expression(left)?;
                 self.resolve_expression(right)?;
             }
-            Expression::Variable(identifier) => {
-                if self.scopes.get_in_top(identifier) == Some(&VariableStatus::Declared) {
-                    return Err(todo!());
+            Expression::Variable {
+                id,
+                identifier,
+                span,
+            } => {
+                if self
+                    .scopes
+                    .get_in_top(identifier)
+                    .is_some_and(|local| local.status == VariableStatus::Declared)
+                {
+                    return Err(Error::ReadInOwnInitializer {
+                        name: identifier.clone(),
+                        span: *span,
+                    });
                 }
-                self.resolve_local(&identifier, expression);
+                self.resolve_local(identifier, *id, true);
             }
             Expression::Grouping(expression) => {
                 self.resolve_expression(expression)?;
             }
-            Expression::Assignment { identifier, value } => {
+            Expression::Assignment {
+                id,
+                identifier,
+                value,
+                ..
+            } => {
                 self.resolve_expression(value)?;
-                self.resolve_local(identifier, expression);
+                self.resolve_local(identifier, *id, false);
             }
             Expression::FunctionCall {
                 function,
                 arguments,
+                ..
             } => {
                 self.resolve_expression(function)?;
                 for argument in arguments {
                     self.resolve_expression(argument)?;
                 }
             }
+            Expression::Get { object, .. } => {
+                self.resolve_expression(object)?;
+            }
+            Expression::Set { object, value, .. } => {
+                self.resolve_expression(value)?;
+                self.resolve_expression(object)?;
+            }
+            Expression::This { id, span } => {
+                if self.current_class == ClassType::None {
+                    return Err(Error::ThisOutsideClass { span: *span });
+                }
+                self.resolve_local("this", *id, true);
+            }
+            Expression::Super { id, span, .. } => match self.current_class {
+                ClassType::None => return Err(Error::SuperOutsideClass { span: *span }),
+                ClassType::Class => return Err(Error::SuperWithoutSuperclass { span: *span }),
+                ClassType::Subclass => self.resolve_local("super", *id, true),
+            },
+            Expression::List { elements, .. } => {
+                for element in elements {
+                    self.resolve_expression(element)?;
+                }
+            }
+            Expression::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.resolve_expression(key)?;
+                    self.resolve_expression(value)?;
+                }
+            }
+            Expression::Index { object, index, .. } => {
+                self.resolve_expression(object)?;
+                self.resolve_expression(index)?;
+            }
+            Expression::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.resolve_expression(value)?;
+                self.resolve_expression(object)?;
+                self.resolve_expression(index)?;
+            }
         }
         Ok(())
     }
 
     fn resolve_function(
         &mut self,
-        parameters: &Vec<String>,
+        parameters: &[Parameter],
         body: &Statement,
+        function_type: FunctionType,
     ) -> Result<(), Error> {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+        let enclosing_loop_depth = std::mem::take(&mut self.loop_depth);
         self.begin_scope();
-        for parameter in parameters {
-            self.declare(parameter); //TODO: this line makes no difference, right?
-            self.define(parameter);
+        let result = parameters
+            .iter()
+            .try_for_each(|Parameter { name, span }| {
+                self.declare(name, Declaration::Parameter(*span))?;
+                self.define(name);
+                Ok(())
+            })
+            .and_then(|()| self.resolve_statement(body));
+        self.end_scope();
+        self.current_function = enclosing_function;
+        self.loop_depth = enclosing_loop_depth;
+        result
+    }
+
+    fn resolve_class(&mut self, class_declaration: &ClassDeclaration) -> Result<(), Error> {
+        let ClassDeclaration {
+            identifier,
+            superclass,
+            methods,
+            span,
+        } = class_declaration;
+        self.declare(identifier, Declaration::Class(*span))?;
+        self.define(identifier);
+
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        if let Some(superclass) = superclass {
+            if let Expression::Variable {
+                identifier: superclass_identifier,
+                ..
+            } = superclass
+            {
+                if superclass_identifier == identifier {
+                    self.current_class = enclosing_class;
+                    return Err(Error::ClassInheritsFromItself { span: *span });
+                }
+            }
+            self.current_class = ClassType::Subclass;
+            if let Err(error) = self.resolve_expression(superclass) {
+                self.current_class = enclosing_class;
+                return Err(error);
+            }
+            self.begin_scope();
+            self.define("super");
         }
-        self.resolve_statement(body)?;
+
+        self.begin_scope();
+        self.define("this");
+        let result = methods.iter().try_for_each(|method| {
+            let function_type = if method.identifier == INITIALIZER_NAME {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(&method.parameters, &method.body, function_type)
+        });
         self.end_scope();
-        Ok(())
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+        self.current_class = enclosing_class;
+        result
     }
 
-    fn resolve_local(&mut self, identifier: &String, expression: &Expression) {
-        if let Some(depth) = self.scopes.any_contains(identifier) {
-            self.interpreter.resolve(expression.clone(), depth);
+    /// Records the slot of a local variable access. Reads mark the variable as used, while
+    /// assignments don't.
+    fn resolve_local(&mut self, identifier: &str, id: ExpressionId, is_read: bool) {
+        if let Some((depth, local)) = self.scopes.get_in_any_mut(&identifier.to_string()) {
+            local.used |= is_read;
+            let index = local.index;
+            self.locals.insert(id, Slot { depth, index });
         }
     }
 
     fn begin_scope(&mut self) {
         self.scopes.push();
+        self.scope_sizes.push(0);
     }
 
     fn end_scope(&mut self) {
-        self.scopes.pop();
+        self.scope_sizes.pop();
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        for (name, local) in scope {
+            if local.used || name.starts_with('_') {
+                continue;
+            }
+            match local.declaration {
+                Some(Declaration::Variable(span)) => {
+                    self.warnings.push(Warning::UnusedVariable { name, span })
+                }
+                Some(Declaration::Parameter(span)) => {
+                    self.warnings.push(Warning::UnusedParameter { name, span })
+                }
+                Some(Declaration::Class(_)) | None => {}
+            }
+        }
+    }
+
+    /// Code after a `return`, `break` or `continue` in the same block never runs.
+    fn warn_about_unreachable_code(&mut self, statements: &[Statement]) {
+        let Some((_, preceding)) = statements.split_last() else {
+            return;
+        };
+        let jump = preceding.iter().find_map(|statement| match statement {
+            Statement::Return { span, .. }
+            | Statement::Break { span }
+            | Statement::Continue { span } => Some(*span),
+            _ => None,
+        });
+        if let Some(span) = jump {
+            self.warnings.push(Warning::UnreachableCode { span });
+        }
+    }
+
+    /// Declares a variable of the current scope, which must not have one with the same name.
+    /// Globals can be declared again.
+    fn declare(&mut self, identifier: &str, declaration: Declaration) -> Result<(), Error> {
+        let (Declaration::Variable(span) | Declaration::Parameter(span) | Declaration::Class(span)) =
+            declaration;
+        let name = identifier.to_string();
+        if self.scopes.get_in_top(&name).is_some() {
+            return Err(Error::DuplicateDeclaration { name, span });
+        }
+        if !matches!(declaration, Declaration::Class(_))
+            && self.scopes.get_in_enclosing(&name).is_some()
+        {
+            self.warnings.push(Warning::ShadowedVariable { name, span });
+        }
+        self.add_local(identifier, Some(declaration));
+        Ok(())
+    }
+
+    /// Gives the variable the next slot of the current scope, in the order the interpreter will
+    /// define it. Globals are looked up by name and don't get a slot.
+    fn add_local(&mut self, identifier: &str, declaration: Option<Declaration>) {
+        let Some(size) = self.scope_sizes.last_mut() else {
+            return;
+        };
+        let local = Local {
+            index: *size,
+            status: VariableStatus::Declared,
+            declaration,
+            used: false,
+        };
+        *size += 1;
+        self.scopes.insert(identifier.to_string(), local);
+    }
+
+    /// Marks the variable as initialized, declaring it first unless it was just declared.
+    fn define(&mut self, identifier: &str) {
+        let identifier = identifier.to_string();
+        if !self
+            .scopes
+            .get_in_top(&identifier)
+            .is_some_and(|local| local.status == VariableStatus::Declared)
+        {
+            self.add_local(&identifier, None);
+        }
+        if let Some(local) = self.scopes.get_in_top_mut(&identifier) {
+            local.status = VariableStatus::Defined;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::analyze;
+    use crate::token::Span;
+
+    fn errors(source: &str) -> Vec<Error> {
+        analyze(source, 1).err().unwrap_or_default()
+    }
+
+    fn warnings(source: &str) -> Vec<String> {
+        let analysis = analyze(source, 1).unwrap();
+        analysis
+            .warnings()
+            .iter()
+            .map(|warning| format!("{} {}", warning.code(), warning.span()))
+            .collect()
+    }
+
+    #[test]
+    fn reading_a_local_in_its_own_initializer() {
+        let errors = errors("{ var a = a; }");
+        let [Error::ReadInOwnInitializer { name, span }] = errors.as_slice() else {
+            panic!("{errors:?}");
+        };
+        assert_eq!(name, "a");
+        assert_eq!(*span, Span::new(1, 11, 10, 11));
+    }
+
+    #[test]
+    fn outer_variable_in_initializer_of_a_global() {
+        assert!(errors("var a = 1; var b = a; var a = a;").is_empty());
+    }
+
+    #[test]
+    fn initializer_reading_a_shadowed_variable() {
+        let errors = errors("{ var a = 1; { var a = a; } }");
+        assert!(matches!(
+            errors.as_slice(),
+            [Error::ReadInOwnInitializer { .. }]
+        ));
+    }
+
+    #[test]
+    fn duplicate_declarations_in_one_scope() {
+        let errors = errors("{ var a = 1; var a = 2; }");
+        let [Error::DuplicateDeclaration { name, span }] = errors.as_slice() else {
+            panic!("{errors:?}");
+        };
+        assert_eq!(name, "a");
+        assert_eq!(*span, Span::new(1, 18, 17, 18));
+    }
+
+    #[test]
+    fn duplicate_functions_classes_and_parameters() {
+        for source in [
+            "{ fun f() {} var f; }",
+            "{ var A; class A {} }",
+            "fun f(a, a) {}",
+        ] {
+            let errors = errors(source);
+            assert!(
+                matches!(errors.as_slice(), [Error::DuplicateDeclaration { .. }]),
+                "{source}: {errors:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn declarations_in_different_scopes_are_not_duplicates() {
+        assert!(errors("{ var a; { var a; } } fun f(a) { { var a; } }").is_empty());
+    }
+
+    #[test]
+    fn resolution_continues_after_an_error_in_a_block() {
+        let errors = errors("{ var a = a; } var b; var b;");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn locals_get_slots_in_declaration_order() {
+        let statements = crate::parser::Parser::new(
+            crate::scanner::Scanner::new("{ var a; var b; fun f(c) { print b; } }", 1)
+                .scan_tokens()
+                .unwrap(),
+        )
+        .parse()
+        .into_result()
+        .unwrap();
+        let mut resolver = Resolver::new();
+        resolver.resolve_statement(&statements[0]).unwrap();
+        let (locals, _) = resolver.finish();
+        // `b` is read from the function's body, two scopes inside the block.
+        let slots = locals.values().copied().collect::<Vec<_>>();
+        assert_eq!(slots, vec![Slot { depth: 2, index: 1 }]);
+    }
+
+    #[test]
+    fn code_without_warnings() {
+        let source = "var unused = 1; fun f(a) { var b = a; return b; } print f(1);";
+        assert!(warnings(source).is_empty());
+    }
+
+    #[test]
+    fn unused_locals_and_parameters() {
+        assert_eq!(
+            warnings("fun f(a, b) { var c = 1; var d; d = b; }"),
+            vec!["W0201 1:7", "W0200 1:19", "W0200 1:30"]
+        );
+    }
+
+    #[test]
+    fn unused_local_function() {
+        assert_eq!(warnings("{ fun g() {} }"), vec!["W0200 1:7"]);
+    }
+
+    #[test]
+    fn variables_used_by_closures_are_used() {
+        assert!(warnings("fun f() { var a = 1; fun g() { return a; } return g; }").is_empty());
+    }
+
+    #[test]
+    fn underscore_silences_unused_warnings() {
+        assert!(warnings("fun f(_a) { var _b; }").is_empty());
+    }
+
+    #[test]
+    fn shadowing() {
+        assert_eq!(
+            warnings("{ var a = 1; { var a = 2; print a; } print a; }"),
+            vec!["W0202 1:20"]
+        );
+        assert_eq!(
+            warnings("fun f(a) { fun g(a) { return a; } return g(a); }"),
+            vec!["W0202 1:18"]
+        );
+    }
+
+    #[test]
+    fn globals_are_not_shadowed() {
+        assert!(warnings("var a = 1; { var a = 2; print a; }").is_empty());
     }
 
-    fn declare(&mut self, identifier: &String) {
-        self.scopes
-            .insert(identifier.clone(), VariableStatus::Declared);
+    #[test]
+    fn unreachable_code() {
+        assert_eq!(
+            warnings("fun f() { return 1; print 2; }"),
+            vec!["W0203 1:11"]
+        );
+        assert_eq!(
+            warnings("while (true) { break; print 1; }"),
+            vec!["W0203 1:16"]
+        );
+        assert!(warnings("fun f() { print 1; return 2; }").is_empty());
     }
 
-    fn define(&mut self, identifier: &String) {
-        self.scopes
-            .insert(identifier.clone(), VariableStatus::Defined);
+    #[test]
+    fn top_level_return() {
+        assert_eq!(warnings("print 1; return;"), vec!["W0204 1:10"]);
+        assert_eq!(warnings("{ return; }"), vec!["W0204 1:3"]);
     }
 }
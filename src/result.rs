@@ -1,51 +1,297 @@
 use crate::object::Error as ObjectError;
+use crate::token::Span;
+use std::fmt::{Display, Formatter};
 use std::io::Error as IoError;
 
 #[derive(Debug)]
 pub enum Error {
+    BreakOutsideLoop { span: Span },
+    ClassInheritsFromItself { span: Span },
+    ContinueOutsideLoop { span: Span },
+    DuplicateDeclaration { name: String, span: Span },
     EvaluationError(ObjectError),
-    ExpectedEndOfExpression,
-    ExpectedExpression { position: usize },
-    ExpectedIdentifier,
-    ExpectedLeftBrace,
-    ExpectedLeftParen,
-    ExpectedRightBrace,
-    ExpectedRightParen,
-    InvalidAssignmentTarget,
+    ExpectedColon { span: Span },
+    ExpectedDot { span: Span },
+    ExpectedEndOfExpression { span: Span },
+    ExpectedExpression { span: Span },
+    ExpectedIdentifier { span: Span },
+    ExpectedLeftBrace { span: Span },
+    ExpectedLeftParen { span: Span },
+    ExpectedRightBrace { span: Span },
+    ExpectedRightBracket { span: Span },
+    ExpectedRightParen { span: Span },
+    InvalidAssignmentTarget { span: Span },
+    InvalidSyntaxTree { message: String },
     IoError(IoError),
-    KeyboardInterrupt,
-    OutOfLineNumbers,
-    TooManyArguments(usize),
-    UnexpectedCharacter { character: char, position: usize },
-    UnexpectedEof,
-    UnterminatedNumber { string: String, position: usize },
-    UnterminatedString { string: String, position: usize },
-    UnmatchedParenthesis { position: usize },
+    JumpTooLarge { span: Span },
+    ReadInOwnInitializer { name: String, span: Span },
+    ReturnFromInitializer { span: Span },
+    SuperOutsideClass { span: Span },
+    SuperWithoutSuperclass { span: Span },
+    ThisOutsideClass { span: Span },
+    TooManyArguments { count: usize, span: Span },
+    TooManyConstants { span: Span },
+    TooManyLocalVariables { span: Span },
+    TooManyUpvalues { span: Span },
+    UnexpectedCharacter { character: char, span: Span },
+    UnterminatedNumber { string: String, span: Span },
+    UnterminatedString { string: String, span: Span },
+    UnmatchedParenthesis { span: Span },
 }
 
 impl Error {
     pub fn exit_code(&self) -> i32 {
         match &self {
+            Error::BreakOutsideLoop { .. } => exitcode::USAGE,
+            Error::ClassInheritsFromItself { .. } => exitcode::USAGE,
+            Error::ContinueOutsideLoop { .. } => exitcode::USAGE,
+            Error::DuplicateDeclaration { .. } => exitcode::USAGE,
             Error::EvaluationError(_) => exitcode::USAGE,
-            Error::ExpectedEndOfExpression => exitcode::USAGE,
+            Error::ExpectedColon { .. } => exitcode::USAGE,
+            Error::ExpectedDot { .. } => exitcode::USAGE,
+            Error::ExpectedEndOfExpression { .. } => exitcode::USAGE,
             Error::ExpectedExpression { .. } => exitcode::USAGE,
-            Error::ExpectedIdentifier => exitcode::USAGE,
-            Error::ExpectedLeftBrace => exitcode::USAGE,
-            Error::ExpectedLeftParen => exitcode::USAGE,
-            Error::ExpectedRightBrace => exitcode::USAGE,
-            Error::ExpectedRightParen => exitcode::USAGE,
+            Error::ExpectedIdentifier { .. } => exitcode::USAGE,
+            Error::ExpectedLeftBrace { .. } => exitcode::USAGE,
+            Error::ExpectedLeftParen { .. } => exitcode::USAGE,
+            Error::ExpectedRightBrace { .. } => exitcode::USAGE,
+            Error::ExpectedRightBracket { .. } => exitcode::USAGE,
+            Error::ExpectedRightParen { .. } => exitcode::USAGE,
+            Error::InvalidSyntaxTree { .. } => exitcode::DATAERR,
             Error::IoError(_) => exitcode::IOERR,
-            Error::KeyboardInterrupt => exitcode::OK,
-            Error::InvalidAssignmentTarget => exitcode::USAGE,
-            Error::OutOfLineNumbers => exitcode::SOFTWARE,
-            Error::TooManyArguments(_) => exitcode::USAGE,
+            Error::JumpTooLarge { .. } => exitcode::USAGE,
+            Error::InvalidAssignmentTarget { .. } => exitcode::USAGE,
+            Error::ReadInOwnInitializer { .. } => exitcode::USAGE,
+            Error::ReturnFromInitializer { .. } => exitcode::USAGE,
+            Error::SuperOutsideClass { .. } => exitcode::USAGE,
+            Error::SuperWithoutSuperclass { .. } => exitcode::USAGE,
+            Error::ThisOutsideClass { .. } => exitcode::USAGE,
+            Error::TooManyArguments { .. } => exitcode::USAGE,
+            Error::TooManyConstants { .. } => exitcode::USAGE,
+            Error::TooManyLocalVariables { .. } => exitcode::USAGE,
+            Error::TooManyUpvalues { .. } => exitcode::USAGE,
             Error::UnexpectedCharacter { .. } => exitcode::USAGE,
-            Error::UnexpectedEof => exitcode::USAGE,
             Error::UnterminatedNumber { .. } => exitcode::USAGE,
             Error::UnterminatedString { .. } => exitcode::USAGE,
             Error::UnmatchedParenthesis { .. } => exitcode::USAGE,
         }
     }
+
+    /// A stable identifier for the kind of error, shown in diagnostics.
+    pub fn code(&self) -> &'static str {
+        match &self {
+            Error::UnexpectedCharacter { .. } => "E0001",
+            Error::UnterminatedString { .. } => "E0002",
+            Error::UnterminatedNumber { .. } => "E0003",
+            Error::UnmatchedParenthesis { .. } => "E0005",
+            Error::ExpectedExpression { .. } => "E0100",
+            Error::ExpectedEndOfExpression { .. } => "E0101",
+            Error::ExpectedIdentifier { .. } => "E0102",
+            Error::ExpectedLeftParen { .. } => "E0103",
+            Error::ExpectedRightParen { .. } => "E0104",
+            Error::ExpectedLeftBrace { .. } => "E0105",
+            Error::ExpectedRightBrace { .. } => "E0106",
+            Error::ExpectedDot { .. } => "E0107",
+            Error::InvalidAssignmentTarget { .. } => "E0108",
+            Error::TooManyArguments { .. } => "E0109",
+            Error::ExpectedRightBracket { .. } => "E0110",
+            Error::ExpectedColon { .. } => "E0111",
+            Error::ClassInheritsFromItself { .. } => "E0200",
+            Error::ReturnFromInitializer { .. } => "E0201",
+            Error::SuperOutsideClass { .. } => "E0202",
+            Error::SuperWithoutSuperclass { .. } => "E0203",
+            Error::ThisOutsideClass { .. } => "E0204",
+            Error::BreakOutsideLoop { .. } => "E0205",
+            Error::ContinueOutsideLoop { .. } => "E0206",
+            Error::ReadInOwnInitializer { .. } => "E0207",
+            Error::DuplicateDeclaration { .. } => "E0208",
+            Error::TooManyConstants { .. } => "E0300",
+            Error::TooManyLocalVariables { .. } => "E0301",
+            Error::TooManyUpvalues { .. } => "E0302",
+            Error::JumpTooLarge { .. } => "E0303",
+            Error::EvaluationError(error) => error.kind.code(),
+            Error::IoError(_) => "E0900",
+            Error::InvalidSyntaxTree { .. } => "E0903",
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match &self {
+            Error::BreakOutsideLoop { span } => Some(*span),
+            Error::ClassInheritsFromItself { span } => Some(*span),
+            Error::ContinueOutsideLoop { span } => Some(*span),
+            Error::DuplicateDeclaration { span, .. } => Some(*span),
+            Error::EvaluationError(error) => Some(error.span),
+            Error::ExpectedColon { span } => Some(*span),
+            Error::ExpectedDot { span } => Some(*span),
+            Error::ExpectedEndOfExpression { span } => Some(*span),
+            Error::ExpectedExpression { span } => Some(*span),
+            Error::ExpectedIdentifier { span } => Some(*span),
+            Error::ExpectedLeftBrace { span } => Some(*span),
+            Error::ExpectedLeftParen { span } => Some(*span),
+            Error::ExpectedRightBrace { span } => Some(*span),
+            Error::ExpectedRightParen { span } => Some(*span),
+            Error::ExpectedRightBracket { span } => Some(*span),
+            Error::InvalidAssignmentTarget { span } => Some(*span),
+            Error::InvalidSyntaxTree { .. } => None,
+            Error::IoError(_) => None,
+            Error::JumpTooLarge { span } => Some(*span),
+            Error::ReadInOwnInitializer { span, .. } => Some(*span),
+            Error::ReturnFromInitializer { span } => Some(*span),
+            Error::SuperOutsideClass { span } => Some(*span),
+            Error::SuperWithoutSuperclass { span } => Some(*span),
+            Error::ThisOutsideClass { span } => Some(*span),
+            Error::TooManyArguments { span, .. } => Some(*span),
+            Error::TooManyConstants { span } => Some(*span),
+            Error::TooManyLocalVariables { span } => Some(*span),
+            Error::TooManyUpvalues { span } => Some(*span),
+            Error::UnexpectedCharacter { span, .. } => Some(*span),
+            Error::UnterminatedNumber { span, .. } => Some(*span),
+            Error::UnterminatedString { span, .. } => Some(*span),
+            Error::UnmatchedParenthesis { span } => Some(*span),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::BreakOutsideLoop { .. } => {
+                write!(formatter, "Can't use 'break' outside of a loop.")
+            }
+            Error::ClassInheritsFromItself { .. } => {
+                write!(formatter, "A class can't inherit from itself.")
+            }
+            Error::ContinueOutsideLoop { .. } => {
+                write!(formatter, "Can't use 'continue' outside of a loop.")
+            }
+            Error::DuplicateDeclaration { name, .. } => {
+                write!(formatter, "'{name}' is already declared in this scope.")
+            }
+            Error::EvaluationError(error) => write!(formatter, "{}", error.kind),
+            Error::ExpectedColon { .. } => {
+                write!(formatter, "Expected ':' between a map key and its value.")
+            }
+            Error::ExpectedDot { .. } => write!(formatter, "Expected '.' after 'super'."),
+            Error::ExpectedEndOfExpression { .. } => {
+                write!(formatter, "Expected ';' at the end of the statement.")
+            }
+            Error::ExpectedExpression { .. } => write!(formatter, "Expected an expression."),
+            Error::ExpectedIdentifier { .. } => write!(formatter, "Expected an identifier."),
+            Error::ExpectedLeftBrace { .. } => write!(formatter, "Expected '{{'."),
+            Error::ExpectedLeftParen { .. } => write!(formatter, "Expected '('."),
+            Error::ExpectedRightBrace { .. } => write!(formatter, "Expected '}}'."),
+            Error::ExpectedRightParen { .. } => write!(formatter, "Expected ')'."),
+            Error::ExpectedRightBracket { .. } => write!(formatter, "Expected ']'."),
+            Error::InvalidAssignmentTarget { .. } => {
+                write!(formatter, "Invalid assignment target.")
+            }
+            Error::InvalidSyntaxTree { message } => {
+                write!(formatter, "Invalid syntax tree: {message}.")
+            }
+            Error::IoError(error) => write!(formatter, "{error}."),
+            Error::JumpTooLarge { .. } => write!(formatter, "Too much code to jump over."),
+            Error::ReadInOwnInitializer { name, .. } => {
+                write!(
+                    formatter,
+                    "Can't read local variable '{name}' in its own initializer."
+                )
+            }
+            Error::ReturnFromInitializer { .. } => {
+                write!(formatter, "Can't return a value from an initializer.")
+            }
+            Error::SuperOutsideClass { .. } => {
+                write!(formatter, "Can't use 'super' outside of a class.")
+            }
+            Error::SuperWithoutSuperclass { .. } => {
+                write!(
+                    formatter,
+                    "Can't use 'super' in a class with no superclass."
+                )
+            }
+            Error::ThisOutsideClass { .. } => {
+                write!(formatter, "Can't use 'this' outside of a class.")
+            }
+            Error::TooManyArguments { count, .. } => {
+                write!(formatter, "Too many arguments: {count}.")
+            }
+            Error::TooManyConstants { .. } => {
+                write!(formatter, "Too many constants in one function.")
+            }
+            Error::TooManyLocalVariables { .. } => {
+                write!(formatter, "Too many local variables in one function.")
+            }
+            Error::TooManyUpvalues { .. } => {
+                write!(formatter, "Too many captured variables in one function.")
+            }
+            Error::UnexpectedCharacter { character, .. } => {
+                write!(formatter, "Unexpected character '{character}'.")
+            }
+            Error::UnterminatedNumber { string, .. } => {
+                write!(formatter, "Invalid number literal '{string}'.")
+            }
+            Error::UnterminatedString { .. } => write!(formatter, "Unterminated string."),
+            Error::UnmatchedParenthesis { .. } => write!(formatter, "Unmatched parenthesis."),
+        }
+    }
+}
+
+/// Something suspicious in code that is still valid. Warnings are reported, but the code runs.
+#[derive(Debug, PartialEq)]
+pub enum Warning {
+    UnusedVariable { name: String, span: Span },
+    UnusedParameter { name: String, span: Span },
+    ShadowedVariable { name: String, span: Span },
+    UnreachableCode { span: Span },
+    TopLevelReturn { span: Span },
+}
+
+impl Warning {
+    /// A stable identifier for the kind of warning, shown in diagnostics.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Warning::UnusedVariable { .. } => "W0200",
+            Warning::UnusedParameter { .. } => "W0201",
+            Warning::ShadowedVariable { .. } => "W0202",
+            Warning::UnreachableCode { .. } => "W0203",
+            Warning::TopLevelReturn { .. } => "W0204",
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Warning::UnusedVariable { span, .. }
+            | Warning::UnusedParameter { span, .. }
+            | Warning::ShadowedVariable { span, .. }
+            | Warning::UnreachableCode { span }
+            | Warning::TopLevelReturn { span } => *span,
+        }
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::UnusedVariable { name, .. } => {
+                write!(formatter, "Local variable '{name}' is never read.")
+            }
+            Warning::UnusedParameter { name, .. } => {
+                write!(formatter, "Parameter '{name}' is never used.")
+            }
+            Warning::ShadowedVariable { name, .. } => {
+                write!(
+                    formatter,
+                    "'{name}' shadows a variable of an enclosing scope."
+                )
+            }
+            Warning::UnreachableCode { .. } => {
+                write!(formatter, "The code after this statement never runs.")
+            }
+            Warning::TopLevelReturn { .. } => {
+                write!(formatter, "'return' outside of a function ends the script.")
+            }
+        }
+    }
 }
 
 impl From<IoError> for Error {
@@ -81,7 +327,9 @@ mod tests {
 
         #[test]
         fn expected_expression() {
-            let error = Error::ExpectedExpression { position: 0 };
+            let error = Error::ExpectedExpression {
+                span: Span::default(),
+            };
             assert_eq!(error.exit_code(), exitcode::USAGE);
         }
 
@@ -92,38 +340,20 @@ mod tests {
             assert_eq!(error.exit_code(), exitcode::IOERR);
         }
 
-        #[test]
-        fn keyboard_interrupt() {
-            let error = Error::KeyboardInterrupt;
-            assert_eq!(error.exit_code(), exitcode::OK);
-        }
-
-        #[test]
-        fn out_of_line_numbers() {
-            let error = Error::OutOfLineNumbers;
-            assert_eq!(error.exit_code(), exitcode::SOFTWARE);
-        }
-
         #[test]
         fn unexpected_character() {
             let error = Error::UnexpectedCharacter {
                 character: 'a',
-                position: 0,
+                span: Span::default(),
             };
             assert_eq!(error.exit_code(), exitcode::USAGE);
         }
 
-        #[test]
-        fn unexpected_eof() {
-            let error = Error::UnexpectedEof;
-            assert_eq!(error.exit_code(), exitcode::USAGE);
-        }
-
         #[test]
         fn unterminated_number() {
             let error = Error::UnterminatedNumber {
                 string: "".to_string(),
-                position: 0,
+                span: Span::default(),
             };
             assert_eq!(error.exit_code(), exitcode::USAGE);
         }
@@ -132,25 +362,53 @@ mod tests {
         fn unterminated_string() {
             let error = Error::UnterminatedString {
                 string: "".to_string(),
-                position: 0,
+                span: Span::default(),
             };
             assert_eq!(error.exit_code(), exitcode::USAGE);
         }
 
         #[test]
         fn unmatched_parenthesis() {
-            let error = Error::UnmatchedParenthesis { position: 0 };
+            let error = Error::UnmatchedParenthesis {
+                span: Span::default(),
+            };
             assert_eq!(error.exit_code(), exitcode::USAGE);
         }
     }
 
+    mod span {
+        use super::*;
+
+        #[test]
+        fn unterminated_string() {
+            let error = Error::UnterminatedString {
+                string: "".to_string(),
+                span: Span::new(1, 4, 3, 4),
+            };
+            assert_eq!(error.span(), Some(Span::new(1, 4, 3, 4)));
+        }
+
+        #[test]
+        fn invalid_syntax_tree() {
+            let error = Error::InvalidSyntaxTree {
+                message: "".to_string(),
+            };
+            assert_eq!(error.span(), None);
+        }
+    }
+
     mod debug {
         use super::*;
 
         #[test]
         fn expected_expression() {
-            let error = Error::ExpectedExpression { position: 0 };
-            assert_eq!(format!("{:?}", error), "ExpectedExpression { position: 0 }");
+            let error = Error::ExpectedExpression {
+                span: Span::default(),
+            };
+            assert_eq!(
+                format!("{:?}", error),
+                "ExpectedExpression { span: Span { line: 0, column: 0, start: 0, end: 0 } }"
+            );
         }
 
         #[test]
@@ -163,45 +421,27 @@ mod tests {
             );
         }
 
-        #[test]
-        fn keyboard_interrupt() {
-            let error = Error::KeyboardInterrupt;
-            assert_eq!(format!("{:?}", error), "KeyboardInterrupt");
-        }
-
-        #[test]
-        fn out_of_line_numbers() {
-            let error = Error::OutOfLineNumbers;
-            assert_eq!(format!("{:?}", error), "OutOfLineNumbers");
-        }
-
         #[test]
         fn unexpected_character() {
             let error = Error::UnexpectedCharacter {
                 character: 'a',
-                position: 0,
+                span: Span::default(),
             };
             assert_eq!(
                 format!("{:?}", error),
-                "UnexpectedCharacter { character: 'a', position: 0 }"
+                "UnexpectedCharacter { character: 'a', span: Span { line: 0, column: 0, start: 0, end: 0 } }"
             );
         }
 
-        #[test]
-        fn unexpected_eof() {
-            let error = Error::UnexpectedEof;
-            assert_eq!(format!("{:?}", error), "UnexpectedEof");
-        }
-
         #[test]
         fn unterminated_number() {
             let error = Error::UnterminatedNumber {
                 string: "".to_string(),
-                position: 0,
+                span: Span::default(),
             };
             assert_eq!(
                 format!("{:?}", error),
-                "UnterminatedNumber { string: \"\", position: 0 }"
+                "UnterminatedNumber { string: \"\", span: Span { line: 0, column: 0, start: 0, end: 0 } }"
             );
         }
 
@@ -209,21 +449,77 @@ mod tests {
         fn unterminated_string() {
             let error = Error::UnterminatedString {
                 string: "".to_string(),
-                position: 0,
+                span: Span::default(),
             };
             assert_eq!(
                 format!("{:?}", error),
-                "UnterminatedString { string: \"\", position: 0 }"
+                "UnterminatedString { string: \"\", span: Span { line: 0, column: 0, start: 0, end: 0 } }"
             );
         }
 
         #[test]
         fn unmatched_parenthesis() {
-            let error = Error::UnmatchedParenthesis { position: 0 };
+            let error = Error::UnmatchedParenthesis {
+                span: Span::default(),
+            };
             assert_eq!(
                 format!("{:?}", error),
-                "UnmatchedParenthesis { position: 0 }"
+                "UnmatchedParenthesis { span: Span { line: 0, column: 0, start: 0, end: 0 } }"
             );
         }
     }
+    mod display {
+        use super::*;
+
+        #[test]
+        fn unexpected_character() {
+            let error = Error::UnexpectedCharacter {
+                character: '@',
+                span: Span::default(),
+            };
+            assert_eq!(error.to_string(), "Unexpected character '@'.");
+        }
+
+        #[test]
+        fn too_many_arguments() {
+            let error = Error::TooManyArguments {
+                count: 256,
+                span: Span::default(),
+            };
+            assert_eq!(error.to_string(), "Too many arguments: 256.");
+        }
+
+        #[test]
+        fn evaluation_error() {
+            let error = Error::EvaluationError(ObjectError::new(
+                crate::object::ErrorKind::UndefinedVariable {
+                    name: "a".to_string(),
+                },
+                Span::default(),
+            ));
+            assert_eq!(error.to_string(), "Undefined variable 'a'.");
+        }
+    }
+
+    mod code {
+        use super::*;
+
+        #[test]
+        fn scanner_error() {
+            let error = Error::UnterminatedString {
+                string: "".to_string(),
+                span: Span::default(),
+            };
+            assert_eq!(error.code(), "E0002");
+        }
+
+        #[test]
+        fn evaluation_error() {
+            let error = Error::EvaluationError(ObjectError::new(
+                crate::object::ErrorKind::DivisionByZero,
+                Span::default(),
+            ));
+            assert_eq!(error.code(), "E0408");
+        }
+    }
 }
@@ -1,145 +1,227 @@
 use crate::result::*;
 use crate::token::*;
 
+/// Tokens, and the comments and blank lines between them.
+pub type TokensAndTrivia = (Vec<Token>, Vec<(Trivia, Span)>);
+
 pub struct Scanner<'a> {
-    _line_number: usize,
-    chars: std::iter::Peekable<std::iter::Enumerate<std::str::Chars<'a>>>,
+    source: &'a str,
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    line: usize,
+    column: usize,
+    /// Comments and blank lines, when they are kept.
+    trivia: Option<Vec<(Trivia, Span)>>,
+    /// Whether the current line has had only whitespace so far.
+    line_is_blank: bool,
 }
 
 impl Scanner<'_> {
-    pub fn new(source: &str, _line_number: usize) -> Scanner {
+    pub fn new(source: &str, line_number: usize) -> Scanner<'_> {
         Scanner {
-            _line_number,
-            chars: source.chars().enumerate().peekable(),
+            source,
+            chars: source.char_indices().peekable(),
+            line: line_number,
+            column: 1,
+            trivia: None,
+            line_is_blank: true,
         }
     }
     pub fn scan_tokens(mut self) -> Result<Vec<Token>, Error> {
+        self.scan_all()
+    }
+    /// Scans the tokens and also keeps the comments and blank lines between them, in the order
+    /// they appear.
+    pub fn scan_tokens_and_trivia(mut self) -> Result<TokensAndTrivia, Error> {
+        self.trivia = Some(Vec::new());
+        let tokens = self.scan_all()?;
+        Ok((tokens, self.trivia.unwrap_or_default()))
+    }
+    fn scan_all(&mut self) -> Result<Vec<Token>, Error> {
         let mut tokens = Vec::new();
         while let Some(token) = self.scan_token() {
             tokens.push(token?);
         }
+        let end = self.source.len();
         tokens.push(Token {
             token_type: TokenType::EOF,
-            start: 0,
+            span: Span::new(self.line, self.column, end, end),
         });
         Ok(tokens)
     }
     fn scan_token(&mut self) -> Option<Result<Token, Error>> {
-        let (start, character) = self.chars.next()?;
-        let token_type = match character {
-            '(' => TokenType::LeftParen,
-            ')' => TokenType::RightParen,
-            '{' => TokenType::LeftBrace,
-            '}' => TokenType::RightBrace,
-            ',' => TokenType::Comma,
-            '.' => TokenType::Dot,
-            '-' => TokenType::Minus,
-            '+' => TokenType::Plus,
-            ';' => TokenType::Semicolon,
-            '*' => TokenType::Star,
-            '!' => {
-                if self.advance_if_matches('=') {
-                    TokenType::BangEqual
-                } else {
-                    TokenType::Bang
+        // Whitespace and comments are skipped in a loop, since a file can have any number of them.
+        loop {
+            let (line, column) = (self.line, self.column);
+            let (start, character) = self.advance()?;
+            let token_type = match character {
+                '(' => TokenType::LeftParen,
+                ')' => TokenType::RightParen,
+                '{' => TokenType::LeftBrace,
+                '}' => TokenType::RightBrace,
+                '[' => TokenType::LeftBracket,
+                ']' => TokenType::RightBracket,
+                ':' => TokenType::Colon,
+                ',' => TokenType::Comma,
+                '.' => TokenType::Dot,
+                '-' => TokenType::Minus,
+                '+' => TokenType::Plus,
+                ';' => TokenType::Semicolon,
+                '*' => TokenType::Star,
+                '!' => {
+                    if self.advance_if_matches('=') {
+                        TokenType::BangEqual
+                    } else {
+                        TokenType::Bang
+                    }
                 }
-            }
-            '=' => {
-                if self.advance_if_matches('=') {
-                    TokenType::EqualEqual
-                } else {
-                    TokenType::Equal
+                '=' => {
+                    if self.advance_if_matches('=') {
+                        TokenType::EqualEqual
+                    } else {
+                        TokenType::Equal
+                    }
                 }
-            }
-            '<' => {
-                if self.advance_if_matches('=') {
-                    TokenType::LessEqual
-                } else {
-                    TokenType::Less
+                '<' => {
+                    if self.advance_if_matches('=') {
+                        TokenType::LessEqual
+                    } else {
+                        TokenType::Less
+                    }
                 }
-            }
-            '>' => {
-                if self.advance_if_matches('=') {
-                    TokenType::GreaterEqual
-                } else {
-                    TokenType::Greater
+                '>' => {
+                    if self.advance_if_matches('=') {
+                        TokenType::GreaterEqual
+                    } else {
+                        TokenType::Greater
+                    }
                 }
-            }
-            '/' => {
-                if self.advance_if_matches('/') {
-                    while self.chars.next().is_some() {}
-                    return None;
-                } else {
-                    TokenType::Slash
+                '/' => {
+                    if self.advance_if_matches('/') {
+                        while let Some((_, character)) = self.chars.peek() {
+                            if *character == '\n' {
+                                break;
+                            }
+                            self.advance();
+                        }
+                        let span = self.span_from(line, column, start);
+                        let text = self.source[start + 2..span.end].trim_end().to_string();
+                        if self.line_is_blank {
+                            self.keep_trivia(Trivia::Comment(text), span);
+                        } else {
+                            self.keep_trivia(Trivia::TrailingComment(text), span);
+                        }
+                        self.line_is_blank = false;
+                        continue;
+                    } else {
+                        TokenType::Slash
+                    }
                 }
-            }
-            ' ' | '\r' | '\t' | '\n' => {
-                return self.scan_token();
-            }
-            '"' => match self.scan_string() {
-                Ok(string) => TokenType::String(string),
-                Err(string) => {
-                    return Some(Err(Error::UnterminatedString {
-                        string,
-                        position: start,
-                    }));
+                '\n' => {
+                    if self.line_is_blank {
+                        let span = self.span_from(line, column, start);
+                        self.keep_trivia(Trivia::BlankLine, span);
+                    }
+                    self.line_is_blank = true;
+                    continue;
                 }
-            },
-            '0'..='9' => match self.scan_number(character) {
-                Ok((value, length)) => TokenType::Number { value, length },
-                Err(string) => {
-                    return Some(Err(Error::UnterminatedNumber {
-                        string,
-                        position: start,
-                    }));
+                ' ' | '\r' | '\t' => {
+                    continue;
                 }
-            },
-            'a'..='z' | 'A'..='Z' | '_' => {
-                let identifier_or_keyword = self.scan_identifier_or_keyword(character);
-                if identifier_or_keyword == AND_KEYWORD {
-                    TokenType::And
-                } else if identifier_or_keyword == CLASS_KEYWORD {
-                    TokenType::Class
-                } else if identifier_or_keyword == ELSE_KEYWORD {
-                    TokenType::Else
-                } else if identifier_or_keyword == FALSE_KEYWORD {
-                    TokenType::False
-                } else if identifier_or_keyword == FUN_KEYWORD {
-                    TokenType::Fun
-                } else if identifier_or_keyword == FOR_KEYWORD {
-                    TokenType::For
-                } else if identifier_or_keyword == IF_KEYWORD {
-                    TokenType::If
-                } else if identifier_or_keyword == NIL_KEYWORD {
-                    TokenType::Nil
-                } else if identifier_or_keyword == OR_KEYWORD {
-                    TokenType::Or
-                } else if identifier_or_keyword == PRINT_KEYWORD {
-                    TokenType::Print
-                } else if identifier_or_keyword == RETURN_KEYWORD {
-                    TokenType::Return
-                } else if identifier_or_keyword == SUPER_KEYWORD {
-                    TokenType::Super
-                } else if identifier_or_keyword == THIS_KEYWORD {
-                    TokenType::This
-                } else if identifier_or_keyword == TRUE_KEYWORD {
-                    TokenType::True
-                } else if identifier_or_keyword == VAR_KEYWORD {
-                    TokenType::Var
-                } else if identifier_or_keyword == WHILE_KEYWORD {
-                    TokenType::While
-                } else {
-                    TokenType::Identifier(identifier_or_keyword)
+                '"' => match self.scan_string() {
+                    Ok(string) => TokenType::String(string),
+                    Err(string) => {
+                        return Some(Err(Error::UnterminatedString {
+                            string,
+                            span: self.span_from(line, column, start),
+                        }));
+                    }
+                },
+                '0'..='9' => match self.scan_number(character) {
+                    Ok((value, length)) => TokenType::Number { value, length },
+                    Err(string) => {
+                        return Some(Err(Error::UnterminatedNumber {
+                            string,
+                            span: self.span_from(line, column, start),
+                        }));
+                    }
+                },
+                'a'..='z' | 'A'..='Z' | '_' => {
+                    let identifier_or_keyword = self.scan_identifier_or_keyword(character);
+                    if identifier_or_keyword == AND_KEYWORD {
+                        TokenType::And
+                    } else if identifier_or_keyword == BREAK_KEYWORD {
+                        TokenType::Break
+                    } else if identifier_or_keyword == CLASS_KEYWORD {
+                        TokenType::Class
+                    } else if identifier_or_keyword == CONTINUE_KEYWORD {
+                        TokenType::Continue
+                    } else if identifier_or_keyword == ELSE_KEYWORD {
+                        TokenType::Else
+                    } else if identifier_or_keyword == FALSE_KEYWORD {
+                        TokenType::False
+                    } else if identifier_or_keyword == FUN_KEYWORD {
+                        TokenType::Fun
+                    } else if identifier_or_keyword == FOR_KEYWORD {
+                        TokenType::For
+                    } else if identifier_or_keyword == IF_KEYWORD {
+                        TokenType::If
+                    } else if identifier_or_keyword == NIL_KEYWORD {
+                        TokenType::Nil
+                    } else if identifier_or_keyword == OR_KEYWORD {
+                        TokenType::Or
+                    } else if identifier_or_keyword == PRINT_KEYWORD {
+                        TokenType::Print
+                    } else if identifier_or_keyword == RETURN_KEYWORD {
+                        TokenType::Return
+                    } else if identifier_or_keyword == SUPER_KEYWORD {
+                        TokenType::Super
+                    } else if identifier_or_keyword == THIS_KEYWORD {
+                        TokenType::This
+                    } else if identifier_or_keyword == TRUE_KEYWORD {
+                        TokenType::True
+                    } else if identifier_or_keyword == VAR_KEYWORD {
+                        TokenType::Var
+                    } else if identifier_or_keyword == WHILE_KEYWORD {
+                        TokenType::While
+                    } else {
+                        TokenType::Identifier(identifier_or_keyword)
+                    }
                 }
-            }
-            _ => {
-                return Some(Err(Error::UnexpectedCharacter {
-                    character,
-                    position: start,
-                }));
-            }
-        };
-        Some(Ok(Token { token_type, start }))
+                _ => {
+                    return Some(Err(Error::UnexpectedCharacter {
+                        character,
+                        span: self.span_from(line, column, start),
+                    }));
+                }
+            };
+            let span = self.span_from(line, column, start);
+            self.line_is_blank = false;
+            return Some(Ok(Token { token_type, span }));
+        }
+    }
+    fn keep_trivia(&mut self, trivia: Trivia, span: Span) {
+        if let Some(kept) = &mut self.trivia {
+            kept.push((trivia, span));
+        }
+    }
+    fn advance(&mut self) -> Option<(usize, char)> {
+        let (index, character) = self.chars.next()?;
+        if character == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some((index, character))
+    }
+    fn offset(&mut self) -> usize {
+        match self.chars.peek() {
+            Some((index, _)) => *index,
+            None => self.source.len(),
+        }
+    }
+    fn span_from(&mut self, line: usize, column: usize, start: usize) -> Span {
+        Span::new(line, column, start, self.offset())
     }
     fn advance_if_matches(&mut self, expected_next: char) -> bool {
         let Some((_, next)) = self.chars.peek() else {
@@ -148,12 +230,12 @@ impl Scanner<'_> {
         if next != &expected_next {
             return false;
         }
-        self.chars.next();
+        self.advance();
         true
     }
     fn scan_string(&mut self) -> Result<String, String> {
         let mut value = String::new();
-        for (_, character) in self.chars.by_ref() {
+        while let Some((_, character)) = self.advance() {
             if character == '"' {
                 return Ok(value);
             }
@@ -167,16 +249,14 @@ impl Scanner<'_> {
         let integer_part = self.scan_integer().map_err(|err| format!("{value}{err}"))?;
         value += &integer_part;
 
-        if let Some((_, character)) = self.chars.peek() {
-            if *character == '.' {
-                value.push(*character);
-                self.chars.next();
-                let fractional_part = self.scan_integer().map_err(|err| format!("{value}{err}"))?;
-                if fractional_part.is_empty() {
-                    return Err(value);
-                }
-                value += &fractional_part;
+        if let Some((_, '.')) = self.chars.peek() {
+            value.push('.');
+            self.advance();
+            let fractional_part = self.scan_integer().map_err(|err| format!("{value}{err}"))?;
+            if fractional_part.is_empty() {
+                return Err(value);
             }
+            value += &fractional_part;
         }
         Ok((value.parse().unwrap(), value.len()))
     }
@@ -186,13 +266,13 @@ impl Scanner<'_> {
             match character {
                 '0'..='9' => {
                     value.push(*character);
-                    self.chars.next();
+                    self.advance();
                 }
-                '.' | ' ' | '\r' | '\t' | '\n' => {
-                    break;
+                'a'..='z' | 'A'..='Z' | '_' => {
+                    return Err(value);
                 }
                 _ => {
-                    return Err(value);
+                    break;
                 }
             }
         }
@@ -204,7 +284,7 @@ impl Scanner<'_> {
             match character {
                 'a'..='z' | 'A'..='Z' | '_' | '0'..='9' => {
                     value.push(*character);
-                    self.chars.next();
+                    self.advance();
                 }
                 _ => {
                     break;
@@ -221,93 +301,135 @@ mod tests {
 
     #[test]
     fn scan_empty_line() {
-        let tokens = Scanner::new("", 0).scan_tokens();
+        let tokens = Scanner::new("", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![Token {
                 token_type: TokenType::EOF,
-                start: 0
+                span: Span::new(1, 1, 0, 0)
             }]
         );
     }
 
     #[test]
     fn scan_whitespace_line() {
-        let tokens = Scanner::new("\t ", 0).scan_tokens();
+        let tokens = Scanner::new("\t ", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![Token {
                 token_type: TokenType::EOF,
-                start: 0
+                span: Span::new(1, 3, 2, 2)
             }]
         );
     }
 
     #[test]
     fn scan_comment_line() {
-        let tokens = Scanner::new("\t//comment", 0).scan_tokens();
+        let tokens = Scanner::new("\t//comment", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![Token {
                 token_type: TokenType::EOF,
-                start: 0
+                span: Span::new(1, 11, 10, 10)
             }]
         );
     }
 
+    #[test]
+    fn scan_comment_stops_at_end_of_line() {
+        let tokens = Scanner::new("//comment\n;", 1).scan_tokens();
+        assert!(tokens.is_ok());
+        assert_eq!(
+            tokens.unwrap(),
+            vec![
+                Token {
+                    token_type: TokenType::Semicolon,
+                    span: Span::new(2, 1, 10, 11)
+                },
+                Token {
+                    token_type: TokenType::EOF,
+                    span: Span::new(2, 2, 11, 11)
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_tracks_lines_and_columns() {
+        let tokens = Scanner::new("\"ü\"\n  x", 1).scan_tokens();
+        assert!(tokens.is_ok());
+        assert_eq!(
+            tokens.unwrap(),
+            vec![
+                Token {
+                    token_type: TokenType::String("ü".to_string()),
+                    span: Span::new(1, 1, 0, 4)
+                },
+                Token {
+                    token_type: TokenType::Identifier("x".to_string()),
+                    span: Span::new(2, 3, 7, 8)
+                },
+                Token {
+                    token_type: TokenType::EOF,
+                    span: Span::new(2, 4, 8, 8)
+                }
+            ]
+        );
+    }
+
     #[test]
     fn scan_single_character_tokens() {
-        let tokens = Scanner::new("(}{,+).-;*", 0).scan_tokens();
+        let tokens = Scanner::new("(}{,+).-;*", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::LeftParen,
-                    start: 0
+                    span: Span::new(1, 1, 0, 1)
                 },
                 Token {
                     token_type: TokenType::RightBrace,
-                    start: 1
+                    span: Span::new(1, 2, 1, 2)
                 },
                 Token {
                     token_type: TokenType::LeftBrace,
-                    start: 2
+                    span: Span::new(1, 3, 2, 3)
                 },
                 Token {
                     token_type: TokenType::Comma,
-                    start: 3
+                    span: Span::new(1, 4, 3, 4)
                 },
                 Token {
                     token_type: TokenType::Plus,
-                    start: 4
+                    span: Span::new(1, 5, 4, 5)
                 },
                 Token {
                     token_type: TokenType::RightParen,
-                    start: 5
+                    span: Span::new(1, 6, 5, 6)
                 },
                 Token {
                     token_type: TokenType::Dot,
-                    start: 6
+                    span: Span::new(1, 7, 6, 7)
                 },
                 Token {
                     token_type: TokenType::Minus,
-                    start: 7
+                    span: Span::new(1, 8, 7, 8)
                 },
                 Token {
                     token_type: TokenType::Semicolon,
-                    start: 8
+                    span: Span::new(1, 9, 8, 9)
                 },
                 Token {
                     token_type: TokenType::Star,
-                    start: 9
+                    span: Span::new(1, 10, 9, 10)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 11, 10, 10)
                 }
             ]
         );
@@ -315,58 +437,58 @@ mod tests {
 
     #[test]
     fn scan_single_or_double_character_tokens() {
-        let tokens = Scanner::new("!(!= = >=< = <=> == /", 0).scan_tokens();
+        let tokens = Scanner::new("!(!= = >=< = <=> == /", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Bang,
-                    start: 0
+                    span: Span::new(1, 1, 0, 1)
                 },
                 Token {
                     token_type: TokenType::LeftParen,
-                    start: 1
+                    span: Span::new(1, 2, 1, 2)
                 },
                 Token {
                     token_type: TokenType::BangEqual,
-                    start: 2
+                    span: Span::new(1, 3, 2, 4)
                 },
                 Token {
                     token_type: TokenType::Equal,
-                    start: 5
+                    span: Span::new(1, 6, 5, 6)
                 },
                 Token {
                     token_type: TokenType::GreaterEqual,
-                    start: 7
+                    span: Span::new(1, 8, 7, 9)
                 },
                 Token {
                     token_type: TokenType::Less,
-                    start: 9
+                    span: Span::new(1, 10, 9, 10)
                 },
                 Token {
                     token_type: TokenType::Equal,
-                    start: 11
+                    span: Span::new(1, 12, 11, 12)
                 },
                 Token {
                     token_type: TokenType::LessEqual,
-                    start: 13
+                    span: Span::new(1, 14, 13, 15)
                 },
                 Token {
                     token_type: TokenType::Greater,
-                    start: 15
+                    span: Span::new(1, 16, 15, 16)
                 },
                 Token {
                     token_type: TokenType::EqualEqual,
-                    start: 17
+                    span: Span::new(1, 18, 17, 19)
                 },
                 Token {
                     token_type: TokenType::Slash,
-                    start: 20
+                    span: Span::new(1, 21, 20, 21)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 22, 21, 21)
                 }
             ]
         );
@@ -374,18 +496,18 @@ mod tests {
 
     #[test]
     fn scan_string() {
-        let tokens = Scanner::new("\"my string\"", 0).scan_tokens();
+        let tokens = Scanner::new("\"my string\"", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::String("my string".to_string()),
-                    start: 0
+                    span: Span::new(1, 1, 0, 11)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 12, 11, 11)
                 }
             ]
         );
@@ -393,7 +515,7 @@ mod tests {
 
     #[test]
     fn scan_unterminated_string() {
-        let tokens = Scanner::new("\"my unterminated string", 0).scan_tokens();
+        let tokens = Scanner::new("\"my unterminated string", 1).scan_tokens();
         assert!(tokens.is_err());
         assert!(matches!(
             tokens.unwrap_err(),
@@ -403,7 +525,7 @@ mod tests {
 
     #[test]
     fn scan_integer() {
-        let tokens = Scanner::new("123", 0).scan_tokens();
+        let tokens = Scanner::new("123", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
@@ -413,11 +535,11 @@ mod tests {
                         value: 123.0,
                         length: 3
                     },
-                    start: 0
+                    span: Span::new(1, 1, 0, 3)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 4, 3, 3)
                 }
             ]
         );
@@ -425,18 +547,18 @@ mod tests {
 
     #[test]
     fn scan_integer_with_error() {
-        let tokens = Scanner::new("123banana", 0).scan_tokens();
+        let tokens = Scanner::new("123banana", 1).scan_tokens();
         assert!(tokens.is_err());
-        let Error::UnterminatedNumber { string, position } = tokens.unwrap_err() else {
+        let Error::UnterminatedNumber { string, span } = tokens.unwrap_err() else {
             panic!();
         };
         assert_eq!(string, "123".to_string());
-        assert_eq!(position, 0);
+        assert_eq!(span, Span::new(1, 1, 0, 3));
     }
 
     #[test]
     fn scan_decimal() {
-        let tokens = Scanner::new("123.0", 0).scan_tokens();
+        let tokens = Scanner::new("123.0", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
@@ -446,11 +568,11 @@ mod tests {
                         value: 123.0,
                         length: 5
                     },
-                    start: 0
+                    span: Span::new(1, 1, 0, 5)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 6, 5, 5)
                 }
             ]
         );
@@ -458,7 +580,7 @@ mod tests {
 
     #[test]
     fn scan_unterminated_number() {
-        let tokens = Scanner::new("123.", 0).scan_tokens();
+        let tokens = Scanner::new("123.", 1).scan_tokens();
         assert!(tokens.is_err());
         assert!(matches!(
             tokens.unwrap_err(),
@@ -466,31 +588,57 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn scan_number_followed_by_semicolon() {
+        let tokens = Scanner::new("42;", 1).scan_tokens();
+        assert!(tokens.is_ok());
+        assert_eq!(
+            tokens.unwrap(),
+            vec![
+                Token {
+                    token_type: TokenType::Number {
+                        value: 42.0,
+                        length: 2
+                    },
+                    span: Span::new(1, 1, 0, 2)
+                },
+                Token {
+                    token_type: TokenType::Semicolon,
+                    span: Span::new(1, 3, 2, 3)
+                },
+                Token {
+                    token_type: TokenType::EOF,
+                    span: Span::new(1, 4, 3, 3)
+                }
+            ]
+        );
+    }
+
     #[test]
     fn scan_decimal_with_error() {
-        let tokens = Scanner::new("42.O", 0).scan_tokens();
+        let tokens = Scanner::new("42.O", 1).scan_tokens();
         assert!(tokens.is_err());
-        let Error::UnterminatedNumber { string, position } = tokens.unwrap_err() else {
+        let Error::UnterminatedNumber { string, span } = tokens.unwrap_err() else {
             panic!();
         };
         assert_eq!(string, "42.".to_string());
-        assert_eq!(position, 0);
+        assert_eq!(span, Span::new(1, 1, 0, 3));
     }
 
     #[test]
     fn scan_keyword_and() {
-        let tokens = Scanner::new("and", 0).scan_tokens();
+        let tokens = Scanner::new("and", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::And,
-                    start: 0
+                    span: Span::new(1, 1, 0, 3)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 4, 3, 3)
                 }
             ]
         );
@@ -498,18 +646,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_class() {
-        let tokens = Scanner::new("class", 0).scan_tokens();
+        let tokens = Scanner::new("class", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Class,
-                    start: 0
+                    span: Span::new(1, 1, 0, 5)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 6, 5, 5)
                 }
             ]
         );
@@ -517,18 +665,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_else() {
-        let tokens = Scanner::new("else", 0).scan_tokens();
+        let tokens = Scanner::new("else", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Else,
-                    start: 0
+                    span: Span::new(1, 1, 0, 4)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 5, 4, 4)
                 }
             ]
         );
@@ -536,18 +684,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_false() {
-        let tokens = Scanner::new("false", 0).scan_tokens();
+        let tokens = Scanner::new("false", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::False,
-                    start: 0
+                    span: Span::new(1, 1, 0, 5)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 6, 5, 5)
                 }
             ]
         );
@@ -555,18 +703,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_fun() {
-        let tokens = Scanner::new("fun", 0).scan_tokens();
+        let tokens = Scanner::new("fun", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Fun,
-                    start: 0
+                    span: Span::new(1, 1, 0, 3)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 4, 3, 3)
                 }
             ]
         );
@@ -574,18 +722,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_for() {
-        let tokens = Scanner::new("for", 0).scan_tokens();
+        let tokens = Scanner::new("for", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::For,
-                    start: 0
+                    span: Span::new(1, 1, 0, 3)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 4, 3, 3)
                 }
             ]
         );
@@ -593,18 +741,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_if() {
-        let tokens = Scanner::new("if", 0).scan_tokens();
+        let tokens = Scanner::new("if", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::If,
-                    start: 0
+                    span: Span::new(1, 1, 0, 2)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 3, 2, 2)
                 }
             ]
         );
@@ -612,18 +760,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_nil() {
-        let tokens = Scanner::new("nil", 0).scan_tokens();
+        let tokens = Scanner::new("nil", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Nil,
-                    start: 0
+                    span: Span::new(1, 1, 0, 3)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 4, 3, 3)
                 }
             ]
         );
@@ -631,18 +779,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_or() {
-        let tokens = Scanner::new("or", 0).scan_tokens();
+        let tokens = Scanner::new("or", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Or,
-                    start: 0
+                    span: Span::new(1, 1, 0, 2)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 3, 2, 2)
                 }
             ]
         );
@@ -650,18 +798,41 @@ mod tests {
 
     #[test]
     fn scan_keyword_print() {
-        let tokens = Scanner::new("print", 0).scan_tokens();
+        let tokens = Scanner::new("print", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Print,
-                    start: 0
+                    span: Span::new(1, 1, 0, 5)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 6, 5, 5)
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_keywords_break_and_continue() {
+        let tokens = Scanner::new("break continue", 1).scan_tokens();
+        assert!(tokens.is_ok());
+        assert_eq!(
+            tokens.unwrap(),
+            vec![
+                Token {
+                    token_type: TokenType::Break,
+                    span: Span::new(1, 1, 0, 5)
+                },
+                Token {
+                    token_type: TokenType::Continue,
+                    span: Span::new(1, 7, 6, 14)
+                },
+                Token {
+                    token_type: TokenType::EOF,
+                    span: Span::new(1, 15, 14, 14)
                 }
             ]
         );
@@ -669,18 +840,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_return() {
-        let tokens = Scanner::new("return", 0).scan_tokens();
+        let tokens = Scanner::new("return", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Return,
-                    start: 0
+                    span: Span::new(1, 1, 0, 6)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 7, 6, 6)
                 }
             ]
         );
@@ -688,18 +859,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_super() {
-        let tokens = Scanner::new("super", 0).scan_tokens();
+        let tokens = Scanner::new("super", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Super,
-                    start: 0
+                    span: Span::new(1, 1, 0, 5)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 6, 5, 5)
                 }
             ]
         );
@@ -707,18 +878,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_this() {
-        let tokens = Scanner::new("this", 0).scan_tokens();
+        let tokens = Scanner::new("this", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::This,
-                    start: 0
+                    span: Span::new(1, 1, 0, 4)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 5, 4, 4)
                 }
             ]
         );
@@ -726,18 +897,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_true() {
-        let tokens = Scanner::new("true", 0).scan_tokens();
+        let tokens = Scanner::new("true", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::True,
-                    start: 0
+                    span: Span::new(1, 1, 0, 4)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 5, 4, 4)
                 }
             ]
         );
@@ -745,18 +916,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_var() {
-        let tokens = Scanner::new("var", 0).scan_tokens();
+        let tokens = Scanner::new("var", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Var,
-                    start: 0
+                    span: Span::new(1, 1, 0, 3)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 4, 3, 3)
                 }
             ]
         );
@@ -764,18 +935,18 @@ mod tests {
 
     #[test]
     fn scan_keyword_while() {
-        let tokens = Scanner::new("while", 0).scan_tokens();
+        let tokens = Scanner::new("while", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::While,
-                    start: 0
+                    span: Span::new(1, 1, 0, 5)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 6, 5, 5)
                 }
             ]
         );
@@ -783,18 +954,18 @@ mod tests {
 
     #[test]
     fn scan_identifier() {
-        let tokens = Scanner::new("myvariable", 0).scan_tokens();
+        let tokens = Scanner::new("myvariable", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Identifier("myvariable".to_string()),
-                    start: 0
+                    span: Span::new(1, 1, 0, 10)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 11, 10, 10)
                 }
             ]
         );
@@ -802,18 +973,18 @@ mod tests {
 
     #[test]
     fn scan_identifier_with_underscores() {
-        let tokens = Scanner::new("__my_var__iable_", 0).scan_tokens();
+        let tokens = Scanner::new("__my_var__iable_", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Identifier("__my_var__iable_".to_string()),
-                    start: 0
+                    span: Span::new(1, 1, 0, 16)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 17, 16, 16)
                 }
             ]
         );
@@ -821,18 +992,18 @@ mod tests {
 
     #[test]
     fn scan_identifier_with_uppercase_letters() {
-        let tokens = Scanner::new("MyVariable", 0).scan_tokens();
+        let tokens = Scanner::new("MyVariable", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Identifier("MyVariable".to_string()),
-                    start: 0
+                    span: Span::new(1, 1, 0, 10)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 11, 10, 10)
                 }
             ]
         );
@@ -840,18 +1011,18 @@ mod tests {
 
     #[test]
     fn scan_identifier_with_number() {
-        let tokens = Scanner::new("my1variable", 0).scan_tokens();
+        let tokens = Scanner::new("my1variable", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Identifier("my1variable".to_string()),
-                    start: 0
+                    span: Span::new(1, 1, 0, 11)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 12, 11, 11)
                 }
             ]
         );
@@ -859,31 +1030,85 @@ mod tests {
 
     #[test]
     fn scan_identifier_starting_with_keyword() {
-        let tokens = Scanner::new("whileforandorvariable", 0).scan_tokens();
+        let tokens = Scanner::new("whileforandorvariable", 1).scan_tokens();
         assert!(tokens.is_ok());
         assert_eq!(
             tokens.unwrap(),
             vec![
                 Token {
                     token_type: TokenType::Identifier("whileforandorvariable".to_string()),
-                    start: 0
+                    span: Span::new(1, 1, 0, 21)
                 },
                 Token {
                     token_type: TokenType::EOF,
-                    start: 0
+                    span: Span::new(1, 22, 21, 21)
                 }
             ]
         );
     }
 
+    #[test]
+    fn scan_many_comment_lines() {
+        let source = "// comment\n".repeat(100_000) + "print";
+        let tokens = Scanner::new(&source, 1).scan_tokens().unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::Print);
+        assert_eq!(tokens[0].span.line, 100_001);
+    }
+
     #[test]
     fn scan_disallowed_character() {
-        let tokens = Scanner::new("[", 0).scan_tokens();
+        let tokens = Scanner::new("@", 1).scan_tokens();
         assert!(tokens.is_err());
-        let Error::UnexpectedCharacter { character, position } = tokens.unwrap_err() else {
+        let Error::UnexpectedCharacter { character, span } = tokens.unwrap_err() else {
             panic!();
         };
-        assert_eq!(character, '[');
-        assert_eq!(position, 0);
+        assert_eq!(character, '@');
+        assert_eq!(span, Span::new(1, 1, 0, 1));
+    }
+
+    #[test]
+    fn comments_are_only_kept_when_asked_for() {
+        let source = "// first\nprint 1; // second\n";
+        let tokens = Scanner::new(source, 1).scan_tokens().unwrap();
+        let (tokens_and_trivia, trivia) = Scanner::new(source, 1).scan_tokens_and_trivia().unwrap();
+        assert_eq!(tokens, tokens_and_trivia);
+        assert_eq!(
+            trivia,
+            vec![
+                (Trivia::Comment(" first".to_string()), Span::new(1, 1, 0, 8)),
+                (
+                    Trivia::TrailingComment(" second".to_string()),
+                    Span::new(2, 10, 18, 27)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_with_only_whitespace_are_blank() {
+        let (_, trivia) = Scanner::new("a;\n\n  \t\nb; // c\n\n", 1)
+            .scan_tokens_and_trivia()
+            .unwrap();
+        let trivia = trivia
+            .into_iter()
+            .map(|(trivia, _)| trivia)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trivia,
+            vec![
+                Trivia::BlankLine,
+                Trivia::BlankLine,
+                Trivia::TrailingComment(" c".to_string()),
+                Trivia::BlankLine,
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_lines_in_strings_are_not_blank() {
+        let (_, trivia) = Scanner::new("print \"a\n\nb\";\n", 1)
+            .scan_tokens_and_trivia()
+            .unwrap();
+        assert!(trivia.is_empty());
     }
 }
@@ -1,30 +1,81 @@
 use crate::expression::Expression;
+use crate::token::Span;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    #[serde(default)]
+    pub span: Span,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct FunctionDeclaration {
     pub identifier: String,
-    pub parameters: Vec<String>,
+    pub parameters: Vec<Parameter>,
     pub body: Box<Statement>,
+    /// Location of the function's name.
+    #[serde(default)]
+    pub span: Span,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ClassDeclaration {
+    pub identifier: String,
+    pub superclass: Option<Expression>,
+    pub methods: Vec<FunctionDeclaration>,
+    #[serde(default)]
+    pub span: Span,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Statement {
+    #[serde(rename = "expression")]
     Expression(Expression),
+    #[serde(rename = "if")]
     If {
         condition: Expression,
         then_statement: Box<Statement>,
         else_statement: Option<Box<Statement>>,
     },
+    #[serde(rename = "print")]
     Print(Expression),
-    Return(Option<Expression>),
+    #[serde(rename = "return")]
+    Return {
+        expression: Option<Expression>,
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "variable_declaration")]
     VariableDeclaration {
         identifier: String,
         expression: Option<Expression>,
+        /// Location of the variable's name.
+        #[serde(default)]
+        span: Span,
     },
+    #[serde(rename = "function_declaration")]
     FunctionDeclaration(FunctionDeclaration),
+    #[serde(rename = "class_declaration")]
+    ClassDeclaration(ClassDeclaration),
+    /// A `while` loop, or a `for` loop once its initializer is moved out. The increment of a
+    /// `for` loop runs after the body, even when the body ends with `continue`.
+    #[serde(rename = "while")]
     While {
         expression: Expression,
         statement: Box<Statement>,
+        increment: Option<Expression>,
+    },
+    #[serde(rename = "break")]
+    Break {
+        #[serde(default)]
+        span: Span,
+    },
+    #[serde(rename = "continue")]
+    Continue {
+        #[serde(default)]
+        span: Span,
     },
+    #[serde(rename = "block")]
     Block(Vec<Statement>),
 }
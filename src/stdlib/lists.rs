@@ -0,0 +1,308 @@
+//! List functions. Lists are changed in place, and functions that take a callback call it with
+//! each element.
+
+use crate::list::List;
+use crate::native::{argument, call, Callback, Globals, Runtime};
+use crate::object::{Arity, Error, ErrorKind, Object};
+use crate::stdlib::range;
+use std::cmp::Ordering;
+
+pub(crate) fn define(globals: &mut impl Globals) {
+    globals.define_native("push", 2, |_, arguments| {
+        let list: List = argument(&arguments, 0)?;
+        list.push(arguments[1].clone());
+        Ok(Object::Nil)
+    });
+    globals.define_native("pop", 1, |_, arguments| {
+        let list: List = argument(&arguments, 0)?;
+        Ok(list.pop().ok_or(ErrorKind::PopFromEmptyList)?)
+    });
+    globals.define_native("slice", 3, |_, arguments| {
+        let list: List = argument(&arguments, 0)?;
+        let range = range(
+            argument(&arguments, 1)?,
+            argument(&arguments, 2)?,
+            list.len(),
+        )?;
+        Ok(Object::List(List::new(list.elements()[range].to_vec())))
+    });
+    globals.define_native("map", 2, |runtime, arguments| {
+        let list: List = argument(&arguments, 0)?;
+        let function: Callback = argument(&arguments, 1)?;
+        let elements = list
+            .elements()
+            .into_iter()
+            .map(|element| call(runtime, &function, vec![element]))
+            .collect::<Result<_, _>>()?;
+        Ok(Object::List(List::new(elements)))
+    });
+    globals.define_native("filter", 2, |runtime, arguments| {
+        let list: List = argument(&arguments, 0)?;
+        let function: Callback = argument(&arguments, 1)?;
+        let mut elements = Vec::new();
+        for element in list.elements() {
+            if call(runtime, &function, vec![element.clone()])?.is_truthy() {
+                elements.push(element);
+            }
+        }
+        Ok(Object::List(List::new(elements)))
+    });
+    globals.define_native("sort", Arity::AtLeast(1), |runtime, arguments| {
+        let list: List = argument(&arguments, 0)?;
+        let comparator: Option<Callback> = argument(&arguments, 1)?;
+        let elements = merge_sort(list.elements(), &mut |left, right| match &comparator {
+            Some(comparator) => compare_with(runtime, comparator, left, right),
+            None => compare(left, right).map_err(Error::from),
+        })?;
+        list.replace(elements);
+        Ok(Object::Nil)
+    });
+}
+
+/// A stable merge sort that stops at the first error. The standard library's sorts may panic
+/// when the order isn't consistent, which a Lox comparator, or NaN, can't promise.
+fn merge_sort(
+    mut elements: Vec<Object>,
+    compare: &mut impl FnMut(&Object, &Object) -> Result<Ordering, Error>,
+) -> Result<Vec<Object>, Error> {
+    if elements.len() <= 1 {
+        return Ok(elements);
+    }
+    let right = elements.split_off(elements.len() / 2);
+    let mut left = merge_sort(elements, compare)?.into_iter().peekable();
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    while let (Some(first), Some(second)) = (left.peek(), right.peek()) {
+        let next = if compare(first, second)?.is_gt() {
+            right.next()
+        } else {
+            left.next()
+        };
+        merged.extend(next);
+    }
+    merged.extend(left.chain(right));
+    Ok(merged)
+}
+
+/// Orders numbers and strings. Lists of any other values need a comparator.
+fn compare(left: &Object, right: &Object) -> Result<Ordering, ErrorKind> {
+    match left {
+        Object::Number(left) => {
+            let right = right.to_number_value()?;
+            Ok(left.partial_cmp(&right).unwrap_or(Ordering::Equal))
+        }
+        Object::String(left) => Ok(left.cmp(&right.clone().string_value()?)),
+        _ => Err(ErrorKind::ExpectedNumberOrString {
+            actual: left.clone(),
+        }),
+    }
+}
+
+/// Calls a comparator, which returns a negative number if `left` goes first, a positive number if
+/// `right` does, and zero if their order doesn't matter.
+fn compare_with(
+    runtime: &mut dyn Runtime,
+    comparator: &Callback,
+    left: &Object,
+    right: &Object,
+) -> Result<Ordering, Error> {
+    let result = call(runtime, comparator, vec![left.clone(), right.clone()])?;
+    let result = f64::from(result.to_number_value()?);
+    Ok(result.partial_cmp(&0.0).unwrap_or(Ordering::Equal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::stdlib::number;
+    use crate::stdlib::tests::run;
+    use std::assert_matches;
+
+    /// Runs `source` and returns the list it leaves in `xs`.
+    fn evaluate(source: &str) -> Result<Object, ErrorKind> {
+        run(&mut Interpreter::new(), source, "xs")
+    }
+
+    fn numbers(values: &[f64]) -> Object {
+        Object::List(List::new(
+            values.iter().map(|value| number(*value)).collect(),
+        ))
+    }
+
+    #[test]
+    fn literal_and_index() {
+        assert_eq!(
+            evaluate("var xs = [1, 2, 3]; xs[1] = xs[0] + xs[2];").unwrap(),
+            numbers(&[1.0, 4.0, 3.0])
+        );
+        assert_eq!(evaluate("var xs = [];").unwrap(), numbers(&[]));
+        assert_eq!(
+            evaluate("var xs = [[1], [2]][1];").unwrap(),
+            numbers(&[2.0])
+        );
+    }
+
+    #[test]
+    fn lists_are_shared() {
+        assert_eq!(
+            evaluate("var xs = [1]; var ys = xs; ys[0] = 2;").unwrap(),
+            numbers(&[2.0])
+        );
+    }
+
+    #[test]
+    fn index_errors() {
+        assert_matches!(
+            evaluate("var xs = [1]; xs[1];").unwrap_err(),
+            ErrorKind::IndexOutOfBounds {
+                index: 1,
+                length: 1
+            }
+        );
+        assert_matches!(
+            evaluate("var xs = [1]; xs[\"a\"] = 1;").unwrap_err(),
+            ErrorKind::InvalidIndex { .. }
+        );
+        assert_matches!(
+            evaluate("var xs = 1; xs[0];").unwrap_err(),
+            ErrorKind::NotIndexable { .. }
+        );
+    }
+
+    #[test]
+    fn len() {
+        assert_eq!(evaluate("var xs = len([1, 2]);").unwrap(), number(2.0));
+        assert_eq!(evaluate("var xs = len(\"ab\");").unwrap(), number(2.0));
+        assert_matches!(
+            evaluate("var xs = len(nil);").unwrap_err(),
+            ErrorKind::WrongArgumentType { .. }
+        );
+    }
+
+    #[test]
+    fn push_and_pop() {
+        assert_eq!(
+            evaluate("var xs = [1]; push(xs, 2); push(xs, 3); pop(xs);").unwrap(),
+            numbers(&[1.0, 2.0])
+        );
+        assert_matches!(
+            evaluate("var xs = []; pop(xs);").unwrap_err(),
+            ErrorKind::PopFromEmptyList
+        );
+    }
+
+    #[test]
+    fn slice() {
+        assert_eq!(
+            evaluate("var xs = slice([1, 2, 3, 4], 1, 3);").unwrap(),
+            numbers(&[2.0, 3.0])
+        );
+        assert_matches!(
+            evaluate("var xs = slice([1], 0, 2);").unwrap_err(),
+            ErrorKind::IndexOutOfBounds { index: 2, .. }
+        );
+        assert_matches!(
+            evaluate("var xs = slice([1, 2], 2, 1);").unwrap_err(),
+            ErrorKind::ReversedRange { start: 2, end: 1 }
+        );
+    }
+
+    #[test]
+    fn map_and_filter() {
+        assert_eq!(
+            evaluate("fun double(x) { return x * 2; } var xs = map([1, 2], double);").unwrap(),
+            numbers(&[2.0, 4.0])
+        );
+        assert_eq!(
+            evaluate("fun big(x) { return x > 1; } var xs = filter([1, 2, 3], big);").unwrap(),
+            numbers(&[2.0, 3.0])
+        );
+    }
+
+    #[test]
+    fn map_checks_the_callback() {
+        assert_matches!(
+            evaluate("fun add(a, b) { return a + b; } var xs = map([1], add);").unwrap_err(),
+            ErrorKind::WrongNumberOfArguments {
+                expected: 2,
+                actual: 1
+            }
+        );
+        assert_matches!(
+            evaluate("fun fail(x) { return x + nil; } var xs = map([1], fail);").unwrap_err(),
+            ErrorKind::ExpectedNumber { .. }
+        );
+    }
+
+    #[test]
+    fn sort() {
+        assert_eq!(
+            evaluate("var xs = [3, 1, 2]; sort(xs);").unwrap(),
+            numbers(&[1.0, 2.0, 3.0])
+        );
+        assert_eq!(
+            evaluate("var xs = [\"b\", \"a\"]; sort(xs);").unwrap(),
+            Object::List(List::new(vec![
+                Object::String("a".to_string()),
+                Object::String("b".to_string())
+            ]))
+        );
+    }
+
+    #[test]
+    fn sort_with_comparator() {
+        assert_eq!(
+            evaluate("fun desc(a, b) { return b - a; } var xs = [1, 3, 2]; sort(xs, desc);")
+                .unwrap(),
+            numbers(&[3.0, 2.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn sort_is_stable() {
+        assert_eq!(
+            evaluate(
+                "fun byFirst(a, b) { return a[0] - b[0]; }
+                 fun second(pair) { return pair[1]; }
+                 var xs = [[2, 1], [1, 2], [2, 3], [1, 4]]; sort(xs, byFirst);
+                 xs = map(xs, second);"
+            )
+            .unwrap(),
+            numbers(&[2.0, 4.0, 1.0, 3.0])
+        );
+    }
+
+    #[test]
+    fn sort_with_inconsistent_order() {
+        let source = "var xs = []; for (var i = 0; i < 500; i = i + 1) push(xs, i);";
+        assert_eq!(
+            evaluate(&format!(
+                "{source} fun shuffle(a, b) {{ return random() - 0.5; }} sort(xs, shuffle); \
+                 xs = [len(xs)];"
+            ))
+            .unwrap(),
+            numbers(&[500.0])
+        );
+        assert_eq!(
+            evaluate("var xs = [3, sqrt(-1), 1, 2]; sort(xs); xs = [len(xs)];").unwrap(),
+            numbers(&[4.0])
+        );
+    }
+
+    #[test]
+    fn sort_errors() {
+        assert_matches!(
+            evaluate("var xs = [1, \"a\"]; sort(xs);").unwrap_err(),
+            ErrorKind::ExpectedNumber { .. } | ErrorKind::ExpectedString { .. }
+        );
+        assert_matches!(
+            evaluate("var xs = [nil, nil]; sort(xs);").unwrap_err(),
+            ErrorKind::ExpectedNumberOrString { .. }
+        );
+        assert_matches!(
+            evaluate("fun bad(a, b) { return nil; } var xs = [1, 2]; sort(xs, bad);").unwrap_err(),
+            ErrorKind::ExpectedNumber { .. }
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Map functions. Keys and values are listed in the order the keys were first inserted.
+
+use crate::list::List;
+use crate::map::Map;
+use crate::native::{argument, Globals};
+use crate::object::Object;
+
+pub(crate) fn define(globals: &mut impl Globals) {
+    globals.define_native("keys", 1, |_, arguments| {
+        let map: Map = argument(&arguments, 0)?;
+        Ok(Object::List(List::new(map.keys())))
+    });
+    globals.define_native("values", 1, |_, arguments| {
+        let map: Map = argument(&arguments, 0)?;
+        Ok(Object::List(List::new(map.values())))
+    });
+    globals.define_native("has", 2, |_, arguments| {
+        let map: Map = argument(&arguments, 0)?;
+        Ok(Object::Boolean(map.has(&arguments[1])?))
+    });
+    globals.define_native("remove", 2, |_, arguments| {
+        let map: Map = argument(&arguments, 0)?;
+        Ok(map.remove(&arguments[1])?.unwrap_or(Object::Nil))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::object::ErrorKind;
+    use crate::stdlib::tests::run;
+    use std::assert_matches;
+
+    /// Runs `source` and returns the value it leaves in `x`.
+    fn evaluate(source: &str) -> Result<Object, ErrorKind> {
+        run(&mut Interpreter::new(), source, "x")
+    }
+
+    fn string(value: &str) -> Object {
+        Object::String(value.to_string())
+    }
+
+    fn number(value: f64) -> Object {
+        Object::Number(value.into())
+    }
+
+    fn list(elements: Vec<Object>) -> Object {
+        Object::List(List::new(elements))
+    }
+
+    #[test]
+    fn literal_and_index() {
+        assert_eq!(
+            evaluate("var m = {\"a\": 1, 2: \"b\"}; var x = [m[\"a\"], m[2]];").unwrap(),
+            list(vec![number(1.0), string("b")])
+        );
+        assert_eq!(
+            evaluate("var m = {}; m[\"k\"] = 3; var x = m[\"k\"];").unwrap(),
+            number(3.0)
+        );
+    }
+
+    #[test]
+    fn literal_does_not_clash_with_blocks() {
+        assert_eq!(
+            evaluate("var x = 1; { var m = {true: nil}; x = len(m); }").unwrap(),
+            number(1.0)
+        );
+    }
+
+    #[test]
+    fn index_errors() {
+        assert_matches!(
+            evaluate("var x = {}[\"missing\"];").unwrap_err(),
+            ErrorKind::KeyNotFound { .. }
+        );
+        assert_matches!(
+            evaluate("var x = {[]: 1};").unwrap_err(),
+            ErrorKind::UnhashableKey { .. }
+        );
+    }
+
+    #[test]
+    fn keys_and_values_keep_insertion_order() {
+        assert_eq!(
+            evaluate("var m = {\"b\": 1, \"a\": 2}; m[\"c\"] = 3; var x = keys(m);").unwrap(),
+            list(vec![string("b"), string("a"), string("c")])
+        );
+        assert_eq!(
+            evaluate("var m = {\"b\": 1, \"a\": 2}; m[\"b\"] = 3; var x = values(m);").unwrap(),
+            list(vec![number(3.0), number(2.0)])
+        );
+    }
+
+    #[test]
+    fn has_and_remove() {
+        assert_eq!(
+            evaluate("var m = {1: 2}; var x = [has(m, 1), remove(m, 1), has(m, 1), remove(m, 1)];")
+                .unwrap(),
+            list(vec![
+                Object::Boolean(true),
+                number(2.0),
+                Object::Boolean(false),
+                Object::Nil
+            ])
+        );
+    }
+
+    #[test]
+    fn equality() {
+        assert_eq!(
+            evaluate("var x = {1: 2, 3: 4} == {3: 4, 1: 2};").unwrap(),
+            Object::Boolean(true)
+        );
+        assert_eq!(
+            evaluate("var x = {1: 2} == {1: 3};").unwrap(),
+            Object::Boolean(false)
+        );
+    }
+}
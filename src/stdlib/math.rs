@@ -0,0 +1,197 @@
+//! Math functions and constants, and a random number generator that can be seeded so scripts
+//! are reproducible.
+
+use crate::native::{argument, Globals};
+use crate::object::{Arity, Error, Object};
+use crate::stdlib::number;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) fn define(globals: &mut impl Globals) {
+    globals.set_global("PI", number(std::f64::consts::PI));
+    globals.set_global("E", number(std::f64::consts::E));
+
+    define_unary(globals, "floor", f64::floor);
+    define_unary(globals, "ceil", f64::ceil);
+    define_unary(globals, "round", f64::round);
+    define_unary(globals, "abs", f64::abs);
+    define_unary(globals, "sqrt", f64::sqrt);
+    define_unary(globals, "exp", f64::exp);
+    define_unary(globals, "log", f64::ln);
+    define_unary(globals, "sin", f64::sin);
+    define_unary(globals, "cos", f64::cos);
+    define_unary(globals, "tan", f64::tan);
+    define_unary(globals, "asin", f64::asin);
+    define_unary(globals, "acos", f64::acos);
+    define_unary(globals, "atan", f64::atan);
+
+    globals.define_native("pow", 2, |_, arguments| {
+        let base: f64 = argument(&arguments, 0)?;
+        let exponent: f64 = argument(&arguments, 1)?;
+        Ok(number(base.powf(exponent)))
+    });
+    globals.define_native("atan2", 2, |_, arguments| {
+        let y: f64 = argument(&arguments, 0)?;
+        let x: f64 = argument(&arguments, 1)?;
+        Ok(number(y.atan2(x)))
+    });
+    globals.define_native("min", Arity::AtLeast(1), |_, arguments| {
+        fold(&arguments, f64::min)
+    });
+    globals.define_native("max", Arity::AtLeast(1), |_, arguments| {
+        fold(&arguments, f64::max)
+    });
+
+    let random = Rc::new(Random::new(time_seed()));
+    let generator = random.clone();
+    globals.define_native("random", 0, move |_, _| Ok(number(generator.next_f64())));
+    globals.define_native("seedRandom", 1, move |_, arguments| {
+        let seed: i64 = argument(&arguments, 0)?;
+        random.seed(seed as u64);
+        Ok(Object::Nil)
+    });
+}
+
+fn define_unary(globals: &mut impl Globals, name: &str, function: fn(f64) -> f64) {
+    globals.define_native(name, 1, move |_, arguments| {
+        let value: f64 = argument(&arguments, 0)?;
+        Ok(number(function(value)))
+    });
+}
+
+fn fold(arguments: &[Object], function: fn(f64, f64) -> f64) -> Result<Object, Error> {
+    let mut result: f64 = argument(arguments, 0)?;
+    for index in 1..arguments.len() {
+        result = function(result, argument(arguments, index)?);
+    }
+    Ok(number(result))
+}
+
+fn time_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+/// A SplitMix64 generator. It is small and fast, and good enough for scripts; it is not meant for
+/// cryptography.
+struct Random {
+    state: Cell<u64>,
+}
+
+impl Random {
+    fn new(seed: u64) -> Random {
+        Random {
+            state: Cell::new(seed),
+        }
+    }
+
+    fn seed(&self, seed: u64) {
+        self.state.set(seed);
+    }
+
+    fn next_u64(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.state.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `[0, 1)`.
+    fn next_f64(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::object::ErrorKind;
+    use crate::stdlib::tests::{evaluate, run};
+    use std::assert_matches;
+
+    fn value(expression: &str) -> f64 {
+        argument(&[evaluate(expression).unwrap()], 0).unwrap()
+    }
+
+    fn random(interpreter: &mut Interpreter) -> Object {
+        run(interpreter, "var result = random();", "result").unwrap()
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(value("PI"), std::f64::consts::PI);
+        assert_eq!(value("E"), std::f64::consts::E);
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(value("floor(1.7)"), 1.0);
+        assert_eq!(value("floor(-1.2)"), -2.0);
+        assert_eq!(value("ceil(1.2)"), 2.0);
+        assert_eq!(value("round(2.5)"), 3.0);
+        assert_eq!(value("abs(-3)"), 3.0);
+    }
+
+    #[test]
+    fn powers_and_roots() {
+        assert_eq!(value("sqrt(16)"), 4.0);
+        assert!(value("sqrt(-1)").is_nan());
+        assert_eq!(value("pow(2, 10)"), 1024.0);
+        assert_eq!(value("log(E)"), 1.0);
+        assert_eq!(value("exp(0)"), 1.0);
+    }
+
+    #[test]
+    fn trigonometry() {
+        assert_eq!(value("sin(0)"), 0.0);
+        assert_eq!(value("cos(0)"), 1.0);
+        assert!((value("tan(PI / 4)") - 1.0).abs() < 1e-12);
+        assert!((value("atan2(1, 1)") - std::f64::consts::FRAC_PI_4).abs() < 1e-12);
+        assert!((value("asin(1)") - std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn min_and_max() {
+        assert_eq!(value("min(3)"), 3.0);
+        assert_eq!(value("min(3, -1, 2)"), -1.0);
+        assert_eq!(value("max(3, -1, 2)"), 3.0);
+    }
+
+    #[test]
+    fn min_needs_numbers() {
+        assert_matches!(
+            evaluate("min(1, \"a\")").unwrap_err(),
+            ErrorKind::WrongArgumentType { index: 1, .. }
+        );
+        assert_matches!(
+            evaluate("max()").unwrap_err(),
+            ErrorKind::NotEnoughArguments { minimum: 1, .. }
+        );
+    }
+
+    #[test]
+    fn random_is_between_zero_and_one() {
+        let mut interpreter = Interpreter::new();
+        for _ in 0..100 {
+            let random: f64 = argument(&[random(&mut interpreter)], 0).unwrap();
+            assert!((0.0..1.0).contains(&random));
+        }
+    }
+
+    #[test]
+    fn seeded_random_is_reproducible() {
+        let sequence = |seed: i64| {
+            let mut interpreter = Interpreter::new();
+            interpreter.eval(&format!("seedRandom({seed});")).unwrap();
+            (0..5).map(|_| random(&mut interpreter)).collect::<Vec<_>>()
+        };
+        assert_eq!(sequence(42), sequence(42));
+        assert_ne!(sequence(42), sequence(43));
+    }
+}
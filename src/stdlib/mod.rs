@@ -0,0 +1,87 @@
+//! Native functions defined in the globals of every backend.
+
+use crate::native::Globals;
+use crate::number::Number;
+use crate::object::{ErrorKind, Object};
+use std::ops::Range;
+
+mod lists;
+mod maps;
+mod math;
+mod strings;
+
+pub(crate) fn define(globals: &mut impl Globals) {
+    globals.define_native("len", 1, |_, arguments| {
+        let length = match &arguments[0] {
+            Object::String(string) => string.chars().count(),
+            Object::List(list) => list.len(),
+            Object::Map(map) => map.len(),
+            actual => {
+                return Err(ErrorKind::WrongArgumentType {
+                    index: 0,
+                    expected: "a string, a list or a map",
+                    actual: actual.clone(),
+                }
+                .into())
+            }
+        };
+        Ok(number(length as f64))
+    });
+    lists::define(globals);
+    maps::define(globals);
+    math::define(globals);
+    strings::define(globals);
+}
+
+fn number(value: f64) -> Object {
+    Object::Number(Number::from(value))
+}
+
+/// Checks the range from `start` up to `end` of `length` elements. Either may be one past the
+/// end, but `end` can't come before `start`.
+fn range(start: i64, end: i64, length: usize) -> Result<Range<usize>, ErrorKind> {
+    let range = bound(start, length)?..bound(end, length)?;
+    if range.end < range.start {
+        return Err(ErrorKind::ReversedRange { start, end });
+    }
+    Ok(range)
+}
+
+/// Checks the start or end of a range of `length` elements, which may be one past the end.
+fn bound(index: i64, length: usize) -> Result<usize, ErrorKind> {
+    usize::try_from(index)
+        .ok()
+        .filter(|index| *index <= length)
+        .ok_or(ErrorKind::IndexOutOfBounds { index, length })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Interpreter;
+    use crate::object::{ErrorKind, Object};
+    use crate::result::Error;
+
+    /// Runs `source` and returns the global `name`, or the kind of the runtime error it raised.
+    pub(super) fn run(
+        interpreter: &mut Interpreter,
+        source: &str,
+        name: &str,
+    ) -> Result<Object, ErrorKind> {
+        match interpreter.eval(source) {
+            Ok(()) => Ok(interpreter.get_global(name).unwrap()),
+            Err(mut errors) => match errors.remove(0) {
+                Error::EvaluationError(error) => Err(error.kind),
+                error => panic!("unexpected error {error:?}"),
+            },
+        }
+    }
+
+    /// The value of `expression` in a new interpreter.
+    pub(super) fn evaluate(expression: &str) -> Result<Object, ErrorKind> {
+        run(
+            &mut Interpreter::new(),
+            &format!("var result = {expression};"),
+            "result",
+        )
+    }
+}
@@ -0,0 +1,330 @@
+//! String functions. Lengths and indices count characters, not bytes, so they work the same for
+//! any Unicode text.
+
+use crate::list::List;
+use crate::native::{argument, Globals};
+use crate::object::{ErrorKind, Object};
+use crate::stdlib::{number, range};
+
+/// The most digits `formatNumber` shows after the decimal point.
+const MAXIMUM_DIGITS: usize = 1000;
+
+pub(crate) fn define(globals: &mut impl Globals) {
+    globals.define_native("substring", 3, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        let length = string.chars().count();
+        let range = range(argument(&arguments, 1)?, argument(&arguments, 2)?, length)?;
+        let substring = string.chars().skip(range.start).take(range.len()).collect();
+        Ok(Object::String(substring))
+    });
+    globals.define_native("split", 2, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        let separator: String = argument(&arguments, 1)?;
+        let parts = if separator.is_empty() {
+            string
+                .chars()
+                .map(|character| Object::String(character.to_string()))
+                .collect()
+        } else {
+            string
+                .split(&separator)
+                .map(|part| Object::String(part.to_string()))
+                .collect()
+        };
+        Ok(Object::List(List::new(parts)))
+    });
+    globals.define_native("join", 2, |_, arguments| {
+        let list: List = argument(&arguments, 0)?;
+        let separator: String = argument(&arguments, 1)?;
+        let parts = list
+            .elements()
+            .into_iter()
+            .map(Object::string_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Object::String(parts.join(&separator)))
+    });
+    globals.define_native("indexOf", 2, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        let needle: String = argument(&arguments, 1)?;
+        let index = match string.find(&needle) {
+            Some(byte_index) => string[..byte_index].chars().count() as f64,
+            None => -1.0,
+        };
+        Ok(number(index))
+    });
+    globals.define_native("upper", 1, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        Ok(Object::String(string.to_uppercase()))
+    });
+    globals.define_native("lower", 1, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        Ok(Object::String(string.to_lowercase()))
+    });
+    globals.define_native("trim", 1, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        Ok(Object::String(string.trim().to_string()))
+    });
+    globals.define_native("replace", 3, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        let from: String = argument(&arguments, 1)?;
+        let to: String = argument(&arguments, 2)?;
+        Ok(Object::String(string.replace(&from, &to)))
+    });
+    globals.define_native("startsWith", 2, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        let prefix: String = argument(&arguments, 1)?;
+        Ok(Object::Boolean(string.starts_with(&prefix)))
+    });
+    globals.define_native("endsWith", 2, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        let suffix: String = argument(&arguments, 1)?;
+        Ok(Object::Boolean(string.ends_with(&suffix)))
+    });
+    globals.define_native("chr", 1, |_, arguments| {
+        let code: i64 = argument(&arguments, 0)?;
+        let character = u32::try_from(code)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or(ErrorKind::InvalidCodePoint { code })?;
+        Ok(Object::String(character.to_string()))
+    });
+    globals.define_native("ord", 1, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        let mut characters = string.chars();
+        match (characters.next(), characters.next()) {
+            (Some(character), None) => Ok(number(character as u32 as f64)),
+            _ => Err(ErrorKind::WrongArgumentType {
+                index: 0,
+                expected: "a string of one character",
+                actual: Object::String(string),
+            }
+            .into()),
+        }
+    });
+    globals.define_native("parseNumber", 1, |_, arguments| {
+        let string: String = argument(&arguments, 0)?;
+        Ok(match string.trim().parse::<f64>() {
+            Ok(value) if value.is_finite() => number(value),
+            _ => Object::Nil,
+        })
+    });
+    globals.define_native("formatNumber", 2, |_, arguments| {
+        let value: f64 = argument(&arguments, 0)?;
+        let digits: Option<i64> = argument(&arguments, 1)?;
+        let formatted = match digits {
+            None => value.to_string(),
+            Some(digits) => {
+                // Formatting panics with a precision above `u16::MAX`, and an `f64` never has
+                // more than a few hundred significant digits anyway.
+                let digits = usize::try_from(digits)
+                    .ok()
+                    .filter(|digits| *digits <= MAXIMUM_DIGITS)
+                    .ok_or_else(|| ErrorKind::WrongArgumentType {
+                        index: 1,
+                        expected: "an integer from 0 to 1000",
+                        actual: arguments[1].clone(),
+                    })?;
+                format!("{value:.digits$}")
+            }
+        };
+        Ok(Object::String(formatted))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib::tests::evaluate;
+    use std::assert_matches;
+
+    fn string(value: &str) -> Object {
+        Object::String(value.to_string())
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(
+            evaluate("split(\"a,b,,c\", \",\")").unwrap(),
+            Object::List(List::new(vec![
+                string("a"),
+                string("b"),
+                string(""),
+                string("c")
+            ]))
+        );
+        assert_eq!(
+            evaluate("split(\"dé\", \"\")").unwrap(),
+            Object::List(List::new(vec![string("d"), string("é")]))
+        );
+    }
+
+    #[test]
+    fn join() {
+        assert_eq!(
+            evaluate("join([\"a\", \"b\"], \", \")").unwrap(),
+            string("a, b")
+        );
+        assert_eq!(evaluate("join([], \"-\")").unwrap(), string(""));
+        assert_eq!(
+            evaluate("join(split(\"a b c\", \" \"), \"-\")").unwrap(),
+            string("a-b-c")
+        );
+    }
+
+    #[test]
+    fn join_needs_strings() {
+        assert_matches!(
+            evaluate("join([\"a\", 1], \"\")").unwrap_err(),
+            ErrorKind::ExpectedString { .. }
+        );
+    }
+
+    #[test]
+    fn len_counts_characters() {
+        assert_eq!(evaluate("len(\"\")").unwrap(), number(0.0));
+        assert_eq!(evaluate("len(\"héllo\")").unwrap(), number(5.0));
+        assert_eq!(evaluate("len(\"日本\")").unwrap(), number(2.0));
+    }
+
+    #[test]
+    fn len_of_a_number() {
+        assert_matches!(
+            evaluate("len(1)").unwrap_err(),
+            ErrorKind::WrongArgumentType { index: 0, .. }
+        );
+    }
+
+    #[test]
+    fn substring() {
+        assert_eq!(
+            evaluate("substring(\"héllo\", 1, 3)").unwrap(),
+            string("él")
+        );
+        assert_eq!(evaluate("substring(\"abc\", 3, 3)").unwrap(), string(""));
+    }
+
+    #[test]
+    fn substring_out_of_bounds() {
+        assert_matches!(
+            evaluate("substring(\"abc\", 1, 4)").unwrap_err(),
+            ErrorKind::IndexOutOfBounds {
+                index: 4,
+                length: 3
+            }
+        );
+        assert_matches!(
+            evaluate("substring(\"abc\", -1, 2)").unwrap_err(),
+            ErrorKind::IndexOutOfBounds { index: -1, .. }
+        );
+        assert_matches!(
+            evaluate("substring(\"abc\", 2, 1)").unwrap_err(),
+            ErrorKind::ReversedRange { start: 2, end: 1 }
+        );
+        assert_matches!(
+            evaluate("substring(\"abc\", 0.5, 1)").unwrap_err(),
+            ErrorKind::WrongArgumentType { index: 1, .. }
+        );
+    }
+
+    #[test]
+    fn index_of() {
+        assert_eq!(
+            evaluate("indexOf(\"日本語\", \"語\")").unwrap(),
+            number(2.0)
+        );
+        assert_eq!(evaluate("indexOf(\"abc\", \"d\")").unwrap(), number(-1.0));
+        assert_eq!(evaluate("indexOf(\"abc\", \"\")").unwrap(), number(0.0));
+    }
+
+    #[test]
+    fn change_case() {
+        assert_eq!(evaluate("upper(\"straße\")").unwrap(), string("STRASSE"));
+        assert_eq!(evaluate("lower(\"ÀB\")").unwrap(), string("àb"));
+    }
+
+    #[test]
+    fn trim() {
+        assert_eq!(evaluate("trim(\" \ta b\n \")").unwrap(), string("a b"));
+    }
+
+    #[test]
+    fn replace() {
+        assert_eq!(
+            evaluate("replace(\"a-b-c\", \"-\", \"+\")").unwrap(),
+            string("a+b+c")
+        );
+    }
+
+    #[test]
+    fn starts_and_ends_with() {
+        assert_eq!(
+            evaluate("startsWith(\"lox\", \"lo\")").unwrap(),
+            Object::Boolean(true)
+        );
+        assert_eq!(
+            evaluate("endsWith(\"lox\", \"lo\")").unwrap(),
+            Object::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn chr_and_ord() {
+        assert_eq!(evaluate("chr(233)").unwrap(), string("é"));
+        assert_eq!(evaluate("ord(\"é\")").unwrap(), number(233.0));
+        assert_eq!(evaluate("ord(chr(128512))").unwrap(), number(128512.0));
+    }
+
+    #[test]
+    fn chr_invalid_code_point() {
+        assert_matches!(
+            evaluate("chr(55296)").unwrap_err(),
+            ErrorKind::InvalidCodePoint { code: 55296 }
+        );
+        assert_matches!(
+            evaluate("chr(-1)").unwrap_err(),
+            ErrorKind::InvalidCodePoint { code: -1 }
+        );
+    }
+
+    #[test]
+    fn ord_needs_one_character() {
+        assert_matches!(
+            evaluate("ord(\"ab\")").unwrap_err(),
+            ErrorKind::WrongArgumentType { index: 0, .. }
+        );
+        assert_matches!(
+            evaluate("ord(\"\")").unwrap_err(),
+            ErrorKind::WrongArgumentType { index: 0, .. }
+        );
+    }
+
+    #[test]
+    fn parse_number() {
+        assert_eq!(evaluate("parseNumber(\" 2.5 \")").unwrap(), number(2.5));
+        assert_eq!(evaluate("parseNumber(\"-3\")").unwrap(), number(-3.0));
+        assert_eq!(evaluate("parseNumber(\"abc\")").unwrap(), Object::Nil);
+        assert_eq!(evaluate("parseNumber(\"inf\")").unwrap(), Object::Nil);
+    }
+
+    #[test]
+    fn format_number() {
+        assert_eq!(evaluate("formatNumber(2, nil)").unwrap(), string("2"));
+        assert_eq!(evaluate("formatNumber(2.5, nil)").unwrap(), string("2.5"));
+        assert_eq!(
+            evaluate("formatNumber(3.14159, 2)").unwrap(),
+            string("3.14")
+        );
+        assert_matches!(
+            evaluate("formatNumber(1, -1)").unwrap_err(),
+            ErrorKind::WrongArgumentType { index: 1, .. }
+        );
+        assert_matches!(
+            evaluate("formatNumber(1, 70000)").unwrap_err(),
+            ErrorKind::WrongArgumentType { index: 1, .. }
+        );
+        assert_eq!(
+            evaluate("len(formatNumber(1, 1000))").unwrap(),
+            number(1002.0)
+        );
+    }
+}
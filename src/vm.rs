@@ -0,0 +1,1067 @@
+use crate::backend::{Analysis, Backend};
+use crate::chunk::{Constant, FunctionPrototype, OpCode};
+use crate::class::INITIALIZER_NAME;
+use crate::compiler::Compiler;
+use crate::heap::{address, track, Address, Trace};
+use crate::list::List;
+use crate::map::Map;
+use crate::native;
+use crate::native::{Globals, NativeFunction, Runtime};
+use crate::number::Number;
+use crate::object::{Arity, Callable, Error, ErrorKind, Object};
+use crate::output::{CapturedOutput, Output};
+use crate::result;
+use crate::stdlib;
+use crate::token::Span;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::Write;
+use std::rc::Rc;
+
+const FRAMES_MAX: usize = 1024;
+
+/// A runtime value of the virtual machine. Numbers, strings, booleans, nil, lists and maps are
+/// shared with the tree-walker, so both backends print and compare them in exactly the same way,
+/// and the same native functions work on them.
+#[derive(Clone)]
+pub enum Value {
+    Object(Object),
+    Closure(Rc<Closure>),
+    BoundMethod(Rc<BoundMethod>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    Native(Rc<NativeFunction>),
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Object(object) => object.is_truthy(),
+            _ => true,
+        }
+    }
+
+    fn number_value(self) -> Result<Number, ErrorKind> {
+        match self {
+            Value::Object(object) => object.to_number_value(),
+            value => Err(ErrorKind::ExpectedNumber {
+                actual: value.into_object(),
+            }),
+        }
+    }
+
+    fn string_value(self) -> Result<String, ErrorKind> {
+        match self {
+            Value::Object(object) => object.string_value(),
+            value => Err(ErrorKind::ExpectedString {
+                actual: value.into_object(),
+            }),
+        }
+    }
+
+    /// The value as an object, to store it in a list or map or pass it to a native function.
+    /// Values with no tree-walker counterpart are wrapped in [`Object::Vm`].
+    fn into_object(self) -> Object {
+        match self {
+            Value::Object(object) => object,
+            value => Object::Vm(Box::new(value)),
+        }
+    }
+
+    pub(crate) fn is_callable(&self) -> bool {
+        matches!(
+            self,
+            Value::Closure(_) | Value::BoundMethod(_) | Value::Class(_) | Value::Native(_)
+        )
+    }
+}
+
+impl From<Object> for Value {
+    fn from(object: Object) -> Self {
+        match object {
+            Object::Vm(value) => *value,
+            object => Value::Object(object),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Object(object), Value::Object(other_object)) => object == other_object,
+            (Value::Closure(closure), Value::Closure(other_closure)) => {
+                Rc::ptr_eq(closure, other_closure)
+            }
+            (Value::BoundMethod(method), Value::BoundMethod(other_method)) => {
+                Rc::ptr_eq(method, other_method)
+            }
+            (Value::Class(class), Value::Class(other_class)) => Rc::ptr_eq(class, other_class),
+            (Value::Instance(instance), Value::Instance(other_instance)) => {
+                Rc::ptr_eq(instance, other_instance)
+            }
+            (Value::Native(native), Value::Native(other_native)) => {
+                Rc::ptr_eq(native, other_native)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Object(object) => write!(formatter, "{object}"),
+            Value::Closure(closure) => write!(formatter, "<fn {}>", closure.prototype.name),
+            Value::BoundMethod(method) => {
+                write!(formatter, "<fn {}>", method.method.prototype.name)
+            }
+            Value::Class(class) => write!(formatter, "{}", class.name),
+            Value::Instance(instance) => write!(formatter, "{} instance", instance.class.name),
+            Value::Native(native) => write!(formatter, "{native}"),
+        }
+    }
+}
+
+impl Value {
+    /// Calls `visit` with the heap object this value refers to, if any.
+    pub(crate) fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        match self {
+            Value::Object(object) => object.trace(visit),
+            Value::Closure(closure) => visit(address(closure)),
+            Value::BoundMethod(method) => visit(address(method)),
+            Value::Class(class) => visit(address(class)),
+            Value::Instance(instance) => visit(address(instance)),
+            Value::Native(_) => {}
+        }
+    }
+    /// Like [`Object::repr`], the value with strings quoted.
+    fn repr(&self) -> String {
+        match self {
+            Value::Object(object) => object.repr(),
+            value => value.to_string(),
+        }
+    }
+}
+
+impl Debug for Value {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Object(object) => write!(formatter, "{object:?}"),
+            value => write!(formatter, "{value}"),
+        }
+    }
+}
+
+pub struct Closure {
+    prototype: Rc<FunctionPrototype>,
+    upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+/// A variable captured by a closure. It points into the stack until the variable goes out of
+/// scope, and then owns the value.
+enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct BoundMethod {
+    receiver: Value,
+    method: Rc<Closure>,
+}
+
+pub struct Class {
+    name: String,
+    methods: RefCell<HashMap<String, Rc<Closure>>>,
+}
+
+pub struct Instance {
+    class: Rc<Class>,
+    fields: RefCell<HashMap<String, Value>>,
+}
+
+impl Trace for Closure {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        for upvalue in &self.upvalues {
+            visit(address(upvalue));
+        }
+    }
+}
+
+impl Trace for RefCell<Upvalue> {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        if let Upvalue::Closed(value) = &*self.borrow() {
+            value.trace(visit);
+        }
+    }
+    fn clear(&self) {
+        *self.borrow_mut() = Upvalue::Closed(Value::Object(Object::Nil));
+    }
+}
+
+impl Trace for BoundMethod {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        self.receiver.trace(visit);
+        visit(address(&self.method));
+    }
+}
+
+impl Trace for Class {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        for method in self.methods.borrow().values() {
+            visit(address(method));
+        }
+    }
+    fn clear(&self) {
+        self.methods.borrow_mut().clear();
+    }
+}
+
+impl Trace for Instance {
+    fn trace(&self, visit: &mut dyn FnMut(Address)) {
+        visit(address(&self.class));
+        for value in self.fields.borrow().values() {
+            value.trace(visit);
+        }
+    }
+    fn clear(&self) {
+        self.fields.borrow_mut().clear();
+    }
+}
+
+/// Wraps a heap object of the virtual machine, registering it with the garbage collector.
+fn tracked<T: Trace + 'static>(object: T) -> Rc<T> {
+    let object = Rc::new(object);
+    track(&object);
+    object
+}
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    base: usize,
+}
+
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<String, Value>,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    output: Output,
+}
+
+impl Runtime for Vm {
+    /// Runs the called function until it returns, on top of the native that called it.
+    fn call(&mut self, function: &Object, arguments: Vec<Object>) -> Result<Object, Error> {
+        let argument_count = arguments.len();
+        let depth = self.frames.len();
+        self.stack.push(function.clone().into());
+        self.stack.extend(arguments.into_iter().map(Value::from));
+        self.call_value(argument_count)?;
+        let result = if self.frames.len() > depth {
+            self.run(depth)?
+        } else {
+            self.pop()
+        };
+        Ok(result.into_object())
+    }
+}
+
+impl Globals for Vm {
+    fn set_global(&mut self, name: &str, value: Object) {
+        self.globals.insert(name.to_string(), value.into());
+    }
+
+    fn define_native<F>(&mut self, name: &str, arity: impl Into<Arity>, function: F)
+    where
+        F: Fn(&mut dyn Runtime, Vec<Object>) -> Result<Object, Error> + 'static,
+    {
+        let native = NativeFunction::new(name, arity, function);
+        self.globals
+            .insert(name.to_string(), Value::Native(Rc::new(native)));
+    }
+}
+
+impl Backend for Vm {
+    fn run_for_value(&mut self, analysis: Analysis) -> Result<String, Vec<result::Error>> {
+        let script = Compiler::compile(&analysis.statements).map_err(|error| vec![error])?;
+        self.interpret(script)
+            .map(|value| value.repr())
+            .map_err(|error| vec![result::Error::EvaluationError(error)])
+    }
+
+    fn globals(&self) -> Vec<(String, String)> {
+        let mut globals = self
+            .globals
+            .iter()
+            .map(|(name, value)| (name.clone(), value.repr()))
+            .collect::<Vec<_>>();
+        globals.sort_by(|(name, _), (other_name, _)| name.cmp(other_name));
+        globals
+    }
+
+    /// Native functions defined from Rust are forgotten too, but `print` keeps its output.
+    fn reset(&mut self) {
+        let output = self.output.clone();
+        *self = Vm::new();
+        self.output = output;
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        let mut vm = Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            output: Rc::new(RefCell::new(std::io::stdout())),
+        };
+        native::define_builtins(&mut vm);
+        stdlib::define(&mut vm);
+        vm
+    }
+
+    /// Sends the output of `print` statements to `output` instead of stdout.
+    pub fn set_output<W: Write + 'static>(&mut self, output: W) {
+        self.output = Rc::new(RefCell::new(output));
+    }
+
+    /// Sends the output of `print` statements to memory, and returns a handle to read it.
+    pub fn capture_output(&mut self) -> CapturedOutput {
+        let captured = CapturedOutput::default();
+        self.set_output(captured.clone());
+        captured
+    }
+
+    fn interpret(&mut self, script: Rc<FunctionPrototype>) -> Result<Value, Error> {
+        let closure = Rc::new(Closure {
+            prototype: script,
+            upvalues: Vec::new(),
+        });
+        self.stack.push(Value::Closure(closure.clone()));
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base: 0,
+        });
+        let result = self.run(0);
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    /// Runs until the frame at `depth` returns, and returns what it returned. The script's frame
+    /// is at depth 0.
+    fn run(&mut self, depth: usize) -> Result<Value, Error> {
+        loop {
+            let byte = self.read_byte();
+            let Ok(op_code) = OpCode::try_from(byte) else {
+                unreachable!("the compiler only emits valid op codes, found {byte}")
+            };
+            match op_code {
+                OpCode::Constant => {
+                    let value = match self.read_constant() {
+                        Constant::Number(number) => Object::Number(number),
+                        Constant::String(string) => Object::String(string),
+                        Constant::Function(_) => unreachable!("functions are loaded by Closure"),
+                    };
+                    self.stack.push(Value::Object(value));
+                }
+                OpCode::Nil => self.stack.push(Value::Object(Object::Nil)),
+                OpCode::True => self.stack.push(Value::Object(Object::Boolean(true))),
+                OpCode::False => self.stack.push(Value::Object(Object::Boolean(false))),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.frame().base + self.read_byte() as usize;
+                    self.stack.push(self.stack[slot].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = self.frame().base + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    let Some(value) = self.globals.get(&name) else {
+                        return Err(self.error(ErrorKind::UndefinedVariable { name }));
+                    };
+                    self.stack.push(value.clone());
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    let value = self.peek(0).clone();
+                    let Some(global) = self.globals.get_mut(&name) else {
+                        return Err(self.error(ErrorKind::UndefinedVariable { name }));
+                    };
+                    *global = value;
+                }
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let value = match &*self.frame().closure.upvalues[index].borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let value = self.peek(0).clone();
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    match &mut *upvalue.borrow_mut() {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    };
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let instance = match self.pop() {
+                        Value::Instance(instance) => instance,
+                        actual => {
+                            return Err(self.error(ErrorKind::OnlyInstancesHaveProperties {
+                                actual: actual.into_object(),
+                            }))
+                        }
+                    };
+                    let field = instance.fields.borrow().get(&name).cloned();
+                    let value = match field {
+                        Some(value) => value,
+                        None => self.bind_method(
+                            &instance.class,
+                            Value::Instance(instance.clone()),
+                            name,
+                        )?,
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    let instance = match self.pop() {
+                        Value::Instance(instance) => instance,
+                        actual => {
+                            return Err(self.error(ErrorKind::OnlyInstancesHaveProperties {
+                                actual: actual.into_object(),
+                            }))
+                        }
+                    };
+                    instance.fields.borrow_mut().insert(name, value.clone());
+                    self.stack.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let Value::Class(superclass) = self.pop() else {
+                        unreachable!("the compiler only loads classes into super")
+                    };
+                    let receiver = self.pop();
+                    let method = self.bind_method(&superclass, receiver, name)?;
+                    self.stack.push(method);
+                }
+                OpCode::List => self
+                    .stack
+                    .push(Value::Object(Object::List(List::default()))),
+                OpCode::ListAppend => {
+                    let value = self.pop().into_object();
+                    let Value::Object(Object::List(list)) = self.peek(0) else {
+                        unreachable!("the compiler only appends to list literals")
+                    };
+                    list.push(value);
+                }
+                OpCode::Map => self.stack.push(Value::Object(Object::Map(Map::default()))),
+                OpCode::MapInsert => {
+                    let value = self.pop().into_object();
+                    let key = self.pop().into_object();
+                    let Value::Object(Object::Map(map)) = self.peek(0) else {
+                        unreachable!("the compiler only inserts into map literals")
+                    };
+                    let result = map.set(&key, value);
+                    result.map_err(|kind| self.error(kind))?;
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop().into_object();
+                    let result = self.pop().into_object().get_index(&index);
+                    let value = result.map_err(|kind| self.error(kind))?;
+                    self.stack.push(value.into());
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop().into_object();
+                    let target = self.pop().into_object();
+                    let result = target.set_index(&index, value.clone().into_object());
+                    result.map_err(|kind| self.error(kind))?;
+                    self.stack.push(value);
+                }
+                OpCode::Equal => {
+                    let (left, right) = self.pop_pair();
+                    self.push_boolean(left == right);
+                }
+                OpCode::NotEqual => {
+                    let (left, right) = self.pop_pair();
+                    self.push_boolean(left != right);
+                }
+                OpCode::Greater => self.comparison(Ordering::is_gt)?,
+                OpCode::GreaterEqual => self.comparison(Ordering::is_ge)?,
+                OpCode::Less => self.comparison(Ordering::is_lt)?,
+                OpCode::LessEqual => self.comparison(Ordering::is_le)?,
+                OpCode::Add => {
+                    let (left, right) = self.pop_pair();
+                    let result = match left {
+                        Value::Object(Object::Number(number)) => right
+                            .number_value()
+                            .map(|right| Object::Number(number + right)),
+                        Value::Object(Object::String(string)) => right
+                            .string_value()
+                            .map(|right| Object::String(string + &right)),
+                        left => Err(ErrorKind::ExpectedNumberOrString {
+                            actual: left.into_object(),
+                        }),
+                    };
+                    self.push_result(result)?;
+                }
+                OpCode::Subtract => self.arithmetic(|left, right| left - right)?,
+                OpCode::Multiply => self.arithmetic(|left, right| left * right)?,
+                OpCode::Divide => self.arithmetic(|left, right| left / right)?,
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push_boolean(!value.is_truthy());
+                }
+                OpCode::Negate => {
+                    let result = self
+                        .pop()
+                        .number_value()
+                        .map(|number| Object::Number(-number));
+                    self.push_result(result)?;
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    writeln!(self.output.borrow_mut(), "{value}").map_err(|error| {
+                        self.error(ErrorKind::FailedToWriteOutput {
+                            message: error.to_string(),
+                        })
+                    })?;
+                }
+                OpCode::Jump => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_u16() as usize;
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip -= offset;
+                }
+                OpCode::Call => {
+                    let argument_count = self.read_byte() as usize;
+                    self.call_value(argument_count)?;
+                }
+                OpCode::Closure => {
+                    let Constant::Function(prototype) = self.read_constant() else {
+                        unreachable!("the compiler only builds closures from functions")
+                    };
+                    let upvalues = (0..prototype.upvalue_count)
+                        .map(|_| {
+                            let is_local = self.read_byte() == 1;
+                            let index = self.read_byte() as usize;
+                            if is_local {
+                                self.capture_upvalue(self.frame().base + index)
+                            } else {
+                                self.frame().closure.upvalues[index].clone()
+                            }
+                        })
+                        .collect();
+                    self.stack.push(Value::Closure(tracked(Closure {
+                        prototype,
+                        upvalues,
+                    })));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.len() == depth {
+                        return Ok(result);
+                    }
+                    self.stack.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_string();
+                    self.stack.push(Value::Class(tracked(Class {
+                        name,
+                        methods: RefCell::new(HashMap::new()),
+                    })));
+                }
+                OpCode::Inherit => {
+                    let Value::Class(superclass) = self.peek(1).clone() else {
+                        let actual = self.peek(1).clone().into_object();
+                        return Err(self.error(ErrorKind::SuperclassMustBeAClass { actual }));
+                    };
+                    let Value::Class(subclass) = self.pop() else {
+                        unreachable!("the compiler only inherits into classes")
+                    };
+                    let methods = superclass.methods.borrow().clone();
+                    subclass.methods.borrow_mut().extend(methods);
+                }
+                OpCode::Method => {
+                    let name = self.read_string();
+                    let Value::Closure(method) = self.pop() else {
+                        unreachable!("the compiler only defines closures as methods")
+                    };
+                    let Value::Class(class) = self.peek(0) else {
+                        unreachable!("the compiler only defines methods on classes")
+                    };
+                    class.methods.borrow_mut().insert(name, method);
+                }
+            }
+        }
+    }
+
+    fn call_value(&mut self, argument_count: usize) -> Result<(), Error> {
+        let callee_slot = self.stack.len() - argument_count - 1;
+        match self.stack[callee_slot].clone() {
+            Value::Closure(closure) => self.call(closure, argument_count),
+            Value::BoundMethod(method) => {
+                self.stack[callee_slot] = method.receiver.clone();
+                self.call(method.method.clone(), argument_count)
+            }
+            Value::Class(class) => {
+                self.stack[callee_slot] = Value::Instance(tracked(Instance {
+                    class: class.clone(),
+                    fields: RefCell::new(HashMap::new()),
+                }));
+                let initializer = class.methods.borrow().get(INITIALIZER_NAME).cloned();
+                match initializer {
+                    Some(initializer) => self.call(initializer, argument_count),
+                    None if argument_count != 0 => {
+                        Err(self.error(ErrorKind::WrongNumberOfArguments {
+                            expected: 0,
+                            actual: argument_count,
+                        }))
+                    }
+                    None => Ok(()),
+                }
+            }
+            Value::Native(native) => {
+                native
+                    .arity()
+                    .check(argument_count)
+                    .map_err(|kind| self.error(kind))?;
+                let arguments = self
+                    .stack
+                    .split_off(callee_slot + 1)
+                    .into_iter()
+                    .map(Value::into_object)
+                    .collect();
+                self.pop();
+                let span = self.span();
+                let result = native.run(self, arguments, span)?;
+                self.stack.push(result.into());
+                Ok(())
+            }
+            called => Err(self.error(ErrorKind::AttemptedToCallUncallableExpression {
+                called: called.into_object(),
+            })),
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, argument_count: usize) -> Result<(), Error> {
+        if argument_count != closure.prototype.arity {
+            return Err(self.error(ErrorKind::WrongNumberOfArguments {
+                expected: closure.prototype.arity,
+                actual: argument_count,
+            }));
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.error(ErrorKind::StackOverflow));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base: self.stack.len() - argument_count - 1,
+        });
+        Ok(())
+    }
+
+    fn bind_method(&self, class: &Class, receiver: Value, name: String) -> Result<Value, Error> {
+        let Some(method) = class.methods.borrow().get(&name).cloned() else {
+            return Err(self.error(ErrorKind::UndefinedProperty { name }));
+        };
+        Ok(Value::BoundMethod(tracked(BoundMethod {
+            receiver,
+            method,
+        })))
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+        let upvalue = tracked(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let slot = match *upvalue.borrow() {
+                Upvalue::Open(slot) if slot >= from => slot,
+                _ => return true,
+            };
+            *upvalue.borrow_mut() = Upvalue::Closed(stack[slot].clone());
+            false
+        });
+    }
+
+    fn comparison(&mut self, operator: fn(Ordering) -> bool) -> Result<(), Error> {
+        let (left, right) = self.pop_pair();
+        let result = left
+            .into_object()
+            .compare(&right.into_object())
+            .map(|ordering| Object::Boolean(ordering.is_some_and(operator)));
+        self.push_result(result)
+    }
+
+    fn arithmetic(
+        &mut self,
+        operation: fn(Object, Object) -> Result<Object, ErrorKind>,
+    ) -> Result<(), Error> {
+        let (left, right) = self.pop_pair();
+        let result = left.number_value().and_then(|left| {
+            let right = right.number_value()?;
+            operation(Object::Number(left), Object::Number(right))
+        });
+        self.push_result(result)
+    }
+
+    fn push_result(&mut self, result: Result<Object, ErrorKind>) -> Result<(), Error> {
+        let object = result.map_err(|kind| self.error(kind))?;
+        self.stack.push(Value::Object(object));
+        Ok(())
+    }
+
+    fn push_boolean(&mut self, boolean: bool) {
+        self.stack.push(Value::Object(Object::Boolean(boolean)));
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("the compiler keeps the stack balanced")
+    }
+
+    fn pop_pair(&mut self) -> (Value, Value) {
+        let right = self.pop();
+        let left = self.pop();
+        (left, right)
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.closure.prototype.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame_mut();
+        let value = frame.closure.prototype.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Constant {
+        let index = self.read_u16() as usize;
+        self.frame().closure.prototype.chunk.constants[index].clone()
+    }
+
+    fn read_string(&mut self) -> String {
+        let Constant::String(string) = self.read_constant() else {
+            unreachable!("the compiler only emits names as string constants")
+        };
+        string
+    }
+
+    /// Builds a runtime error located at the instruction being executed.
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error::new(kind, self.span())
+    }
+
+    /// The location of the instruction being executed.
+    fn span(&self) -> Span {
+        let frame = self.frame();
+        frame
+            .closure
+            .prototype
+            .chunk
+            .spans
+            .get(frame.ip.saturating_sub(1))
+            .copied()
+            .unwrap_or_else(Span::default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches;
+
+    fn run(source: &str) -> Result<Vm, Vec<result::Error>> {
+        let mut vm = Vm::new();
+        vm.eval_from_line(source, 1)?;
+        Ok(vm)
+    }
+
+    fn global(vm: &Vm, name: &str) -> String {
+        vm.globals[name].to_string()
+    }
+
+    #[test]
+    fn arithmetic_and_globals() {
+        let vm = run("var a = 1 + 2 * 3; var b = \"lo\" + \"x\";").unwrap();
+        assert_eq!(vm.globals["a"], Value::Object(Object::Number(7.0.into())));
+        assert_eq!(
+            vm.globals["b"],
+            Value::Object(Object::String("lox".to_string()))
+        );
+    }
+
+    #[test]
+    fn closures_share_captured_variables() {
+        let vm = run(
+            "fun counter() { var n = 0; fun inc() { n = n + 1; return n; } return inc; }
+             var c = counter(); c(); var result = c();",
+        )
+        .unwrap();
+        assert_eq!(
+            vm.globals["result"],
+            Value::Object(Object::Number(2.0.into()))
+        );
+    }
+
+    #[test]
+    fn classes_and_super() {
+        let vm = run(
+            "class A { init(x) { this.x = x; } get() { return this.x; } }
+             class B < A { get() { return super.get() + 1; } }
+             var b = B(41); var result = b.get();",
+        )
+        .unwrap();
+        assert_eq!(global(&vm, "b"), "B instance");
+        assert_eq!(
+            vm.globals["result"],
+            Value::Object(Object::Number(42.0.into()))
+        );
+    }
+
+    #[test]
+    fn runtime_error_has_span() {
+        let errors = run("var a = 1;\nvar b = a + nil;").err().unwrap();
+        let [result::Error::EvaluationError(error)] = errors.as_slice() else {
+            panic!("expected a single runtime error, got {errors:?}");
+        };
+        assert_matches!(error.kind, ErrorKind::ExpectedNumber { .. });
+        assert_eq!(error.span.line, 2);
+        assert_eq!(error.span.column, 11);
+    }
+
+    #[test]
+    fn mixed_equality_and_comparison() {
+        let vm =
+            run("fun f() {} var a = 1 == \"1\"; var b = f == f; var c = nil != false;").unwrap();
+        assert_eq!(global(&vm, "a"), "false");
+        assert_eq!(global(&vm, "b"), "true");
+        assert_eq!(global(&vm, "c"), "true");
+        let errors = run("fun f() {} f < 1;").err().unwrap();
+        assert_matches!(
+            errors.as_slice(),
+            [result::Error::EvaluationError(Error {
+                kind: ErrorKind::CannotCompare { .. },
+                ..
+            })]
+        );
+    }
+
+    #[test]
+    fn wrong_number_of_arguments() {
+        let errors = run("fun f(a) {} f(1, 2);").err().unwrap();
+        assert_matches!(
+            errors.as_slice(),
+            [result::Error::EvaluationError(Error {
+                kind: ErrorKind::WrongNumberOfArguments {
+                    expected: 1,
+                    actual: 2
+                },
+                ..
+            })]
+        );
+    }
+
+    #[test]
+    fn deep_recursion_overflows() {
+        let errors = run("fun f() { f(); } f();").err().unwrap();
+        assert_matches!(
+            errors.as_slice(),
+            [result::Error::EvaluationError(Error {
+                kind: ErrorKind::StackOverflow,
+                ..
+            })]
+        );
+    }
+
+    #[test]
+    fn state_is_kept_between_evaluations() {
+        let mut vm = Vm::new();
+        vm.eval_from_line("var a = 1;", 1).unwrap();
+        assert!(vm.eval_from_line("a = a + nil;", 2).is_err());
+        vm.eval_from_line("a = a + 1;", 3).unwrap();
+        assert_eq!(global(&vm, "a"), "2");
+    }
+
+    #[test]
+    fn print_writes_to_the_output() {
+        let mut vm = Vm::new();
+        let output = vm.capture_output();
+        vm.eval_from_line("print 1 + 2;", 1).unwrap();
+        assert_eq!(output.contents(), "3\n");
+
+        struct Closed;
+        impl Write for Closed {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        vm.set_output(Closed);
+        let errors = vm.eval_from_line("print 1;", 1).unwrap_err();
+        assert_matches!(
+            errors.as_slice(),
+            [result::Error::EvaluationError(Error {
+                kind: ErrorKind::FailedToWriteOutput { .. },
+                ..
+            })]
+        );
+    }
+
+    #[test]
+    fn lists() {
+        let vm =
+            run("var xs = [1, [2]]; xs[0] = xs[1][0] + 1; var same = xs == [3, [2]];").unwrap();
+        assert_eq!(global(&vm, "xs"), "[3, [2]]");
+        assert_eq!(global(&vm, "same"), "true");
+    }
+
+    #[test]
+    fn list_index_errors() {
+        let errors = run("var xs = [1];\nxs[2];").err().unwrap();
+        assert_matches!(
+            errors.as_slice(),
+            [result::Error::EvaluationError(Error {
+                kind: ErrorKind::IndexOutOfBounds {
+                    index: 2,
+                    length: 1
+                },
+                span,
+            })] if span.line == 2
+        );
+        let errors = run("nil[0] = 1;").err().unwrap();
+        assert_matches!(
+            errors.as_slice(),
+            [result::Error::EvaluationError(Error {
+                kind: ErrorKind::NotIndexable { .. },
+                ..
+            })]
+        );
+    }
+
+    #[test]
+    fn natives_call_back_into_the_vm() {
+        let vm =
+            run("var xs = [1, 2]; push(xs, 3); fun f(x) { return x * 2; } var ys = map(xs, f);")
+                .unwrap();
+        assert_eq!(global(&vm, "xs"), "[1, 2, 3]");
+        assert_eq!(global(&vm, "ys"), "[2, 4, 6]");
+        let errors = run("fun f(x) {\n  return x + nil;\n}\nmap([1], f);")
+            .err()
+            .unwrap();
+        assert_matches!(
+            errors.as_slice(),
+            [result::Error::EvaluationError(Error {
+                kind: ErrorKind::ExpectedNumber { .. },
+                span,
+            })] if span.line == 2
+        );
+    }
+
+    #[test]
+    fn cycles_are_collected() {
+        let vm = run(
+            "fun outer() { fun inner() { return inner; } return inner; } outer();\n\
+             class Node { init() { this.next = this; this.method = this.init; } } Node();",
+        )
+        .unwrap();
+        // The closure and its upvalue, then the instance and the method bound to it.
+        assert_eq!(crate::heap::collect().collected, 4);
+        drop(vm);
+    }
+
+    #[test]
+    fn maps() {
+        let vm = run(
+            "var m = {\"a\": 1}; m[\"b\"] = m[\"a\"] + 1; var same = m == {\"b\": 2, \"a\": 1};",
+        )
+        .unwrap();
+        assert_eq!(global(&vm, "m"), "{\"a\": 1, \"b\": 2}");
+        assert_eq!(global(&vm, "same"), "true");
+        let errors = run("var m = {};\nm[nil];").err().unwrap();
+        assert_matches!(
+            errors.as_slice(),
+            [result::Error::EvaluationError(Error {
+                kind: ErrorKind::KeyNotFound { .. },
+                span,
+            })] if span.line == 2
+        );
+    }
+}
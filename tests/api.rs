@@ -0,0 +1,300 @@
+//! Uses the interpreter the way an embedding Rust program would.
+
+use rust_lox::{
+    analyze_syntax_tree, argument, call, dump_syntax_tree, parse_syntax_tree, Arity, Backend,
+    Callback, Error, ErrorKind, Interpreter, Object, RuntimeError, Statement, SYNTAX_TREE_VERSION,
+};
+
+fn number(value: f64) -> Object {
+    Object::Number(value.into())
+}
+
+#[test]
+fn globals_persist_between_evaluations() {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("var a = 1;").unwrap();
+    interpreter.eval("a = a + 1;").unwrap();
+    assert_eq!(interpreter.get_global("a"), Some(number(2.0)));
+    assert_eq!(interpreter.get_global("b"), None);
+}
+
+#[test]
+fn globals_set_from_rust_are_visible_to_lox() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_global("name", Object::String("Lox".to_string()));
+    interpreter
+        .eval("var greeting = \"Hello, \" + name;")
+        .unwrap();
+    assert_eq!(
+        interpreter.get_global("greeting"),
+        Some(Object::String("Hello, Lox".to_string()))
+    );
+}
+
+#[test]
+fn print_output_can_be_captured() {
+    let mut interpreter = Interpreter::new();
+    let output = interpreter.capture_output();
+    interpreter.eval("print \"one\"; print \"two\";").unwrap();
+    assert_eq!(output.contents(), "one\ntwo\n");
+}
+
+#[test]
+fn native_functions_can_be_called_from_lox() {
+    let mut interpreter = Interpreter::new();
+    interpreter.define_native("add", 2, |_, arguments| {
+        let left: f64 = argument(&arguments, 0)?;
+        let right: f64 = argument(&arguments, 1)?;
+        Ok(Object::Number((left + right).into()))
+    });
+    interpreter.eval("var sum = add(1, 2);").unwrap();
+    assert_eq!(interpreter.get_global("sum"), Some(number(3.0)));
+}
+
+#[test]
+fn native_function_errors_are_runtime_errors() {
+    let mut interpreter = Interpreter::new();
+    interpreter.define_native("fail", 0, |_, _| Err(ErrorKind::DivisionByZero.into()));
+    let errors = interpreter.eval("\n  fail();").unwrap_err();
+    let [Error::EvaluationError(RuntimeError { kind, span })] = errors.as_slice() else {
+        panic!("expected one runtime error, got {errors:?}");
+    };
+    assert!(matches!(kind, ErrorKind::DivisionByZero));
+    assert_eq!((span.line, span.column), (2, 8));
+}
+
+#[test]
+fn native_function_arity_is_checked() {
+    let mut interpreter = Interpreter::new();
+    interpreter.define_native("one", 1, |_, _| Ok(Object::Nil));
+    let errors = interpreter.eval("one();").unwrap_err();
+    assert!(matches!(
+        errors.as_slice(),
+        [Error::EvaluationError(RuntimeError {
+            kind: ErrorKind::WrongNumberOfArguments {
+                expected: 1,
+                actual: 0
+            },
+            ..
+        })]
+    ));
+}
+
+#[test]
+fn syntax_errors_are_all_returned() {
+    let mut interpreter = Interpreter::new();
+    let errors = interpreter.eval("var = 1;\nprint ;").unwrap_err();
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn eval_file() {
+    let mut interpreter = Interpreter::new();
+    let output = interpreter.capture_output();
+    interpreter
+        .eval_file("tests/programs/closures.lox")
+        .unwrap();
+    let expected = std::fs::read_to_string("tests/programs/closures.out").unwrap();
+    assert_eq!(output.contents(), expected);
+}
+
+#[test]
+fn eval_missing_file() {
+    let mut interpreter = Interpreter::new();
+    let errors = interpreter
+        .eval_file("tests/programs/missing.lox")
+        .unwrap_err();
+    assert!(matches!(errors.as_slice(), [Error::IoError(_)]));
+}
+
+#[test]
+fn native_functions_can_call_back_into_lox() {
+    let mut interpreter = Interpreter::new();
+    interpreter.define_native("apply", Arity::AtLeast(1), |runtime, arguments| {
+        let function: Callback = argument(&arguments, 0)?;
+        call(runtime, &function, arguments[1..].to_vec())
+    });
+    interpreter
+        .eval("fun add(a, b) { return a + b; } var sum = apply(add, 1, 2);")
+        .unwrap();
+    assert_eq!(interpreter.get_global("sum"), Some(number(3.0)));
+}
+
+#[test]
+fn native_argument_type_errors() {
+    let mut interpreter = Interpreter::new();
+    interpreter.define_native("negate", 1, |_, arguments| {
+        let value: f64 = argument(&arguments, 0)?;
+        Ok(number(-value))
+    });
+    let errors = interpreter.eval("negate(\"a\");").unwrap_err();
+    assert_eq!(
+        errors[0].to_string(),
+        "Argument 1 must be a number, found \"a\"."
+    );
+}
+
+#[test]
+fn garbage_collection_frees_cycles_but_keeps_globals() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval("var kept = []; push(kept, kept); { var lost = []; push(lost, lost); }")
+        .unwrap();
+    let stats = interpreter.collect_garbage();
+    assert_eq!(stats.collections, 1);
+    assert!(stats.collected >= 1);
+    assert_eq!(interpreter.gc_stats().live, stats.live);
+    interpreter.eval("print len(kept);").unwrap();
+}
+
+#[test]
+fn identical_expressions_from_different_evaluations_resolve_separately() {
+    let mut interpreter = Interpreter::new();
+    let output = interpreter.capture_output();
+    // Both `x` are at the same position, but one is a local and the other a global.
+    interpreter
+        .eval("var get; { var x = \"block\"; fun f() { return x; } get = f; }")
+        .unwrap();
+    interpreter.eval("var x = \"global\";").unwrap();
+    interpreter
+        .eval("var out; { var y = \"other\"; { { {      print x; } } } }")
+        .unwrap();
+    interpreter.eval("print get();").unwrap();
+    assert_eq!(output.contents(), "global\nblock\n");
+}
+
+#[test]
+fn top_level_return_ends_the_script() {
+    let mut interpreter = Interpreter::new();
+    let output = interpreter.capture_output();
+    interpreter
+        .eval("print \"before\"; { return 1; } print \"after\";")
+        .unwrap();
+    assert_eq!(output.contents(), "before\n");
+}
+
+#[test]
+fn return_unwinds_loops_and_blocks() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval(
+            "fun find() { for (var i = 0; i < 10; i = i + 1) { { if (i == 3) return i; } } }
+             var found = find();",
+        )
+        .unwrap();
+    assert_eq!(interpreter.get_global("found"), Some(number(3.0)));
+}
+
+#[test]
+fn errors_in_blocks_leave_the_global_scope_active() {
+    let mut interpreter = Interpreter::new();
+    assert!(interpreter.eval("{ var a = 1; a(); }").is_err());
+    interpreter.eval("var b = 2;").unwrap();
+    assert_eq!(interpreter.get_global("b"), Some(number(2.0)));
+}
+
+#[test]
+fn values_of_different_types_are_unequal_but_cannot_be_ordered() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval("fun f() {} var a = 1 == \"1\"; var b = f == f; var c = clock == f;")
+        .unwrap();
+    assert_eq!(interpreter.get_global("a"), Some(Object::Boolean(false)));
+    assert_eq!(interpreter.get_global("b"), Some(Object::Boolean(true)));
+    assert_eq!(interpreter.get_global("c"), Some(Object::Boolean(false)));
+    let errors = interpreter.eval("\"a\" < \"b\";").unwrap_err();
+    assert!(matches!(
+        errors.as_slice(),
+        [Error::EvaluationError(RuntimeError {
+            kind: ErrorKind::CannotCompare { .. },
+            ..
+        })]
+    ));
+}
+
+#[test]
+fn syntax_trees_can_be_dumped_and_run_without_source() {
+    let json = dump_syntax_tree("var a = 1; a = a + 1;").unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .run(analyze_syntax_tree(&json).unwrap())
+        .unwrap();
+    assert_eq!(interpreter.get_global("a"), Some(number(2.0)));
+    assert_eq!(
+        dump_syntax_tree("var a = 1; a = a + 1;").unwrap(),
+        json,
+        "ids are not part of the format"
+    );
+}
+
+#[test]
+fn parsing_keeps_the_statements_around_syntax_errors() {
+    let parsed = parse_syntax_tree("print 1;\nprint (2;\nvar a = 3;");
+    assert!(matches!(
+        parsed.errors.as_slice(),
+        [Error::UnmatchedParenthesis { .. }]
+    ));
+    assert!(matches!(
+        parsed.statements.as_slice(),
+        [
+            Statement::Print { .. },
+            Statement::VariableDeclaration { .. }
+        ]
+    ));
+}
+
+#[test]
+fn syntax_trees_written_by_hand_need_no_spans() {
+    let json = r#"{"version": 1, "statements": [
+        {"variable_declaration": {"identifier": "a", "expression": {"literal": {"string": "hi"}}}},
+        {"expression": {"assignment": {"identifier": "a", "value": {"binary": {
+            "left": {"variable": {"identifier": "a"}},
+            "operator": "+",
+            "right": {"literal": {"string": "!"}}
+        }}}}}
+    ]}"#;
+    let mut interpreter = Interpreter::new();
+    interpreter.run(analyze_syntax_tree(json).unwrap()).unwrap();
+    assert_eq!(
+        interpreter.get_global("a"),
+        Some(Object::String("hi!".to_string()))
+    );
+}
+
+#[test]
+fn syntax_trees_from_other_versions_are_rejected() {
+    let json = format!(
+        r#"{{"version": {}, "statements": []}}"#,
+        SYNTAX_TREE_VERSION + 1
+    );
+    let errors = analyze_syntax_tree(&json).err().unwrap();
+    assert_eq!(
+        errors[0].to_string(),
+        "Invalid syntax tree: version 2 is not supported, only version 1 is."
+    );
+}
+
+#[test]
+fn invalid_syntax_trees_are_errors() {
+    let errors = analyze_syntax_tree(r#"{"version": 1, "statements": [{"loop": {}}]}"#)
+        .err()
+        .unwrap();
+    assert!(matches!(
+        errors.as_slice(),
+        [Error::InvalidSyntaxTree { .. }]
+    ));
+    let errors = analyze_syntax_tree(r#"[{"print": {"literal": "nil"}}]"#)
+        .err()
+        .unwrap();
+    assert!(matches!(
+        errors.as_slice(),
+        [Error::InvalidSyntaxTree { .. }]
+    ));
+    let errors = analyze_syntax_tree(r#"{"version": 1, "statements": [{"break": {}}]}"#)
+        .err()
+        .unwrap();
+    assert!(matches!(
+        errors.as_slice(),
+        [Error::BreakOutsideLoop { .. }]
+    ));
+}
@@ -0,0 +1,65 @@
+//! Runs `--check` on files, which reports their errors and warnings without running them.
+
+mod common;
+
+use common::{lox, TempDir};
+use std::path::PathBuf;
+use std::process::Output;
+
+fn check(arguments: &[&str]) -> Output {
+    lox()
+        .arg("--check")
+        .args(arguments)
+        .output()
+        .expect("the interpreter binary should run")
+}
+
+#[test]
+fn programs_that_fail_at_runtime_pass_the_check() {
+    let program = PathBuf::from("tests/programs/runtime_error.lox");
+    let output = check(&[program.to_str().unwrap()]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert!(
+        !stdout.contains("before"),
+        "the program should not run: {stdout}"
+    );
+    assert_eq!(stdout, "Checked 1 file: 0 errors, 0 warnings.\n");
+}
+
+#[test]
+fn every_file_is_checked_and_errors_fail_the_check() {
+    let directory = TempDir::new();
+    let warning = directory.write("warning.lox", "{ var unused; }\n");
+    let warning = warning.to_str().unwrap();
+    let error = directory.write("error.lox", "print (1;\n");
+    let error = error.to_str().unwrap();
+    let output = check(&[warning, error]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(exitcode::USAGE), "{stdout}");
+    assert!(stdout.contains(&format!("{warning}:1:7")), "{stdout}");
+    assert!(stdout.contains(&format!("{error}:1:9")), "{stdout}");
+    assert!(
+        stdout.ends_with("Checked 2 files: 1 error, 1 warning.\n"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn json_output_lists_every_diagnostic() {
+    let directory = TempDir::new();
+    let warning = directory.write("json.lox", "{ var unused; }\nbreak;\n");
+    let warning = warning.to_str().unwrap();
+    let output = check(&[warning, "--format", "json"]);
+    let diagnostics: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let codes = diagnostics
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|diagnostic| diagnostic["code"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(codes, ["W0200", "E0205"]);
+    assert_eq!(diagnostics[1]["file"], warning);
+    assert_eq!(diagnostics[1]["severity"], "error");
+    assert_eq!(output.status.code(), Some(exitcode::USAGE));
+}
@@ -0,0 +1,95 @@
+//! Formats the programs in `tests/programs` and runs `fmt` on files.
+
+mod common;
+
+use common::{lox, programs, TempDir};
+use rust_lox::dump_syntax_tree;
+use rust_lox::formatter::{format, NOT_FORMATTED};
+use serde_json::Value;
+use std::fs;
+use std::process::Output;
+
+/// The syntax tree of the source as JSON, without the locations that formatting changes.
+fn syntax_tree(source: &str) -> Value {
+    fn remove_spans(value: &mut Value) {
+        match value {
+            Value::Object(object) => {
+                object.remove("span");
+                object.values_mut().for_each(remove_spans);
+            }
+            Value::Array(array) => array.iter_mut().for_each(remove_spans),
+            _ => {}
+        }
+    }
+    let mut tree = serde_json::from_str(&dump_syntax_tree(source).unwrap()).unwrap();
+    remove_spans(&mut tree);
+    tree
+}
+
+fn fmt(arguments: &[&str]) -> Output {
+    lox()
+        .arg("fmt")
+        .args(arguments)
+        .output()
+        .expect("the interpreter binary should run")
+}
+
+#[test]
+fn formatted_programs_parse_to_the_same_syntax_tree() {
+    for program in programs() {
+        let source = fs::read_to_string(&program).unwrap();
+        let formatted = format(&source).unwrap();
+        assert_eq!(
+            syntax_tree(&formatted),
+            syntax_tree(&source),
+            "{}:\n{formatted}",
+            program.display()
+        );
+    }
+}
+
+#[test]
+fn formatting_twice_changes_nothing() {
+    for program in programs() {
+        let formatted = format(&fs::read_to_string(&program).unwrap()).unwrap();
+        assert_eq!(
+            format(&formatted).unwrap(),
+            formatted,
+            "{}",
+            program.display()
+        );
+    }
+}
+
+#[test]
+fn fmt_rewrites_files_and_check_only_reports_them() {
+    let directory = TempDir::new();
+    let file = directory.write("messy.lox", "var a=1; // one\nif(a){print a;}\n");
+    let file = file.to_str().unwrap();
+    let formatted = "var a = 1; // one\nif (a) {\n    print a;\n}\n";
+
+    let output = fmt(&["--check", file]);
+    assert_eq!(output.status.code(), Some(NOT_FORMATTED));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{file} is not formatted\n")
+    );
+    assert_ne!(fs::read_to_string(file).unwrap(), formatted);
+
+    assert!(fmt(&[file]).status.success());
+    assert_eq!(fs::read_to_string(file).unwrap(), formatted);
+    assert!(fmt(&["--check", file]).status.success());
+}
+
+#[test]
+fn files_with_errors_are_left_alone() {
+    let source = "print (1;\n";
+    let directory = TempDir::new();
+    let broken = directory.write("broken.lox", source);
+    let formatted = directory.write("formatted.lox", "print 1;\n");
+    let output = fmt(&[broken.to_str().unwrap(), formatted.to_str().unwrap()]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(exitcode::USAGE), "{stdout}");
+    assert!(stdout.contains("error[E0005]"), "{stdout}");
+    assert_eq!(fs::read_to_string(&broken).unwrap(), source);
+}
@@ -0,0 +1,117 @@
+//! Runs every program in `tests/programs` on both backends and compares the output with the
+//! `.out` file next to it.
+
+mod common;
+
+use common::{programs, TempDir};
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+const BACKENDS: [&str; 2] = ["tree-walk", "vm"];
+
+fn run(backend: &str, program: &Path) -> String {
+    lox(&["--backend", backend], program)
+}
+
+fn lox(arguments: &[&str], file: &Path) -> String {
+    String::from_utf8(lox_output(arguments, file).stdout).expect("output should be valid UTF-8")
+}
+
+fn lox_output(arguments: &[&str], file: &Path) -> Output {
+    common::lox()
+        .args(arguments)
+        .arg(file)
+        .output()
+        .expect("the interpreter binary should run")
+}
+
+#[test]
+fn programs_produce_expected_output_on_every_backend() {
+    for program in programs() {
+        let expected = fs::read_to_string(program.with_extension("out")).unwrap();
+        for backend in BACKENDS {
+            assert_eq!(
+                run(backend, &program),
+                expected,
+                "{} on the {backend} backend",
+                program.display()
+            );
+        }
+    }
+}
+
+/// Programs run from their dumped syntax tree print the same, except that errors can't show the
+/// source line they point to.
+#[test]
+fn programs_run_the_same_from_their_syntax_tree() {
+    let directory = TempDir::new();
+    for program in programs() {
+        let name = program.file_stem().unwrap().to_str().unwrap();
+        let tree = directory.write(&format!("{name}.json"), &lox(&["--dump-ast"], &program));
+        let expected = fs::read_to_string(program.with_extension("out"))
+            .unwrap()
+            .replace(program.to_str().unwrap(), tree.to_str().unwrap())
+            .lines()
+            .filter(|line| !is_snippet(line))
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+        for backend in BACKENDS {
+            assert_eq!(
+                lox(&["--backend", backend, "--from-ast"], &tree),
+                expected,
+                "{} on the {backend} backend",
+                program.display()
+            );
+        }
+    }
+}
+
+#[test]
+fn programs_with_errors_fail() {
+    let program = Path::new("tests/programs/runtime_error.lox");
+    for backend in BACKENDS {
+        let output = lox_output(&["--backend", backend], program);
+        assert_eq!(output.status.code(), Some(exitcode::USAGE), "{backend}");
+    }
+    let output = lox_output(&[], Path::new("tests/programs/arithmetic.lox"));
+    assert!(output.status.success());
+}
+
+#[test]
+fn syntax_tree_errors_fail() {
+    let directory = TempDir::new();
+    let source = directory.write("invalid.lox", "print (1;\n");
+    let output = lox_output(&["--dump-ast"], &source);
+    assert_eq!(output.status.code(), Some(exitcode::USAGE));
+
+    let tree = directory.write("invalid.json", "[{\"print\": ");
+    let output = lox_output(&["--from-ast"], &tree);
+    assert_eq!(output.status.code(), Some(exitcode::DATAERR));
+}
+
+#[test]
+fn errors_in_trees_without_spans_have_no_location() {
+    let directory = TempDir::new();
+    let tree = directory.write(
+        "break.json",
+        r#"{"version": 1, "statements": [{"break": {}}]}"#,
+    );
+    let output = lox_output(&["--from-ast"], &tree);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(exitcode::USAGE), "{stdout}");
+    assert!(
+        stdout.contains(&format!("--> {}\n", tree.display())),
+        "{stdout}"
+    );
+}
+
+/// Whether the line is part of the source shown under an error, like `3 | print 1 + nil;`.
+fn is_snippet(line: &str) -> bool {
+    line.split_once('|').is_some_and(|(gutter, _)| {
+        gutter
+            .trim()
+            .chars()
+            .all(|character| character.is_ascii_digit())
+    })
+}
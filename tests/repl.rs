@@ -0,0 +1,110 @@
+//! Drives the REPL through standard input on both backends. Without a terminal there are no
+//! prompts, so the output is only what the input prints.
+
+mod common;
+
+use common::{lox, TempDir};
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+
+const BACKENDS: [&str; 2] = ["tree-walk", "vm"];
+
+/// Runs the REPL with a home directory of its own for each test, so history from other runs
+/// doesn't leak in.
+fn repl(backend: &str, home: &Path, input: &str) -> String {
+    let mut child = lox()
+        .arg("--backend")
+        .arg(backend)
+        .env("HOME", home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("the interpreter binary should run");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).expect("output should be valid UTF-8")
+}
+
+#[test]
+fn expressions_show_their_value() {
+    let home = TempDir::new();
+    for backend in BACKENDS {
+        let output = repl(
+            backend,
+            home.path(),
+            "var a = 1;\na = 2;\n\"two\";\n[a, \"a\"];\nprint \"printed\";\n",
+        );
+        assert_eq!(output, "2\n\"two\"\n[2, \"a\"]\nprinted\n", "{backend}");
+    }
+}
+
+#[test]
+fn unbalanced_input_continues_on_the_next_line() {
+    let home = TempDir::new();
+    for backend in BACKENDS {
+        let output = repl(
+            backend,
+            home.path(),
+            "fun add(a,\n  b) {\n  return a + b;\n}\nadd(1, 2);\nprint \"a\nb\";\n",
+        );
+        assert_eq!(output, "3\na\nb\n", "{backend}");
+    }
+}
+
+#[test]
+fn errors_keep_the_repl_running() {
+    let home = TempDir::new();
+    for backend in BACKENDS {
+        let output = repl(backend, home.path(), "1 + nil;\n1 + 1;\n");
+        assert!(output.contains("error[E0401]"), "{output}");
+        assert!(output.ends_with("\n2\n"), "{output}");
+    }
+}
+
+#[test]
+fn history_is_saved_in_the_home_directory() {
+    let home = TempDir::new();
+    repl("tree-walk", home.path(), "var a = 1;\n{\n}\n");
+    let history = std::fs::read_to_string(home.path().join(".lox_history")).unwrap();
+    assert!(history.contains("var a = 1;"), "{history}");
+    // Multi-line entries are kept as one entry, with the line break escaped.
+    assert!(history.contains("{\\n}"), "{history}");
+}
+
+#[test]
+fn commands_inspect_and_change_the_session() {
+    let home = TempDir::new();
+    let script = home.write("script.lox", "var loaded = \"yes\";\n");
+    for backend in BACKENDS {
+        let input = format!(
+            "var a = [1, \"x\"];\n:globals\n:load {}\nloaded;\n:reset\n:globals\n",
+            script.display()
+        );
+        let output = repl(backend, home.path(), &input);
+        let (before_reset, after_reset) = output.split_once("\"yes\"\n").unwrap();
+        assert!(before_reset.contains("a = [1, \"x\"]\n"), "{output}");
+        assert!(!after_reset.contains("a = "), "{output}");
+        assert!(!after_reset.contains("loaded = "), "{output}");
+    }
+}
+
+#[test]
+fn commands_show_tokens_and_syntax_trees() {
+    let home = TempDir::new();
+    let output = repl("tree-walk", home.path(), ":tokens print x;\n:ast 1 + 2\n");
+    assert!(
+        output.starts_with("1:1 Print\n1:7 Identifier(\"x\")\n"),
+        "{output}"
+    );
+    assert!(output.contains("\"binary\": {"), "{output}");
+    let output = repl("vm", home.path(), ":time 1 + 2\n:what\n");
+    assert!(output.starts_with("3\ntook "), "{output}");
+    assert!(output.contains("Unknown command :what."), "{output}");
+}